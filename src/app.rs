@@ -1,13 +1,51 @@
-use std::{any::Any, collections::{HashMap, HashSet}, hint::black_box, sync::{atomic::{AtomicUsize, Ordering}, mpsc::{Receiver, RecvError, Sender}, Arc}};
+use std::{collections::{HashMap, HashSet}, sync::{atomic::{AtomicUsize, Ordering}, mpsc::{Receiver, Sender}, Arc}};
 
 use datazoo::Bimultimap;
-use egui::{include_image, Align, Color32, ImageSource, Layout, Pos2, RichText, Ui, Vec2};
-use egui_snarl::{ui::{BackgroundPattern, Grid, PinInfo, SnarlStyle, SnarlViewer, WireStyle}, NodeId, Snarl};
-use noise::{utils::{NoiseFnWrapper, PlaneMapBuilder}, NoiseFn};
+use egui::{Align, Color32, ImageSource, Key, KeyboardShortcut, Layout, Modifiers, Pos2, Rect, RichText, Sense, TextureHandle, TextureOptions, Ui, Vec2};
+use egui_snarl::{ui::{BackgroundPattern, Grid, PinInfo, SnarlStyle, SnarlViewer, Viewport}, InPinId, NodeId, OutPinId, Snarl};
 use serde::{Deserialize, Serialize};
 use slotmap::SlotMap;
 
-use crate::noises::{self, DynNoise, NoiseClassification, NoiseConfig, NoiseType};
+use strum::VariantArray;
+
+mod clipboard;
+mod explore;
+mod group;
+mod history;
+mod variations;
+mod volume;
+
+use explore::Explorer;
+use history::{GraphState, History, Snapshot};
+use variations::{SweepTarget, VariationGrid};
+use volume::VolumeViewer;
+
+use crate::{codegen::{self, shader::{ShaderLanguage, ShaderProgram}}, graph::{self, NoiseGraph, SavedNode, SavedWire, SeedMode}, group::Group, heightfield::{self, HeightfieldView}, library::{Preset, PresetLibrary}, noises::{self, NoiseConfig, NoiseType, PinType}, params::{BodyContext, ParamKind, ParamSpec}, random::Rng, render::{self, Animation, CompiledNoise, Dimensions, Histogram, Projection, SampleSettings}};
+
+/// Resolution of images written by "Export PNG".
+#[cfg(not(target_arch = "wasm32"))]
+const EXPORT_SIZE: usize = 1024;
+
+/// Resolution of the frames written by "Export animation", kept small as every frame is rendered.
+#[cfg(not(target_arch = "wasm32"))]
+const ANIMATION_EXPORT_SIZE: usize = 512;
+
+/// Number of bars in the histograms shown behind curve editors.
+const HISTOGRAM_BINS: usize = 64;
+
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+const DUPLICATE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::D);
+const GROUP_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::G);
+
+/// Identifies the graph's UI state, such as which nodes are selected.
+const GRAPH_ID_SALT: &str = "noise_graph";
+
+/// Previews repeat when shown 3x3, so the texture must wrap rather than clamp.
+const PREVIEW_TEXTURE_OPTIONS: TextureOptions = TextureOptions::LINEAR_REPEAT;
+
+/// Fill of scalar pins, which are also square, to tell them apart from noise pins.
+const SCALAR_PIN_COLOR: Color32 = Color32::from_rgb(0xe0, 0xa0, 0x40);
 
 slotmap::new_key_type! {
     pub struct NodeSlotKey;
@@ -33,12 +71,41 @@ pub struct NoiseExplorerApp {
     recalculate_sender: std::sync::mpsc::Sender<RecalculateRequest>,
     recalculate_receiver: std::sync::mpsc::Receiver<RecalculateResult>,
     slot_to_node: SlotMap<NodeSlotKey, NodeId>,
+    /// Nodes whose preview needs rendering again although their output hasn't changed, such as
+    /// animated nodes when the timeline moves.
+    restyled_nodes: HashSet<NodeSlotKey>,
+    history: History,
+    /// Groups that can be added from the graph menu. Saved with the graph.
+    group_library: Vec<Group>,
+    /// Added to the seeds of nodes in [`SeedMode::Offset`]. Saved with the graph.
+    master_seed: u32,
+    /// Presets that can be added from the graph menu. Shared between graphs, and saved to the
+    /// library file on native or the browser's storage on the web.
+    presets: PresetLibrary,
+    /// Whether saving the presets is safe. It isn't when an unreadable library couldn't be moved
+    /// aside, as saving would overwrite it.
+    presets_writable: bool,
+    /// A preset waiting for the user to name it before it's saved.
+    preset_prompt: Option<Preset>,
+    /// Title and text of the shader validation report window, when open.
+    shader_report: Option<(String, String)>,
+    /// Where pasted nodes go in graph coordinates: under the pointer, or in the middle of the view
+    /// when the pointer is elsewhere. Updated whenever the graph is drawn.
+    paste_position: Pos2,
+    volume_viewer: Option<VolumeViewer>,
+    variation_grid: Option<VariationGrid>,
+    explorer: Option<Explorer>,
+    animation: Animation,
+    /// Position of the timeline in seconds.
+    time: f64,
+    playing: bool,
 }
 
 impl NoiseExplorerApp {
-    fn default(ctx: egui::Context) -> Self {
+    fn default(ctx: egui::Context, storage: Option<&dyn eframe::Storage>) -> Self {
         let (request_tx, request_rx) = std::sync::mpsc::channel();
         let (response_tx, response_rx) = std::sync::mpsc::channel();
+        let (presets, presets_writable) = load_presets(storage);
 
         std::thread::Builder::new()
             .name("Recalculator".to_string())
@@ -66,10 +133,63 @@ impl NoiseExplorerApp {
             recalculate_sender: request_tx,
             recalculate_receiver: response_rx,
             slot_to_node: SlotMap::with_key(),
+            restyled_nodes: HashSet::new(),
+            history: History::default(),
+            group_library: Vec::new(),
+            master_seed: 0,
+            presets,
+            presets_writable,
+            preset_prompt: None,
+            shader_report: None,
+            paste_position: Pos2::ZERO,
+            volume_viewer: None,
+            variation_grid: None,
+            explorer: None,
+            animation: Animation::default(),
+            time: 0.0,
+            playing: false,
         }
     }
 }
 
+/// Where the preset library is kept in the browser's storage.
+#[cfg(target_arch = "wasm32")]
+const PRESETS_KEY: &str = "presets";
+
+/// Loads the user's preset library, and whether it can be saved. A missing library is the same as
+/// an empty library. An unreadable library file is moved aside to a backup, or if that fails, left
+/// alone by not saving presets at all.
+#[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+fn load_presets(storage: Option<&dyn eframe::Storage>) -> (PresetLibrary, bool) {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = crate::library::default_path().filter(|path| path.exists()) {
+        match PresetLibrary::load(&path) {
+            Ok(presets) => return (presets, true),
+            Err(err) => {
+                log::error!("Failed to load presets from {}: {err}", path.display());
+                let mut backup = path.clone().into_os_string();
+                backup.push(".bak");
+                if let Err(err) = std::fs::rename(&path, &backup) {
+                    log::error!("Failed to back up {}, presets won't be saved: {err}", path.display());
+                    return (PresetLibrary::default(), false);
+                }
+                log::warn!("Moved the unreadable presets to {}", backup.to_string_lossy());
+            }
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    if let Some(json) = storage.and_then(|storage| storage.get_string(PRESETS_KEY)) {
+        match PresetLibrary::from_json(&json) {
+            Ok(presets) => return (presets, true),
+            Err(err) => {
+                log::error!("Failed to load presets, they won't be saved: {err}");
+                return (PresetLibrary::default(), false);
+            }
+        }
+    }
+    (PresetLibrary::default(), true)
+}
+
 fn recalculator_thread(request_rx: Receiver<RecalculateRequest>, response_tx: Sender<RecalculateResult>, ctx: egui::Context) {
     loop {
         let Ok(request) = request_rx.recv() else { break };
@@ -78,37 +198,30 @@ fn recalculator_thread(request_rx: Receiver<RecalculateRequest>, response_tx: Se
             // This request has been superseded, skip it.
             continue;
         }
-        
-        let mut image_colors = Vec::with_capacity(request.texture_width * request.texture_height);
-        let mut noise_min = std::f64::MAX;
-        let mut noise_max = std::f64::MIN;
-        for y in 0..request.texture_height {
-            for x in 0..request.texture_width {
-                let noise_val = request.noise_fn.get([
-                    x as f64 / request.texture_width as f64 * request.noise_width,
-                    y as f64 / request.texture_height as f64 * request.noise_height
-                ]);
-                if noise_val < noise_min {
-                    noise_min = noise_val;
-                }
-                if noise_val > noise_max {
-                    noise_max = noise_val;
-                }
-                // This assumes the noise is in the range [-1, 1].
-                let noise_u8 = ((noise_val * 0.5 + 0.5).clamp(0.0, 1.0) * 255.0) as u8;
-                image_colors.push(egui::Color32::from_gray(noise_u8));
-            }
-        }
 
-        // TODO: somehow actually convert this into a texture egui can display.
-        std::hint::black_box(image_colors);
+        let noise_map = render::sample(&request.noise_fn, &request.sample_settings);
+        let (noise_min, noise_max) = render::value_range(&noise_map);
+        let histogram = render::histogram(&noise_map, HISTOGRAM_BINS);
+        let (width, height) = noise_map.size();
+        let texture = match &request.heightfield {
+            Some(view) => {
+                let image = heightfield::render(&noise_map, view, render::PREVIEW_SIZE, render::PREVIEW_SIZE);
+                egui::ColorImage::from_rgb([image.width() as usize, image.height() as usize], image.as_raw())
+            }
+            None => {
+                let gray: Vec<u8> = noise_map.iter().map(|&noise_val| render::to_gray(noise_val)).collect();
+                egui::ColorImage::from_gray([width, height], &gray)
+            }
+        };
 
         if response_tx.send(RecalculateResult {
             node_id: request.node_id,
+            target: request.target,
             new_version: request.new_version,
             noise_max,
             noise_min,
-            texture: ()
+            histogram,
+            texture,
         }).is_ok() {
             ctx.request_repaint();
         };
@@ -116,13 +229,213 @@ fn recalculator_thread(request_rx: Receiver<RecalculateRequest>, response_tx: Se
 }
 
 impl NoiseExplorerApp {
+    /// Replaces the current graph with the contents of a saved graph.
+    fn load_graph(&mut self, graph: &NoiseGraph) {
+        self.node_graph = Snarl::new();
+        self.slot_to_node.clear();
+        self.changed_nodes.clear();
+        self.restyled_nodes.clear();
+        let node_ids: Vec<NodeId> = graph
+            .nodes()
+            .iter()
+            .map(|saved| insert_graph_node(
+                &mut self.node_graph,
+                &mut self.slot_to_node,
+                &mut self.changed_nodes,
+                Pos2::from(saved.position),
+                |key| GraphNode::from_saved(key, saved)))
+            .collect();
+        for wire in graph.wires() {
+            let (Some(&from), Some(&to)) = (node_ids.get(wire.from), node_ids.get(wire.to)) else { continue };
+            self.node_graph.connect(OutPinId { node: from, output: wire.output }, InPinId { node: to, input: wire.input });
+        }
+    }
+
+    /// Writes the preset library back to its file. On the web they're written to the browser's
+    /// storage whenever eframe saves the app instead.
+    fn save_presets(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = crate::library::default_path().filter(|_| self.presets_writable) {
+            if let Err(err) = self.presets.save(&path) {
+                log::error!("Failed to save presets to {}: {err}", path.display());
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn import_presets(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Preset pack", &[crate::library::PACK_EXTENSION])
+            .pick_file() else { return };
+        match PresetLibrary::load(&path) {
+            Ok(pack) => {
+                self.presets.import(pack);
+                self.save_presets();
+            }
+            Err(err) => log::error!("Failed to import {}: {err}", path.display()),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_presets(&self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Preset pack", &[crate::library::PACK_EXTENSION])
+            .set_file_name(format!("presets.{}", crate::library::PACK_EXTENSION))
+            .save_file() else { return };
+        if let Err(err) = self.presets.save(&path) {
+            log::error!("Failed to export {}: {err}", path.display());
+        }
+    }
+
+    /// Shows the window asking for a new preset's name, if one is waiting.
+    fn show_preset_prompt(&mut self, ctx: &egui::Context) {
+        let Some(preset) = &mut self.preset_prompt else { return };
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new("Save preset").open(&mut open).collapsible(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                save |= ui.text_edit_singleline(&mut preset.name).lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+            });
+            if self.presets.contains(&preset.name) {
+                ui.label("Replaces the preset with this name.");
+            }
+            save |= ui.add_enabled(!preset.name.is_empty(), egui::Button::new("Save")).clicked();
+        });
+        if save && !preset.name.is_empty() {
+            if let Some(preset) = self.preset_prompt.take() {
+                self.presets.add(preset);
+                self.save_presets();
+            }
+        } else if !open {
+            self.preset_prompt = None;
+        }
+    }
+
+    fn graph_state(&mut self) -> GraphState<'_> {
+        GraphState {
+            snarl: &mut self.node_graph,
+            slot_to_node: &mut self.slot_to_node,
+            changed_nodes: &mut self.changed_nodes,
+            master_seed: &mut self.master_seed,
+        }
+    }
+
+    fn undo(&mut self) {
+        let mut history = std::mem::take(&mut self.history);
+        history.undo(&mut self.graph_state());
+        self.history = history;
+    }
+
+    fn redo(&mut self) {
+        let mut history = std::mem::take(&mut self.history);
+        history.redo(&mut self.graph_state());
+        self.history = history;
+    }
+
+    fn apply_edit_action(&mut self, ctx: &egui::Context, action: EditAction, selected: &[NodeId]) {
+        match action {
+            EditAction::Copy => {
+                if let Some(text) = clipboard::copy(&self.node_graph, selected) {
+                    ctx.copy_text(text);
+                }
+            }
+            EditAction::Paste(text) => {
+                clipboard::paste(&text, &mut self.node_graph, &mut self.slot_to_node, &mut self.changed_nodes, self.paste_position);
+            }
+            EditAction::Duplicate => {
+                clipboard::duplicate(&mut self.node_graph, &mut self.slot_to_node, &mut self.changed_nodes, selected);
+            }
+            EditAction::Group => group::collapse(&mut self.graph_state(), selected),
+            EditAction::SavePreset => self.preset_prompt = new_preset(&self.node_graph, selected),
+            EditAction::SetMasterSeed(master_seed) => self.graph_state().set_master_seed(master_seed),
+            EditAction::ReseedAll => {
+                let master_seed = self.master_seed.wrapping_add(1);
+                self.graph_state().set_master_seed(master_seed);
+                let mut rng = Rng::new(master_seed.into());
+                for node in self.node_graph.nodes_mut() {
+                    if graph::reseed_node(node.noise_type, &mut node.config, node.seed_mode, node.seed_locked, &mut rng) {
+                        self.changed_nodes.insert(node.node_id_key);
+                    }
+                }
+            }
+            EditAction::ApplyVariation(values) => {
+                for (target, value) in values {
+                    self.apply_sweep_value(target, value);
+                }
+            }
+            EditAction::ApplyGraph(graph) => {
+                self.load_graph(&graph);
+                self.master_seed = graph.master_seed();
+            }
+        }
+    }
+
+    /// Sets a value swept by the variation grid.
+    fn apply_sweep_value(&mut self, target: SweepTarget, value: f64) {
+        match target {
+            SweepTarget::MasterSeed => self.graph_state().set_master_seed(value.round() as i64 as u32),
+            SweepTarget::Param { node, name } => {
+                let Some(node) = self.slot_to_node.get(node).and_then(|&id| self.node_graph.get_node_mut(id)) else { return };
+                let Some(value) = node.noise_type.param(name).and_then(|spec| spec.from_scalar(value)) else { return };
+                node.config.values_mut().set(name, value);
+                self.changed_nodes.insert(node.node_id_key);
+            }
+        }
+    }
+
+    /// Asks the recalculator thread to render a node's preview, superseding any earlier request.
+    fn request_preview(&self, graph: &NoiseGraph, indices: &HashMap<NodeId, usize>, node_key: NodeSlotKey) {
+        let &node_id = self.slot_to_node.get(node_key).expect("Didn't find node");
+        let node = self.node_graph.get_node(node_id).expect("Didn't find node in graph");
+        let new_version = node.config_version.fetch_add(1, Ordering::SeqCst) + 1;
+        let _ = self.recalculate_sender.send(RecalculateRequest {
+            node_id: node_key,
+            target: RenderTarget::Preview,
+            new_version,
+            config_version: Arc::clone(&node.config_version),
+            noise_fn: graph.compile(indices[&node_id]),
+            sample_settings: node.preview_settings(&self.animation, self.time),
+            heightfield: node.show_heightfield.then_some(node.heightfield_view),
+        });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_graph(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Noise graph", &[crate::graph::FILE_EXTENSION])
+            .pick_file() else { return };
+        match NoiseGraph::load(&path) {
+            Ok(graph) => {
+                self.load_graph(&graph);
+                self.group_library = graph.groups().to_vec();
+                self.master_seed = graph.master_seed();
+                self.history.clear();
+            }
+            Err(err) => log::error!("Failed to open {}: {err}", path.display()),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_graph(&self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Noise graph", &[crate::graph::FILE_EXTENSION])
+            .set_file_name(format!("untitled.{}", crate::graph::FILE_EXTENSION))
+            .save_file() else { return };
+        let mut graph = to_noise_graph(&self.node_graph, self.master_seed).0;
+        graph.set_groups(self.group_library.clone());
+        if let Err(err) = graph.save(&path) {
+            log::error!("Failed to save {}: {err}", path.display());
+        }
+    }
+
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         // This is also where you can customize the look and feel of egui using
         // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
         egui_extras::install_image_loaders(&cc.egui_ctx);
 
-        let default = Self::default(cc.egui_ctx.clone());
+        let default = Self::default(cc.egui_ctx.clone(), cc.storage);
 
         if std::env::var("FRESH").ok().map(|val| val == "true").unwrap_or(false) {
             return default;
@@ -148,7 +461,12 @@ impl NoiseExplorerApp {
 
 impl eframe::App for NoiseExplorerApp {
     /// Called by the frame work to save state before shutdown.
+    #[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        #[cfg(target_arch = "wasm32")]
+        if self.presets_writable {
+            storage.set_string(PRESETS_KEY, self.presets.to_json());
+        }
         // eframe::set_value(storage, eframe::APP_KEY, &PersistableApp {
         //     node_type_filter: std::mem::take(&mut self.node_type_filter),
         //     node_type_filter_lowercase: std::mem::take(&mut self.node_type_filter_lowercase),
@@ -161,21 +479,143 @@ impl eframe::App for NoiseExplorerApp {
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         while let Ok(response) = self.recalculate_receiver.try_recv() {
+            match response.target {
+                RenderTarget::Preview => {}
+                RenderTarget::Variation(cell) => {
+                    if let Some(variation_grid) = &mut self.variation_grid {
+                        variation_grid.receive(ctx, cell, response);
+                    }
+                    continue;
+                }
+                RenderTarget::Specimen(specimen) => {
+                    if let Some(explorer) = &mut self.explorer {
+                        explorer.receive(ctx, specimen, response);
+                    }
+                    continue;
+                }
+            }
             // If None, node was deleted in the mean time.
             let Some(&node_id) = self.slot_to_node.get(response.node_id) else { continue };
             let node = self.node_graph.get_node_mut(node_id).expect("Didn't find node");
             if node.config_version.load(Ordering::SeqCst) == response.new_version {
                 node.data_version = response.new_version;
                 node.noise_range = Some((response.noise_min, response.noise_max));
-                // TODO: set texture from response
+                node.histogram = Some(response.histogram);
+                match &mut node.texture {
+                    Some(texture) => texture.set(response.texture, PREVIEW_TEXTURE_OPTIONS),
+                    None => {
+                        node.texture = Some(ctx.load_texture(
+                            format!("noise_preview_{:?}", response.node_id),
+                            response.texture,
+                            PREVIEW_TEXTURE_OPTIONS));
+                    }
+                }
             }
         }
 
         // Put your widgets into a `SidePanel`, `TopBottomPanel`, `CentralPanel`, `Window` or `Area`.
         // For inspiration and more examples, go to https://emilk.github.io/egui
 
+        let mut edit_action = None;
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("New").clicked() {
+                        self.load_graph(&NoiseGraph::default());
+                        self.group_library.clear();
+                        self.master_seed = 0;
+                        self.history.clear();
+                        ui.close_menu();
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        if ui.button("Open…").clicked() {
+                            ui.close_menu();
+                            self.open_graph();
+                        }
+                        if ui.button("Save…").clicked() {
+                            ui.close_menu();
+                            self.save_graph();
+                        }
+                    }
+                });
+                ui.menu_button("Edit", |ui| {
+                    if ui.add_enabled(self.history.can_undo(), egui::Button::new("Undo").shortcut_text("Ctrl+Z")).clicked() {
+                        self.undo();
+                        ui.close_menu();
+                    }
+                    if ui.add_enabled(self.history.can_redo(), egui::Button::new("Redo").shortcut_text("Ctrl+Shift+Z")).clicked() {
+                        self.redo();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    // Pasting needs the clipboard's contents, which egui only provides with Ctrl+V.
+                    if ui.add(egui::Button::new("Copy selected").shortcut_text("Ctrl+C"))
+                        .on_hover_text("Shift+click or Shift+drag to select nodes")
+                        .clicked() {
+                        edit_action = Some(EditAction::Copy);
+                        ui.close_menu();
+                    }
+                    if ui.add(egui::Button::new("Duplicate selected").shortcut_text("Ctrl+D")).clicked() {
+                        edit_action = Some(EditAction::Duplicate);
+                        ui.close_menu();
+                    }
+                    if ui.add(egui::Button::new("Group selected").shortcut_text("Ctrl+G")).clicked() {
+                        edit_action = Some(EditAction::Group);
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("Presets", |ui| {
+                    if ui.button("Save selected as preset…").clicked() {
+                        edit_action = Some(EditAction::SavePreset);
+                        ui.close_menu();
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        if ui.button("Import pack…").clicked() {
+                            ui.close_menu();
+                            self.import_presets();
+                        }
+                        if ui.add_enabled(!self.presets.presets().is_empty(), egui::Button::new("Export pack…")).clicked() {
+                            ui.close_menu();
+                            self.export_presets();
+                        }
+                    }
+                    if !self.presets.presets().is_empty() {
+                        ui.separator();
+                    }
+                    let mut removed = None;
+                    for (index, preset) in self.presets.presets().iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.label(&preset.name);
+                            if ui.small_button("🗑").on_hover_text("Delete preset").clicked() {
+                                removed = Some(index);
+                            }
+                        });
+                    }
+                    if let Some(index) = removed {
+                        self.presets.remove(index);
+                        self.save_presets();
+                    }
+                });
+                ui.separator();
+                ui.label("Master seed:");
+                // Applied as an edit action, so that it's undoable.
+                let mut master_seed = self.master_seed;
+                if ui
+                    .add(egui::DragValue::new(&mut master_seed))
+                    .on_hover_text("Added to the seeds of nodes whose seed mode is offset from master")
+                    .changed() {
+                    edit_action = Some(EditAction::SetMasterSeed(master_seed));
+                }
+                if ui.button("🎲").on_hover_text("Pick a new master seed").clicked() {
+                    let time = ctx.input(|input| input.time);
+                    edit_action = Some(EditAction::SetMasterSeed(Rng::new(time.to_bits() ^ u64::from(self.master_seed)).next_u32()));
+                }
+                if ui.button("Reseed all").on_hover_text("Give every unlocked node new seeds").clicked() {
+                    edit_action = Some(EditAction::ReseedAll);
+                }
+                ui.separator();
                 egui::widgets::global_theme_preference_buttons(ui);
                 ui.separator();
                 ui.add(egui::github_link_file!(
@@ -187,7 +627,87 @@ impl eframe::App for NoiseExplorerApp {
             });
         });
 
+        let mut time_changed = false;
+        if self.playing {
+            self.time = (self.time + ctx.input(|input| input.stable_dt) as f64) % self.animation.duration;
+            time_changed = true;
+            ctx.request_repaint();
+        }
+        egui::TopBottomPanel::bottom("timeline").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button(if self.playing { "⏸" } else { "▶" }).clicked() {
+                    self.playing = !self.playing;
+                }
+                time_changed |= ui
+                    .add(egui::Slider::new(&mut self.time, 0.0..=self.animation.duration).suffix(" s").text("Time"))
+                    .changed();
+                ui.separator();
+                ui.label("Duration:");
+                ui.add(egui::DragValue::new(&mut self.animation.duration).range(0.1..=600.0).speed(0.1).suffix(" s"));
+                ui.label("FPS:");
+                ui.add(egui::DragValue::new(&mut self.animation.frames_per_second).range(1.0..=60.0));
+                ui.label("Speed:");
+                time_changed |= ui
+                    .add(egui::DragValue::new(&mut self.animation.speed).speed(0.01).suffix(" /s"))
+                    .changed();
+            });
+        });
+        self.time = self.time.min(self.animation.duration);
+
+        // Text fields have their own undo.
+        if !ctx.wants_keyboard_input() {
+            // Checked first, as the plain shortcut would also match with shift held.
+            if ctx.input_mut(|input| input.consume_shortcut(&REDO_SHORTCUT)) {
+                self.redo();
+            } else if ctx.input_mut(|input| input.consume_shortcut(&UNDO_SHORTCUT)) {
+                self.undo();
+            }
+            if ctx.input_mut(|input| input.consume_shortcut(&DUPLICATE_SHORTCUT)) {
+                edit_action = Some(EditAction::Duplicate);
+            }
+            if ctx.input_mut(|input| input.consume_shortcut(&GROUP_SHORTCUT)) {
+                edit_action = Some(EditAction::Group);
+            }
+            // Copy and paste arrive as their own events, with the system clipboard already read.
+            ctx.input(|input| {
+                for event in &input.events {
+                    match event {
+                        egui::Event::Copy => edit_action = Some(EditAction::Copy),
+                        egui::Event::Paste(text) => edit_action = Some(EditAction::Paste(text.clone())),
+                        _ => {}
+                    }
+                }
+            });
+        }
+
+        if let Some(variation_grid) = &mut self.variation_grid {
+            let sender = &self.recalculate_sender;
+            if !variation_grid.show(ctx, &self.node_graph, &self.slot_to_node, self.master_seed, sender) {
+                self.variation_grid = None;
+            } else if let Some(values) = variation_grid.take_chosen() {
+                edit_action = Some(EditAction::ApplyVariation(values));
+            }
+        }
+        if let Some(explorer) = &mut self.explorer {
+            if !explorer.show(ctx, &self.recalculate_sender) {
+                self.explorer = None;
+            } else if let Some(graph) = explorer.take_chosen() {
+                edit_action = Some(EditAction::ApplyGraph(graph));
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
+            // Edits need input or an action, so frames without them, like those of an animation
+            // playing, skip capturing the graph.
+            let may_edit = edit_action.is_some() || ctx.input(|input| {
+                input.pointer.any_down()
+                    || input.events.iter().any(|event| !matches!(event, egui::Event::PointerMoved(_) | egui::Event::MouseMoved(_)))
+            });
+            let before = may_edit.then(|| Snapshot::capture(&self.node_graph, self.master_seed));
+            if let Some(action) = edit_action {
+                let selected = Snarl::<GraphNode>::get_selected_nodes(GRAPH_ID_SALT, ui);
+                self.apply_edit_action(ctx, action, &selected);
+            }
             let mut node_graph = std::mem::take(&mut self.node_graph);
             let mut viewer = GraphNodeViewer {
                 node_type_filter: &mut self.node_type_filter,
@@ -195,10 +715,42 @@ impl eframe::App for NoiseExplorerApp {
                 clear_graph: false,
                 changed_nodes: &mut self.changed_nodes,
                 slot_to_node: &mut self.slot_to_node,
+                restyled_nodes: &mut self.restyled_nodes,
+                group_library: &mut self.group_library,
+                master_seed: &mut self.master_seed,
+                presets: &self.presets,
+                preset_prompt: &mut self.preset_prompt,
+                shader_report: &mut self.shader_report,
+                paste_position: &mut self.paste_position,
+                volume_viewer: &mut self.volume_viewer,
+                variation_grid: &mut self.variation_grid,
+                explorer: &mut self.explorer,
+                #[cfg(not(target_arch = "wasm32"))]
+                animation: &self.animation,
             };
-            node_graph.show(&mut viewer, &self.node_graph_style, "noise_graph", ui);
-            if !viewer.clear_graph {
-                self.node_graph = node_graph;
+            node_graph.show(&mut viewer, &self.node_graph_style, GRAPH_ID_SALT, ui);
+            let clear_graph = viewer.clear_graph;
+            self.node_graph = node_graph;
+            if clear_graph {
+                self.load_graph(&NoiseGraph::default());
+            }
+            if let Some(before) = before {
+                let (time, pointer_down) = ctx.input(|input| (input.time, input.pointer.any_down()));
+                self.history.record(&before, &Snapshot::capture(&self.node_graph, self.master_seed), time, pointer_down);
+            }
+
+            if let Some((title, report)) = &self.shader_report {
+                let mut open = true;
+                egui::Window::new(title.as_str()).open(&mut open).show(ctx, |ui| ui.label(report));
+                if !open {
+                    self.shader_report = None;
+                }
+            }
+            self.show_preset_prompt(ctx);
+            if let Some(volume_viewer) = &mut self.volume_viewer {
+                if !volume_viewer.show(ctx, &self.node_graph, &self.slot_to_node, self.master_seed) {
+                    self.volume_viewer = None;
+                }
             }
 
 
@@ -228,29 +780,100 @@ impl eframe::App for NoiseExplorerApp {
                 for changed_node in self.changed_nodes.drain() {
                     add_dirty_tree(changed_node, &connections, &mut dirty_nodes);
                 }
+                if let Some(variation_grid) = &mut self.variation_grid {
+                    variation_grid.invalidate(&dirty_nodes);
+                }
+                let (graph, indices) = to_noise_graph(&self.node_graph, self.master_seed);
                 for dirty_node in dirty_nodes.drain() {
-                    let &node_id = self.slot_to_node.get(dirty_node).expect("Didn't find node");
-                    let node = self.node_graph.get_node_mut(node_id).expect("Didn't find node in graph");
-                    let new_version = node.config_version.fetch_add(1, Ordering::SeqCst) + 1;
-                    let _ = self.recalculate_sender.send(RecalculateRequest {
-                        node_id: dirty_node,
-                        new_version: new_version,
-                        config_version: Arc::clone(&node.config_version),
-                        noise_fn: DynNoise::new(noise::Constant::new(0.5)),
-                        texture_height: 256,
-                        texture_width: 256,
-                        noise_width: 1.0,
-                        noise_height: 1.0
-                    });
+                    self.restyled_nodes.remove(&dirty_node);
+                    self.request_preview(&graph, &indices, dirty_node);
                 }
             }
-            
 
-            
+            if time_changed {
+                self.restyled_nodes.extend(self.node_graph
+                    .nodes()
+                    .filter(|node| node.animated && node.projection == Projection::Plane)
+                    .map(|node| node.node_id_key));
+            }
+            if !self.restyled_nodes.is_empty() {
+                // Wait for each node's previous render to arrive before requesting the next.
+                // Otherwise continuous changes, like playback or orbiting a heightfield, would
+                // supersede every result before it could be shown.
+                let ready: Vec<NodeSlotKey> = self.restyled_nodes
+                    .iter()
+                    .copied()
+                    .filter(|&key| match self.slot_to_node.get(key) {
+                        Some(&node_id) => {
+                            let node = &self.node_graph[node_id];
+                            node.data_version == node.config_version.load(Ordering::SeqCst)
+                        }
+                        None => true,
+                    })
+                    .collect();
+                let (graph, indices) = to_noise_graph(&self.node_graph, self.master_seed);
+                for key in ready {
+                    self.restyled_nodes.remove(&key);
+                    if self.slot_to_node.contains_key(key) {
+                        self.request_preview(&graph, &indices, key);
+                    }
+                }
+            }
         });
     }
 }
 
+/// Removes a node's parameter pin, disconnecting it and moving wires on later pins down by one.
+fn remove_param_pin(snarl: &mut Snarl<GraphNode>, node: NodeId, pin: usize) {
+    let noise_inputs = snarl[node].noise_input_count();
+    let pin_count = snarl[node].param_pins.len();
+    let remotes: Vec<Vec<OutPinId>> = (noise_inputs + pin..noise_inputs + pin_count)
+        .map(|input| snarl.in_pin(InPinId { node, input }).remotes)
+        .collect();
+    snarl[node].param_pins.remove(pin);
+    for (offset, remotes) in remotes.into_iter().enumerate() {
+        let input = noise_inputs + pin + offset;
+        for remote in remotes {
+            snarl.disconnect(remote, InPinId { node, input });
+            if offset > 0 {
+                snarl.connect(remote, InPinId { node, input: input - 1 });
+            }
+        }
+    }
+}
+
+/// Moves wires to the pins with the same names after a node's inputs changed, such as an
+/// expression using other names. Wires to inputs that are gone are removed, and parameter pins
+/// move along with the inputs before them.
+fn remap_inputs(snarl: &mut Snarl<GraphNode>, node: NodeId, old_names: &[String]) {
+    let new_names = snarl[node].input_names();
+    let input_count = old_names.len() + snarl[node].param_pins.len();
+    let remotes: Vec<Vec<OutPinId>> = (0..input_count).map(|input| snarl.in_pin(InPinId { node, input }).remotes).collect();
+    for (input, remotes) in remotes.iter().enumerate() {
+        for &remote in remotes {
+            snarl.disconnect(remote, InPinId { node, input });
+        }
+    }
+    for (input, remotes) in remotes.into_iter().enumerate() {
+        let moved_to = match old_names.get(input) {
+            Some(old_name) => new_names.iter().position(|name| name == old_name),
+            None => Some(input - old_names.len() + new_names.len()),
+        };
+        let Some(moved_to) = moved_to else { continue };
+        for remote in remotes {
+            snarl.connect(remote, InPinId { node, input: moved_to });
+        }
+    }
+}
+
+/// Noise pins are round and scalar pins square.
+fn pin_info(pin_type: PinType) -> PinInfo {
+    match pin_type {
+        PinType::Noise => PinInfo::circle(),
+        PinType::Scalar => PinInfo::square().with_fill(SCALAR_PIN_COLOR),
+    }
+}
+
 fn powered_by_egui_and_eframe(ui: &mut egui::Ui) {
     ui.horizontal(|ui| {
         ui.spacing_mut().item_spacing.x = 0.0;
@@ -268,11 +891,342 @@ fn powered_by_egui_and_eframe(ui: &mut egui::Ui) {
 #[derive(Serialize, Deserialize)]
 pub struct GraphNode {
     node_id_key: NodeSlotKey,
+    /// Identifies the node when rendering a saved graph from the command line.
+    name: String,
     noise_type: NoiseType,
     config: NoiseConfig,
     data_version: usize,
     noise_range: Option<(f64, f64)>,
     config_version: Arc<AtomicUsize>,
+    /// Sample this node's output so that it wraps seamlessly.
+    #[serde(default)]
+    tileable: bool,
+    /// Show the preview repeated 3x3 so that seams are easy to spot.
+    #[serde(default)]
+    preview_tiled: bool,
+    #[serde(default)]
+    projection: Projection,
+    /// Preview this node at the timeline's current time rather than as a static 2D slice.
+    #[serde(default)]
+    animated: bool,
+    /// Preview this node as a shaded heightfield rather than grayscale.
+    #[serde(default)]
+    show_heightfield: bool,
+    #[serde(default)]
+    heightfield_view: HeightfieldView,
+    #[serde(default)]
+    dimensions: Dimensions,
+    /// Position of the previewed slice along the z and w axes, for 3D and 4D noise.
+    #[serde(default)]
+    z: f64,
+    #[serde(default)]
+    w: f64,
+    /// Names of the parameters exposed as input pins, after the node's noise inputs.
+    #[serde(default)]
+    param_pins: Vec<String>,
+    #[serde(default)]
+    seed_mode: SeedMode,
+    /// Keep this node's seeds when reseeding the whole graph.
+    #[serde(default)]
+    seed_locked: bool,
+    /// The distribution of the preview's values, shown behind the curves of nodes it feeds.
+    #[serde(skip)]
+    histogram: Option<Histogram>,
+    #[serde(skip)]
+    texture: Option<TextureHandle>,
+}
+
+impl GraphNode {
+    fn new(node_id_key: NodeSlotKey, name: String, noise_type: NoiseType, config: NoiseConfig) -> Self {
+        Self {
+            node_id_key,
+            name,
+            noise_type,
+            config,
+            data_version: 0,
+            noise_range: None,
+            config_version: Arc::new(AtomicUsize::new(0)),
+            tileable: false,
+            preview_tiled: false,
+            projection: Projection::default(),
+            animated: false,
+            show_heightfield: false,
+            heightfield_view: HeightfieldView::default(),
+            dimensions: Dimensions::default(),
+            z: 0.0,
+            w: 0.0,
+            param_pins: Vec::new(),
+            seed_mode: SeedMode::default(),
+            seed_locked: false,
+            histogram: None,
+            texture: None,
+        }
+    }
+
+    fn from_saved(node_id_key: NodeSlotKey, saved: &SavedNode) -> Self {
+        let mut node = Self::new(node_id_key, saved.name.clone(), saved.noise_type, saved.config.clone());
+        node.restore(saved);
+        node
+    }
+
+    /// Sets everything the saved node describes, apart from its position.
+    fn restore(&mut self, saved: &SavedNode) {
+        self.name.clone_from(&saved.name);
+        self.noise_type = saved.noise_type;
+        self.config = saved.config.clone();
+        self.tileable = saved.tileable;
+        self.projection = saved.projection;
+        self.animated = saved.animated;
+        self.show_heightfield = saved.show_heightfield;
+        self.heightfield_view = saved.heightfield_view;
+        self.dimensions = saved.dimensions;
+        self.z = saved.z;
+        self.w = saved.w;
+        self.param_pins.clone_from(&saved.param_pins);
+        self.seed_mode = saved.seed_mode;
+        self.seed_locked = saved.seed_locked;
+    }
+
+    fn to_saved(&self, pos: Pos2) -> SavedNode {
+        SavedNode {
+            name: self.name.clone(),
+            noise_type: self.noise_type,
+            config: self.config.clone(),
+            position: pos.into(),
+            tileable: self.tileable,
+            projection: self.projection,
+            animated: self.animated,
+            show_heightfield: self.show_heightfield,
+            heightfield_view: self.heightfield_view,
+            dimensions: self.dimensions,
+            z: self.z,
+            w: self.w,
+            param_pins: self.param_pins.clone(),
+            seed_mode: self.seed_mode,
+            seed_locked: self.seed_locked,
+        }
+    }
+
+    fn seed_params(&self) -> impl Iterator<Item = &'static ParamSpec> {
+        self.noise_type.params().iter().filter(|spec| matches!(spec.kind, ParamKind::Seed { .. }))
+    }
+
+    /// The number of input pins before the parameter pins.
+    fn noise_input_count(&self) -> usize {
+        self.noise_type.input_count(&self.config)
+    }
+
+    fn input_type(&self, input: usize) -> PinType {
+        if input < self.noise_input_count() {
+            self.noise_type.input_type(&self.config, input)
+        } else {
+            PinType::Scalar
+        }
+    }
+
+    /// The labels of the input pins before the parameter pins.
+    fn input_names(&self) -> Vec<String> {
+        (0..self.noise_input_count()).map(|input| self.noise_type.input_name(&self.config, input)).collect()
+    }
+
+    /// The parameters exposed as pins that have a wire connected.
+    fn driven_params(&self, inputs: &[egui_snarl::InPin]) -> Vec<String> {
+        let noise_inputs = self.noise_input_count();
+        self.param_pins
+            .iter()
+            .enumerate()
+            .filter(|&(pin, _)| inputs.get(noise_inputs + pin).is_some_and(|input| !input.remotes.is_empty()))
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+
+    /// Settings for rendering this node `width` pixels wide, at its slice through the noise.
+    fn sample_settings(&self, width: usize) -> SampleSettings {
+        SampleSettings {
+            dimensions: self.dimensions,
+            z: self.z,
+            w: self.w,
+            ..SampleSettings::new(width, self.tileable, self.projection)
+        }
+    }
+
+    /// Settings for rendering the preview, with animated nodes moved to the timeline's position.
+    fn preview_settings(&self, animation: &Animation, time: f64) -> SampleSettings {
+        let settings = self.sample_settings(render::PREVIEW_SIZE);
+        if self.animated { animation.settings_at(&settings, time) } else { settings }
+    }
+}
+
+/// Positions a slice through higher dimensional noise. Values past the ends can be typed in.
+fn slice_slider<'a>(value: &'a mut f64, axis: &str) -> egui::Slider<'a> {
+    egui::Slider::new(value, -4.0..=4.0)
+        .clamping(egui::SliderClamping::Never)
+        .text(axis)
+}
+
+/// Inserts a node into the graph, allocating its slot key and marking it for recalculation.
+fn insert_graph_node(
+        snarl: &mut Snarl<GraphNode>,
+        slot_to_node: &mut SlotMap<NodeSlotKey, NodeId>,
+        changed_nodes: &mut HashSet<NodeSlotKey>,
+        pos: Pos2,
+        make_node: impl FnOnce(NodeSlotKey) -> GraphNode) -> NodeId {
+    let key = slot_to_node.insert_with_key(|key| snarl.insert_node(pos, make_node(key)));
+    changed_nodes.insert(key);
+    slot_to_node[key]
+}
+
+/// Inserts the nodes of a saved graph and the wires between them, moved by `offset` and renamed
+/// where their names are taken. Returns the ids of the new nodes, in the saved graph's order.
+fn insert_saved_graph(
+        snarl: &mut Snarl<GraphNode>,
+        slot_to_node: &mut SlotMap<NodeSlotKey, NodeId>,
+        changed_nodes: &mut HashSet<NodeSlotKey>,
+        graph: &NoiseGraph,
+        offset: Vec2) -> Vec<NodeId> {
+    let node_ids: Vec<NodeId> = graph
+        .nodes()
+        .iter()
+        .map(|saved| {
+            let mut saved = saved.clone();
+            saved.name = unique_name(snarl, &saved.name);
+            let pos = Pos2::from(saved.position) + offset;
+            insert_graph_node(snarl, slot_to_node, changed_nodes, pos, |key| GraphNode::from_saved(key, &saved))
+        })
+        .collect();
+    for wire in graph.wires() {
+        let (Some(&from), Some(&to)) = (node_ids.get(wire.from), node_ids.get(wire.to)) else { continue };
+        snarl.connect(OutPinId { node: from, output: wire.output }, InPinId { node: to, input: wire.input });
+    }
+    node_ids
+}
+
+/// Picks a name for a new node that no other node in the graph is using, for example "perlin_2".
+fn unique_node_name(snarl: &Snarl<GraphNode>, noise_type: NoiseType) -> String {
+    unique_name(snarl, &noise_type.lowercase_name())
+}
+
+/// Returns `name` if no node is using it, otherwise numbers it, replacing any existing number.
+fn unique_name(snarl: &Snarl<GraphNode>, name: &str) -> String {
+    let taken = |name: &str| snarl.nodes().any(|node| node.name == name);
+    if !taken(name) {
+        return name.to_string();
+    }
+    let base = match name.rsplit_once('_') {
+        Some((base, number)) if !base.is_empty() && number.parse::<u32>().is_ok() => base,
+        _ => name,
+    };
+    (2..).map(|n| format!("{base}_{n}")).find(|name| !taken(name)).expect("Ran out of names")
+}
+
+/// Converts the editor's graph into its saved form, also returning the index each node was given.
+fn to_noise_graph(snarl: &Snarl<GraphNode>, master_seed: u32) -> (NoiseGraph, HashMap<NodeId, usize>) {
+    let mut graph = NoiseGraph::default();
+    graph.set_master_seed(master_seed);
+    let indices: HashMap<NodeId, usize> = snarl
+        .nodes_pos_ids()
+        .map(|(node_id, pos, node)| (node_id, graph.add_node(node.to_saved(pos))))
+        .collect();
+    for (out_pin, in_pin) in snarl.wires() {
+        graph.add_wire(SavedWire {
+            from: indices[&out_pin.node],
+            output: out_pin.output,
+            to: indices[&in_pin.node],
+            input: in_pin.input,
+        });
+    }
+    (graph, indices)
+}
+
+/// Converts some of the editor's nodes into a saved graph, keeping only the wires between them.
+/// Nodes that no longer exist are skipped.
+fn to_noise_subgraph(snarl: &Snarl<GraphNode>, nodes: &[NodeId]) -> NoiseGraph {
+    let mut graph = NoiseGraph::default();
+    let indices: HashMap<NodeId, usize> = nodes
+        .iter()
+        .filter_map(|&node_id| {
+            let info = snarl.get_node_info(node_id)?;
+            Some((node_id, graph.add_node(info.value.to_saved(info.pos))))
+        })
+        .collect();
+    for (out_pin, in_pin) in snarl.wires() {
+        let (Some(&from), Some(&to)) = (indices.get(&out_pin.node), indices.get(&in_pin.node)) else { continue };
+        graph.add_wire(SavedWire { from, output: out_pin.output, to, input: in_pin.input });
+    }
+    graph
+}
+
+/// Makes a preset of some of the editor's nodes, named after the node if there's only one. Returns
+/// `None` if none of the nodes exist.
+fn new_preset(snarl: &Snarl<GraphNode>, nodes: &[NodeId]) -> Option<Preset> {
+    let mut graph = to_noise_subgraph(snarl, nodes);
+    let origin = graph
+        .nodes()
+        .iter()
+        .map(|node| Pos2::from(node.position))
+        .reduce(Pos2::min)?;
+    for node in graph.nodes_mut() {
+        node.position = (Pos2::from(node.position) - origin).to_pos2().into();
+    }
+    let name = match graph.nodes() {
+        [node] => node.name.clone(),
+        _ => "Preset".to_string(),
+    };
+    Some(Preset { name, graph })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn export_png(snarl: &Snarl<GraphNode>, node_id: NodeId, master_seed: u32) {
+    let Some(node) = snarl.get_node(node_id) else { return };
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("PNG image", &["png"])
+        .set_file_name(format!("{}.png", node.name))
+        .save_file() else { return };
+    let settings = node.sample_settings(EXPORT_SIZE);
+    let (graph, indices) = to_noise_graph(snarl, master_seed);
+    let noise_map = render::sample(&graph.compile(indices[&node_id]), &settings);
+    if let Err(err) = render::to_gray_image(&noise_map).save(&path) {
+        log::error!("Failed to export {}: {err}", path.display());
+    }
+}
+
+/// Exports the node's animation as a GIF, or as a numbered PNG sequence if a `.png` path is chosen.
+#[cfg(not(target_arch = "wasm32"))]
+fn export_animation(snarl: &Snarl<GraphNode>, node_id: NodeId, animation: &Animation, master_seed: u32) {
+    let Some(node) = snarl.get_node(node_id) else { return };
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("Animated GIF", &["gif"])
+        .add_filter("PNG sequence", &["png"])
+        .set_file_name(format!("{}.gif", node.name))
+        .save_file() else { return };
+    let settings = node.sample_settings(ANIMATION_EXPORT_SIZE);
+    let (graph, indices) = to_noise_graph(snarl, master_seed);
+    let noise = graph.compile(indices[&node_id]);
+    let frames = animation.sample_frames(&noise, &settings);
+    let result = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("png") => render::write_png_sequence(frames, &path),
+        _ => render::write_gif(frames, animation.frames_per_second, &path),
+    };
+    if let Err(err) = result {
+        log::error!("Failed to export {}: {err}", path.display());
+    }
+}
+
+/// Saves a node as Rust source, returning an error if it can't be translated. Failing to write the
+/// file is only logged, like other exports.
+#[cfg(not(target_arch = "wasm32"))]
+fn export_rust(snarl: &Snarl<GraphNode>, node_id: NodeId, master_seed: u32) -> Result<(), codegen::rust::RustError> {
+    let Some(node) = snarl.get_node(node_id) else { return Ok(()) };
+    let (graph, indices) = to_noise_graph(snarl, master_seed);
+    let source = codegen::rust::generate(&graph, indices[&node_id], node.dimensions.count())?;
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("Rust source", &["rs"])
+        .set_file_name(format!("{}.rs", node.name))
+        .save_file() else { return Ok(()) };
+    if let Err(err) = std::fs::write(&path, source) {
+        log::error!("Failed to export {}: {err}", path.display());
+    }
+    Ok(())
 }
 
 struct GraphNodeViewer<'app> {
@@ -281,62 +1235,130 @@ struct GraphNodeViewer<'app> {
     clear_graph: bool,
     changed_nodes: &'app mut HashSet<NodeSlotKey>,
     slot_to_node: &'app mut SlotMap<NodeSlotKey, NodeId>,
+    restyled_nodes: &'app mut HashSet<NodeSlotKey>,
+    group_library: &'app mut Vec<Group>,
+    master_seed: &'app mut u32,
+    presets: &'app PresetLibrary,
+    preset_prompt: &'app mut Option<Preset>,
+    shader_report: &'app mut Option<(String, String)>,
+    paste_position: &'app mut Pos2,
+    volume_viewer: &'app mut Option<VolumeViewer>,
+    variation_grid: &'app mut Option<VariationGrid>,
+    explorer: &'app mut Option<Explorer>,
+    #[cfg(not(target_arch = "wasm32"))]
+    animation: &'app Animation,
 }
 
-impl<'app> GraphNodeViewer<'app> {
-    fn add_noise_button(&mut self, ui: &mut Ui, noise_type: &'static NoiseType, node_graph: &mut Snarl<GraphNode>, pos: Pos2) {
+impl GraphNodeViewer<'_> {
+    fn add_noise_button(&mut self, ui: &mut Ui, noise_type: NoiseType, node_graph: &mut Snarl<GraphNode>, pos: Pos2) {
         let response = ui.button(noise_type.name());
     
         if response.clicked() {
-            self.slot_to_node.insert_with_key(|key| {
-                let node_id = node_graph.insert_node(pos, GraphNode {
-                    node_id_key: key,
-                    noise_type: *noise_type,
-                    config: noise_type.config(),
-                    data_version: 0,
-                    noise_range: None,
-                    config_version: Arc::new(AtomicUsize::new(0)),
-                });
-                self.changed_nodes.insert(key);
-                node_id
+            let name = unique_node_name(node_graph, noise_type);
+            insert_graph_node(node_graph, self.slot_to_node, self.changed_nodes, pos, |key| {
+                GraphNode::new(key, name, noise_type, noise_type.config())
             });
             ui.close_menu();
         }
     }
+
+    /// Adds a button inserting a copy of a group from the library.
+    fn add_group_button(&mut self, ui: &mut Ui, index: usize, node_graph: &mut Snarl<GraphNode>, pos: Pos2) {
+        let group = &self.group_library[index];
+        if ui.button(&group.name).clicked() {
+            let name = unique_name(node_graph, &group.name.to_lowercase());
+            let config = NoiseConfig::Group(Box::new(group.clone()));
+            insert_graph_node(node_graph, self.slot_to_node, self.changed_nodes, pos, |key| {
+                GraphNode::new(key, name, NoiseType::GROUP, config)
+            });
+            ui.close_menu();
+        }
+    }
+
+    /// Adds a button inserting a preset's nodes with their top left corner at `pos`.
+    fn add_preset_button(&mut self, ui: &mut Ui, preset: &Preset, node_graph: &mut Snarl<GraphNode>, pos: Pos2) {
+        if ui.button(&preset.name).clicked() {
+            insert_saved_graph(node_graph, self.slot_to_node, self.changed_nodes, &preset.graph, pos.to_vec2());
+            ui.close_menu();
+        }
+    }
+
+    /// Shows the group's name, pin names and which inner parameters it exposes for editing.
+    fn edit_group(ui: &mut Ui, group: &mut Group) {
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut group.name);
+        });
+        if !group.inputs.is_empty() {
+            ui.label("Inputs:");
+            for input in &mut group.inputs {
+                ui.text_edit_singleline(&mut input.name);
+            }
+        }
+        ui.label("Outputs:");
+        for output in &mut group.outputs {
+            ui.text_edit_singleline(&mut output.name);
+        }
+        ui.label("Exposed parameters:");
+        let Group { graph, exposed, .. } = group;
+        for (index, node) in graph.nodes().iter().enumerate() {
+            if matches!(node.config, NoiseConfig::Empty) {
+                continue;
+            }
+            let mut is_exposed = exposed.contains(&index);
+            if ui.checkbox(&mut is_exposed, &node.name).changed() {
+                if is_exposed {
+                    exposed.push(index);
+                } else {
+                    exposed.retain(|&exposed| exposed != index);
+                }
+            }
+        }
+    }
 }
 
-impl<'app> SnarlViewer<GraphNode> for GraphNodeViewer<'app> {
+impl SnarlViewer<GraphNode> for GraphNodeViewer<'_> {
     fn title(&mut self, _: &GraphNode) -> String {
         unimplemented!("Should not be called")
     }
 
     fn inputs(&mut self, node: &GraphNode) -> usize {
-        node.noise_type.input_count()
+        node.noise_input_count() + node.param_pins.len()
     }
 
     fn show_input(&mut self, pin: &egui_snarl::InPin, ui: &mut egui::Ui, scale: f32, snarl: &mut Snarl<GraphNode>)
         -> egui_snarl::ui::PinInfo {
         if let Some(node) = snarl.get_node(pin.id.node) {
-            node.noise_type.show_input(pin.id.input, ui, scale);
-            PinInfo::circle()
+            let noise_inputs = node.noise_input_count();
+            if pin.id.input < noise_inputs {
+                node.noise_type.show_input(&node.config, pin.id.input, ui, scale);
+            } else {
+                let name = &node.param_pins[pin.id.input - noise_inputs];
+                ui.label(node.noise_type.param(name).map_or(name.as_str(), |spec| spec.label));
+            }
+            pin_info(node.input_type(pin.id.input))
         } else {
             PinInfo::triangle()
         }
     }
 
-    fn outputs(&mut self, _: &GraphNode) -> usize {
-        1
+    fn outputs(&mut self, node: &GraphNode) -> usize {
+        node.noise_type.output_count(&node.config)
     }
 
     fn show_output(
         &mut self,
-        _pin: &egui_snarl::OutPin,
+        pin: &egui_snarl::OutPin,
         ui: &mut egui::Ui,
         _scale: f32,
-        _snarl: &mut Snarl<GraphNode>,
+        snarl: &mut Snarl<GraphNode>,
     ) -> egui_snarl::ui::PinInfo {
-        ui.label("Output");
-        PinInfo::circle()
+        let node = &snarl[pin.id.node];
+        match &node.config {
+            NoiseConfig::Group(group) => ui.label(&group.outputs[pin.id.output].name),
+            _ => ui.label("Output"),
+        };
+        pin_info(node.noise_type.output_type(&node.config, pin.id.output))
     }
 
     fn show_header(
@@ -352,8 +1374,7 @@ impl<'app> SnarlViewer<GraphNode> for GraphNodeViewer<'app> {
             let node_key = graph_node.node_id_key;
             match graph_node.noise_type.show_header(&mut graph_node.config, ui, scale) {
                 noises::HeaderResponse::Remove => {
-                    snarl.remove_node(node);
-                    self.slot_to_node.remove(node_key);
+                    GraphState { snarl, slot_to_node: self.slot_to_node, changed_nodes: self.changed_nodes, master_seed: self.master_seed }.remove(node_key);
                 }
                 noises::HeaderResponse::Changed => {
                     self.changed_nodes.insert(graph_node.node_id_key);
@@ -372,28 +1393,128 @@ impl<'app> SnarlViewer<GraphNode> for GraphNodeViewer<'app> {
     fn show_body(
             &mut self,
             node_id: NodeId,
-            _inputs: &[egui_snarl::InPin],
+            inputs: &[egui_snarl::InPin],
             _outputs: &[egui_snarl::OutPin],
             ui: &mut Ui,
             scale: f32,
             snarl: &mut Snarl<GraphNode>,
         ) {
+        // The histogram of the node feeding the first input, shown behind curves.
+        let input_histogram = inputs
+            .first()
+            .and_then(|input| input.remotes.first())
+            .and_then(|remote| snarl.get_node(remote.node)?.histogram.clone());
+        let renamed = ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.add(egui::TextEdit::singleline(&mut snarl[node_id].name).desired_width(128.0 * scale)).lost_focus()
+        }).inner;
+        if renamed {
+            // Names pick nodes in the command line renderer, so they're kept unique. Taking the
+            // name out first keeps the node from clashing with itself.
+            let name = std::mem::take(&mut snarl[node_id].name);
+            snarl[node_id].name = unique_name(snarl, &name);
+        }
         let node = snarl.get_node_mut(node_id).unwrap();
-        let changed = node.noise_type.show_body(&mut node.config, ui, scale);
+        let driven = node.driven_params(inputs);
+        let input_names = node.input_names();
+        let context = BodyContext { driven: &driven, input_histogram: input_histogram.as_ref() };
+        let mut changed = node.noise_type.show_body(&mut node.config, &context, ui, scale);
+        let renamed_inputs = changed && node.input_names() != input_names;
+        if node.seed_params().next().is_some() {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt(("seed_mode", node.node_id_key))
+                    .selected_text(node.seed_mode.name())
+                    .show_ui(ui, |ui| {
+                        for seed_mode in SeedMode::VARIANTS {
+                            changed |= ui.selectable_value(&mut node.seed_mode, *seed_mode, seed_mode.name()).changed();
+                        }
+                    });
+                ui.toggle_value(&mut node.seed_locked, "🔒")
+                    .on_hover_text("Keep this node's seeds when reseeding all nodes");
+            });
+        }
+        let mut restyled = false;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt(("projection", node.node_id_key))
+                .selected_text(node.projection.name())
+                .show_ui(ui, |ui| {
+                    for projection in Projection::VARIANTS {
+                        changed |= ui.selectable_value(&mut node.projection, *projection, projection.name()).changed();
+                    }
+                });
+            if node.projection == Projection::Plane {
+                changed |= ui.checkbox(&mut node.tileable, "Tileable").changed();
+                changed |= ui.checkbox(&mut node.animated, "Animate").changed();
+            }
+            if node.show_heightfield {
+                restyled |= ui.checkbox(&mut node.show_heightfield, "Heightfield").changed();
+            } else {
+                ui.checkbox(&mut node.preview_tiled, "Preview 3×3");
+                restyled |= ui.checkbox(&mut node.show_heightfield, "Heightfield").changed();
+            }
+        });
+        if node.show_heightfield {
+            let view = &mut node.heightfield_view;
+            ui.horizontal(|ui| {
+                ui.label("Height:");
+                restyled |= ui.add(egui::DragValue::new(&mut view.height_scale).range(0.0..=2.0).speed(0.005)).changed();
+                ui.label("Light:");
+                restyled |= ui
+                    .add(egui::DragValue::new(&mut view.light_azimuth).range(0.0..=360.0).suffix("°"))
+                    .on_hover_text("Direction")
+                    .changed();
+                restyled |= ui
+                    .add(egui::DragValue::new(&mut view.light_elevation).range(0.0..=90.0).suffix("°"))
+                    .on_hover_text("Elevation")
+                    .changed();
+            });
+        }
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt(("dimensions", node.node_id_key))
+                .selected_text(node.dimensions.name())
+                .show_ui(ui, |ui| {
+                    for dimensions in Dimensions::VARIANTS {
+                        changed |= ui.selectable_value(&mut node.dimensions, *dimensions, dimensions.name()).changed();
+                    }
+                });
+            // The cylinder and sphere already use the z axis.
+            if node.dimensions >= Dimensions::Three && node.projection == Projection::Plane {
+                changed |= ui.add(slice_slider(&mut node.z, "Z")).changed();
+            }
+            if node.dimensions == Dimensions::Four {
+                changed |= ui.add(slice_slider(&mut node.w, "W")).changed();
+            }
+        });
         if changed {
             self.changed_nodes.insert(node.node_id_key);
         }
         static IMAGE: ImageSource<'static> = egui::include_image!("../assets/fbm.png");
         ui.with_layout(Layout::top_down(Align::Center), |ui| {
-            ui.add(egui::Image::new(IMAGE.clone())
+            let image = match &node.texture {
+                Some(texture) => {
+                    let uv_extent = if node.preview_tiled && !node.show_heightfield { 3.0 } else { 1.0 };
+                    egui::Image::new(texture).uv(Rect::from_min_max(Pos2::ZERO, Pos2::new(uv_extent, uv_extent)))
+                }
+                None => egui::Image::new(IMAGE.clone()),
+            };
+            let response = ui.add(image
                 .maintain_aspect_ratio(true)
                 .fit_to_exact_size(Vec2::new(256.0, 256.0) * scale)
+                .sense(if node.show_heightfield { Sense::drag() } else { Sense::hover() })
             );
+            // Dragging a heightfield orbits the camera around it.
+            if response.dragged() {
+                let delta = response.drag_delta() / scale;
+                let view = &mut node.heightfield_view;
+                view.yaw = (view.yaw + delta.x * 0.5).rem_euclid(360.0);
+                view.pitch = (view.pitch + delta.y * 0.5).clamp(5.0, 90.0);
+                restyled = true;
+            }
             ui.horizontal(|ui| {
-                ui.label(&format!("Data version: {}", node.data_version));
+                ui.label(format!("Data version: {}", node.data_version));
             });
             ui.horizontal(|ui| {
-                ui.label(&format!("Config version: {}", node.config_version.load(Ordering::SeqCst)));
+                ui.label(format!("Config version: {}", node.config_version.load(Ordering::SeqCst)));
             });
             ui.horizontal(|ui| {
                 ui.spacing_mut().item_spacing = Vec2::ZERO;
@@ -404,8 +1525,129 @@ impl<'app> SnarlViewer<GraphNode> for GraphNodeViewer<'app> {
                     ui.label(RichText::new(format!("{}", range.1)).color(if range.1 > 1.0 { Color32::RED } else { ui.style().visuals.text_color() }));
                     ui.label("]");
                 }
-            })
+            });
+        });
+        if restyled {
+            self.restyled_nodes.insert(node.node_id_key);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.horizontal(|ui| {
+            if ui.button("Export PNG…").clicked() {
+                export_png(snarl, node_id, *self.master_seed);
+            }
+            if snarl[node_id].projection == Projection::Plane && ui.button("Export animation…").clicked() {
+                export_animation(snarl, node_id, self.animation, *self.master_seed);
+            }
         });
+        if renamed_inputs {
+            remap_inputs(snarl, node_id, &input_names);
+        }
+    }
+
+    fn has_node_menu(&mut self, _node: &GraphNode) -> bool {
+        true
+    }
+
+    fn show_node_menu(
+            &mut self,
+            node: NodeId,
+            _inputs: &[egui_snarl::InPin],
+            _outputs: &[egui_snarl::OutPin],
+            ui: &mut Ui,
+            _scale: f32,
+            snarl: &mut Snarl<GraphNode>,
+        ) {
+        if ui.button("Copy as Rust code").clicked() {
+            let (graph, indices) = to_noise_graph(snarl, *self.master_seed);
+            match codegen::rust::generate(&graph, indices[&node], snarl[node].dimensions.count()) {
+                Ok(source) => ui.ctx().copy_text(source),
+                Err(err) => *self.shader_report = Some(("Rust code error".to_string(), err.to_string())),
+            }
+            ui.close_menu();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if ui.button("Export as Rust code…").clicked() {
+            ui.close_menu();
+            if let Err(err) = export_rust(snarl, node, *self.master_seed) {
+                *self.shader_report = Some(("Rust code error".to_string(), err.to_string()));
+            }
+        }
+        if ui.button("Volume slices…").clicked() {
+            *self.volume_viewer = Some(VolumeViewer::new(snarl[node].node_id_key));
+            ui.close_menu();
+        }
+        if ui.button("Variations…").clicked() {
+            *self.variation_grid = Some(VariationGrid::new(snarl[node].node_id_key, snarl, *self.master_seed));
+            ui.close_menu();
+        }
+        if ui.button("Explore…").clicked() {
+            let seed = ui.input(|input| input.time).to_bits() ^ u64::from(*self.master_seed);
+            *self.explorer = Some(Explorer::new(node, snarl, *self.master_seed, seed));
+            ui.close_menu();
+        }
+        if ui.button("Save as preset…").clicked() {
+            *self.preset_prompt = new_preset(snarl, &[node]);
+            ui.close_menu();
+        }
+        let noise_type = snarl[node].noise_type;
+        if noise_type.params().iter().any(|spec| spec.is_scalar()) {
+            ui.menu_button("Parameter pins", |ui| {
+                for spec in noise_type.params().iter().filter(|spec| spec.is_scalar()) {
+                    let position = snarl[node].param_pins.iter().position(|name| name == spec.name);
+                    let mut exposed = position.is_some();
+                    if ui.checkbox(&mut exposed, spec.label).changed() {
+                        match position {
+                            Some(pin) => remove_param_pin(snarl, node, pin),
+                            None => snarl[node].param_pins.push(spec.name.to_string()),
+                        }
+                        self.changed_nodes.insert(snarl[node].node_id_key);
+                    }
+                }
+            });
+        }
+        if let NoiseConfig::Group(group) = &mut snarl[node].config {
+            ui.separator();
+            ui.menu_button("Edit group", |ui| Self::edit_group(ui, group));
+            if ui.button("Add to graph menu").clicked() {
+                // Replaces an earlier version of the same group.
+                self.group_library.retain(|saved| saved.name != group.name);
+                self.group_library.push((**group).clone());
+                ui.close_menu();
+            }
+            if ui.button("Expand group").clicked() {
+                group::expand(
+                    &mut GraphState { snarl, slot_to_node: self.slot_to_node, changed_nodes: self.changed_nodes, master_seed: self.master_seed },
+                    node);
+                ui.close_menu();
+                return;
+            }
+        }
+        ui.separator();
+        for language in [ShaderLanguage::Wgsl, ShaderLanguage::Glsl] {
+            if ui.button(format!("Copy as {}", language.name())).clicked() {
+                let (graph, indices) = to_noise_graph(snarl, *self.master_seed);
+                match ShaderProgram::compile(&graph, indices[&node]) {
+                    Ok(program) => ui.ctx().copy_text(program.generate(language)),
+                    Err(err) => *self.shader_report = Some(("Shader error".to_string(), err.to_string())),
+                }
+                ui.close_menu();
+            }
+        }
+        if ui.button("Validate shader").clicked() {
+            let (graph, indices) = to_noise_graph(snarl, *self.master_seed);
+            let node_index = indices[&node];
+            let report = match ShaderProgram::compile(&graph, node_index) {
+                Ok(program) => {
+                    // The shaders only cover the plane, sampled without seamless blending.
+                    let settings = SampleSettings::preview(false, Projection::Plane);
+                    let validation = program.validate(&graph.build_noise_fn::<2>(node_index), &settings);
+                    format!("Compared against the CPU noise at {}×{} points.\n{validation}", settings.width, settings.height)
+                }
+                Err(err) => err.to_string(),
+            };
+            *self.shader_report = Some((format!("Shader validation: {}", graph.nodes()[node_index].name), report));
+            ui.close_menu();
+        }
     }
 
     fn has_graph_menu(&mut self, _pos: Pos2, _snarl: &mut Snarl<GraphNode>) -> bool {
@@ -434,15 +1676,62 @@ impl<'app> SnarlViewer<GraphNode> for GraphNodeViewer<'app> {
                     self.add_noise_button(ui, noise_type, snarl, pos);
                 }
             });
+            ui.menu_button("Modifiers", |ui| {
+                for noise_type in NoiseType::modifiers() {
+                    self.add_noise_button(ui, noise_type, snarl, pos);
+                }
+            });
+            ui.menu_button("Raster", |ui| {
+                for noise_type in NoiseType::rasters() {
+                    self.add_noise_button(ui, noise_type, snarl, pos);
+                }
+            });
+            ui.menu_button("Scalars", |ui| {
+                for noise_type in NoiseType::scalars() {
+                    self.add_noise_button(ui, noise_type, snarl, pos);
+                }
+            });
+            if !self.group_library.is_empty() {
+                ui.menu_button("Groups", |ui| {
+                    for index in 0..self.group_library.len() {
+                        self.add_group_button(ui, index, snarl, pos);
+                    }
+                });
+            }
+            if !self.presets.presets().is_empty() {
+                ui.menu_button("Presets", |ui| {
+                    for preset in self.presets.presets() {
+                        self.add_preset_button(ui, preset, snarl, pos);
+                    }
+                });
+            }
         } else {
             let mut matches = 0;
-            for noise_type in NoiseType::all() {
+            for noise_type in NoiseType::sources()
+                    .chain(NoiseType::combinators())
+                    .chain(NoiseType::modifiers())
+                    .chain(NoiseType::rasters())
+                    .chain(NoiseType::scalars()) {
                 if !noise_type.lowercase_name().contains(self.node_type_filter_lowercase as &_) {
                     continue;
                 }
                 matches += 1;
                 self.add_noise_button(ui, noise_type, snarl, pos);
             }
+            for index in 0..self.group_library.len() {
+                if !self.group_library[index].name.to_lowercase().contains(self.node_type_filter_lowercase as &_) {
+                    continue;
+                }
+                matches += 1;
+                self.add_group_button(ui, index, snarl, pos);
+            }
+            for preset in self.presets.presets() {
+                if !preset.name.to_lowercase().contains(self.node_type_filter_lowercase as &_) {
+                    continue;
+                }
+                matches += 1;
+                self.add_preset_button(ui, preset, snarl, pos);
+            }
             if matches == 0 {
                 ui.centered_and_justified(|ui| {
                     ui.label("No matches");
@@ -458,6 +1747,11 @@ impl<'app> SnarlViewer<GraphNode> for GraphNodeViewer<'app> {
     }
 
     fn connect(&mut self, from: &egui_snarl::OutPin, to: &egui_snarl::InPin, snarl: &mut Snarl<GraphNode>) {
+        let from_node = &snarl[from.id.node];
+        let output_type = from_node.noise_type.output_type(&from_node.config, from.id.output);
+        if output_type != snarl[to.id.node].input_type(to.id.input) {
+            return;
+        }
         if from.id.node != to.id.node {
             snarl.connect(from.id, to.id);
             self.changed_nodes.insert(snarl.get_node(to.id.node).unwrap().node_id_key);
@@ -473,23 +1767,73 @@ impl<'app> SnarlViewer<GraphNode> for GraphNodeViewer<'app> {
             self.changed_nodes.insert(to_node.node_id_key);
         }
     }
+
+    /// Also the only place the view's pan and zoom are available, so it keeps track of where
+    /// pasted nodes should go.
+    fn draw_background(
+            &mut self,
+            background: Option<&BackgroundPattern>,
+            viewport: &Viewport,
+            snarl_style: &SnarlStyle,
+            style: &egui::Style,
+            painter: &egui::Painter,
+            _snarl: &Snarl<GraphNode>) {
+        if let Some(background) = background {
+            background.draw(viewport, snarl_style, style, painter);
+        }
+        let pointer = painter.ctx().pointer_hover_pos().filter(|pos| viewport.rect.contains(*pos));
+        *self.paste_position = viewport.screen_pos_to_graph(pointer.unwrap_or(viewport.rect.center()));
+    }
+}
+
+enum EditAction {
+    /// Copy the selected nodes to the system clipboard.
+    Copy,
+    /// Paste text from the system clipboard, if it holds copied nodes.
+    Paste(String),
+    /// Copy and paste the selected nodes without going through the clipboard.
+    Duplicate,
+    /// Collapse the selected nodes into a group node.
+    Group,
+    /// Ask for a name to save the selected nodes as a preset under.
+    SavePreset,
+    /// Set the master seed, from the top panel.
+    SetMasterSeed(u32),
+    /// Pick new seeds for every node that isn't locked.
+    ReseedAll,
+    /// Apply the values of a thumbnail from the variation grid.
+    ApplyVariation(Vec<(SweepTarget, f64)>),
+    /// Replace the graph with one from the explorer.
+    ApplyGraph(NoiseGraph),
+}
+
+/// Where a rendered image is shown.
+#[derive(Clone, Copy)]
+enum RenderTarget {
+    Preview,
+    /// A thumbnail in the variation grid.
+    Variation(usize),
+    /// A thumbnail of a graph in the explorer's history.
+    Specimen(usize),
 }
 
 struct RecalculateRequest {
     node_id: NodeSlotKey,
+    target: RenderTarget,
     new_version: usize,
     config_version: Arc<AtomicUsize>,
-    texture_width: usize,
-    texture_height: usize,
-    noise_width: f64,
-    noise_height: f64,
-    noise_fn: DynNoise,
+    sample_settings: SampleSettings,
+    /// Render the samples as a shaded heightfield rather than grayscale.
+    heightfield: Option<HeightfieldView>,
+    noise_fn: CompiledNoise,
 }
 
 struct RecalculateResult {
     node_id: NodeSlotKey,
+    target: RenderTarget,
     new_version: usize,
     noise_max: f64,
     noise_min: f64,
-    texture: () // TODO
+    histogram: Histogram,
+    texture: egui::ColorImage,
 }
\ No newline at end of file
@@ -0,0 +1,113 @@
+//! Copying nodes between graphs as text.
+//!
+//! Copied nodes are written to the system clipboard as a saved graph, so they can be pasted into
+//! another window, or another browser tab running the web version.
+
+use std::collections::HashSet;
+
+use egui::{Pos2, Vec2};
+use egui_snarl::{NodeId, Snarl};
+use slotmap::SlotMap;
+
+use crate::graph::NoiseGraph;
+
+use super::{insert_saved_graph, to_noise_subgraph, GraphNode, NodeSlotKey};
+
+/// First line of copied nodes, so that other text on the clipboard isn't mistaken for nodes.
+const HEADER: &str = "noise-explorer nodes";
+
+/// How far duplicated nodes are moved from the originals, so they don't hide them.
+const DUPLICATE_OFFSET: Vec2 = Vec2::splat(40.0);
+
+/// Serializes the given nodes along with the wires between them. Wires from nodes that weren't
+/// copied are left out. Returns `None` if there is nothing to copy.
+pub fn copy(snarl: &Snarl<GraphNode>, nodes: &[NodeId]) -> Option<String> {
+    let graph = to_noise_subgraph(snarl, nodes);
+    if graph.nodes().is_empty() {
+        return None;
+    }
+    Some(format!("{HEADER}\n{}", graph.to_json()))
+}
+
+/// Adds copied nodes to the graph with new keys, renaming any whose name is already taken. The
+/// nodes keep their layout, with the top left corner of their positions moved to `position`.
+/// Text that doesn't hold copied nodes is ignored.
+pub fn paste(
+        text: &str,
+        snarl: &mut Snarl<GraphNode>,
+        slot_to_node: &mut SlotMap<NodeSlotKey, NodeId>,
+        changed_nodes: &mut HashSet<NodeSlotKey>,
+        position: Pos2) {
+    let Some(json) = text.strip_prefix(HEADER) else { return };
+    let graph = match NoiseGraph::from_json(json) {
+        Ok(graph) => graph,
+        Err(err) => {
+            log::warn!("Failed to paste nodes: {err}");
+            return;
+        }
+    };
+    let corner = top_left(graph.nodes().iter().map(|node| Pos2::from(node.position)));
+    insert_saved_graph(snarl, slot_to_node, changed_nodes, &graph, position - corner);
+}
+
+/// Copies and pastes nodes without going through the clipboard, just past the originals.
+pub fn duplicate(
+        snarl: &mut Snarl<GraphNode>,
+        slot_to_node: &mut SlotMap<NodeSlotKey, NodeId>,
+        changed_nodes: &mut HashSet<NodeSlotKey>,
+        nodes: &[NodeId]) {
+    let Some(text) = copy(snarl, nodes) else { return };
+    let corner = top_left(nodes.iter().filter_map(|&node| Some(snarl.get_node_info(node)?.pos)));
+    paste(&text, snarl, slot_to_node, changed_nodes, corner + DUPLICATE_OFFSET);
+}
+
+/// The smallest coordinates of some positions.
+fn top_left(positions: impl Iterator<Item = Pos2>) -> Pos2 {
+    positions.fold(Pos2::new(f32::INFINITY, f32::INFINITY), Pos2::min)
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+    use egui_snarl::{InPinId, OutPinId};
+
+    use crate::noises::NoiseType;
+
+    use super::{super::insert_graph_node, *};
+
+    #[test]
+    fn pasting_keeps_wires_between_copied_nodes() {
+        let mut snarl = Snarl::new();
+        let mut slot_to_node = SlotMap::with_key();
+        let mut changed_nodes = HashSet::new();
+        let mut add = |snarl: &mut Snarl<GraphNode>, name: &str, noise_type: NoiseType, pos: Pos2| {
+            insert_graph_node(snarl, &mut slot_to_node, &mut changed_nodes, pos, |key| {
+                GraphNode::new(key, name.to_string(), noise_type, noise_type.config())
+            })
+        };
+        let p = add(&mut snarl, "p", NoiseType::PERLIN, pos2(0.0, 0.0));
+        let c = add(&mut snarl, "c", NoiseType::CONSTANT, pos2(0.0, 100.0));
+        let m = add(&mut snarl, "m", NoiseType::MAX, pos2(200.0, 50.0));
+        snarl.connect(OutPinId { node: p, output: 0 }, InPinId { node: m, input: 0 });
+        snarl.connect(OutPinId { node: c, output: 0 }, InPinId { node: m, input: 1 });
+
+        // Only "p" and "m" are copied, so the wire from "c" stays behind.
+        let text = copy(&snarl, &[p, m]).unwrap();
+        paste(&text, &mut snarl, &mut slot_to_node, &mut changed_nodes, pos2(500.0, 500.0));
+
+        let find = |name: &str| snarl.nodes_pos_ids().find(|(_, _, node)| node.name == name).map(|(id, pos, _)| (id, pos));
+        let (p_2, p_2_pos) = find("p_2").expect("Pasted nodes should be renamed");
+        let (m_2, m_2_pos) = find("m_2").expect("Pasted nodes should be renamed");
+        assert_eq!(snarl.nodes().count(), 5);
+        assert_eq!((p_2_pos, m_2_pos), (pos2(500.0, 500.0), pos2(700.0, 550.0)));
+        let wires: Vec<_> = snarl.wires().filter(|(_, to)| to.node == m_2).map(|(from, to)| (from.node, to.input)).collect();
+        assert_eq!(wires, [(p_2, 0)]);
+
+        let keys: HashSet<NodeSlotKey> = snarl.nodes().map(|node| node.node_id_key).collect();
+        assert_eq!(keys.len(), 5);
+        assert_eq!(slot_to_node.len(), 5);
+        for (key, &node) in &slot_to_node {
+            assert_eq!(snarl[node].node_id_key, key);
+        }
+    }
+}
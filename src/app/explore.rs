@@ -0,0 +1,194 @@
+//! A window exploring variations of a node by evolution. Each generation mutates and breeds the
+//! favorites picked from earlier ones, and every graph bred is kept in a history tree, so that any
+//! of them can be bred from again or applied to the graph.
+
+use std::{collections::BTreeSet, sync::{atomic::AtomicUsize, mpsc::Sender, Arc}};
+
+use egui::{RichText, TextureHandle, Vec2};
+use egui_snarl::{NodeId, Snarl};
+
+use crate::{evolve::{self, Mutation}, graph::NoiseGraph, random::Rng, render::SampleSettings};
+
+use super::{to_noise_graph, GraphNode, NodeSlotKey, RecalculateRequest, RecalculateResult, RenderTarget, PREVIEW_TEXTURE_OPTIONS};
+
+/// Resolution of each thumbnail.
+const THUMBNAIL_SIZE: usize = 96;
+
+/// The most children a generation can have.
+const MAX_POPULATION: usize = 24;
+
+/// A graph in the history tree.
+struct Specimen {
+    graph: NoiseGraph,
+    /// The specimens this one was bred from. Only the original graph has none.
+    parents: Vec<usize>,
+    mutations: Vec<Mutation>,
+    texture: Option<TextureHandle>,
+}
+
+pub struct Explorer {
+    node: NodeSlotKey,
+    name: String,
+    /// Index of the explored node, which is the same in every specimen as mutations only add nodes.
+    output: usize,
+    sample_settings: SampleSettings,
+    specimens: Vec<Specimen>,
+    /// Indices of the specimens in each generation, the first being the original graph.
+    generations: Vec<Vec<usize>>,
+    /// How many specimens have been sent to be rendered.
+    requested: usize,
+    favorites: BTreeSet<usize>,
+    population: usize,
+    strength: f64,
+    rng: Rng,
+    /// Thumbnails never need rendering again, so this never changes.
+    version: Arc<AtomicUsize>,
+    /// A specimen the user chose to apply, waiting to replace the editor's graph.
+    chosen: Option<NoiseGraph>,
+}
+
+impl Explorer {
+    /// Starts from the current graph with a first generation of its mutations.
+    pub fn new(node_id: NodeId, snarl: &Snarl<GraphNode>, master_seed: u32, seed: u64) -> Self {
+        let node = &snarl[node_id];
+        let (graph, indices) = to_noise_graph(snarl, master_seed);
+        let mut explorer = Self {
+            node: node.node_id_key,
+            name: node.name.clone(),
+            output: indices[&node_id],
+            sample_settings: node.sample_settings(THUMBNAIL_SIZE),
+            specimens: vec![Specimen { graph, parents: Vec::new(), mutations: Vec::new(), texture: None }],
+            generations: vec![vec![0]],
+            requested: 0,
+            favorites: BTreeSet::from([0]),
+            population: 8,
+            strength: 0.3,
+            rng: Rng::new(seed),
+            version: Arc::new(AtomicUsize::new(0)),
+            chosen: None,
+        };
+        explorer.breed_generation();
+        explorer
+    }
+
+    /// The graph of the specimen the user chose since this was last called.
+    pub fn take_chosen(&mut self) -> Option<NoiseGraph> {
+        self.chosen.take()
+    }
+
+    pub fn receive(&mut self, ctx: &egui::Context, specimen: usize, result: RecalculateResult) {
+        let Some(specimen) = self.specimens.get_mut(specimen) else { return };
+        specimen.texture = Some(ctx.load_texture("specimen", result.texture, PREVIEW_TEXTURE_OPTIONS));
+    }
+
+    /// Adds a generation of children of the favorites, each mutated from one favorite or bred
+    /// from two.
+    fn breed_generation(&mut self) {
+        let parents: Vec<usize> = self.favorites.iter().copied().collect();
+        let mut generation = Vec::new();
+        for _ in 0..self.population {
+            let Some(&a) = self.rng.pick(&parents) else { return };
+            let others: Vec<usize> = parents.iter().copied().filter(|&b| b != a).collect();
+            let (graph, parents) = match self.rng.pick(&others) {
+                Some(&b) if self.rng.chance(0.5) => {
+                    let graph = evolve::breed(&self.specimens[a].graph, &self.specimens[b].graph, &mut self.rng);
+                    (graph, vec![a, b])
+                }
+                _ => (self.specimens[a].graph.clone(), vec![a]),
+            };
+            let (graph, mutations) = evolve::mutate(&graph, self.output, self.strength, &mut self.rng);
+            generation.push(self.specimens.len());
+            self.specimens.push(Specimen { graph, parents, mutations, texture: None });
+        }
+        self.generations.push(generation);
+        self.favorites.clear();
+    }
+
+    /// Shows the window, returning false once it has been closed.
+    pub fn show(&mut self, ctx: &egui::Context, sender: &Sender<RecalculateRequest>) -> bool {
+        self.request_thumbnails(sender);
+        let mut open = true;
+        egui::Window::new(format!("Explore: {}", self.name))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Children:");
+                    ui.add(egui::DragValue::new(&mut self.population).range(1..=MAX_POPULATION));
+                    ui.label("Mutation:");
+                    ui.add(egui::Slider::new(&mut self.strength, 0.0..=1.0));
+                });
+                ui.horizontal(|ui| {
+                    let breed = ui
+                        .add_enabled(!self.favorites.is_empty(), egui::Button::new("Breed next generation"))
+                        .on_disabled_hover_text("Click thumbnails to pick favorites first");
+                    if breed.clicked() {
+                        self.breed_generation();
+                    }
+                    ui.label(format!("{} favorites", self.favorites.len()));
+                });
+                ui.separator();
+
+                // Newest first, as that's where favorites are usually picked.
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for number in (0..self.generations.len()).rev() {
+                        let title = if number == 0 { "Original".to_string() } else { format!("Generation {number}") };
+                        ui.label(RichText::new(title).strong());
+                        ui.horizontal_wrapped(|ui| {
+                            for position in 0..self.generations[number].len() {
+                                self.show_specimen(ui, self.generations[number][position]);
+                            }
+                        });
+                    }
+                });
+            });
+        open
+    }
+
+    fn show_specimen(&mut self, ui: &mut egui::Ui, index: usize) {
+        let specimen = &self.specimens[index];
+        let favorite = self.favorites.contains(&index);
+        let lineage = match specimen.parents.as_slice() {
+            [] => format!("#{index}"),
+            parents => {
+                let parents: Vec<String> = parents.iter().map(|parent| format!("#{parent}")).collect();
+                format!("#{index} ← {}", parents.join(" × "))
+            }
+        };
+        let mut hover = specimen.mutations.iter().map(|mutation| mutation.to_string()).collect::<Vec<_>>();
+        hover.push("Click to pick as a favorite, right-click to apply".to_string());
+        ui.vertical(|ui| {
+            let size = Vec2::splat(THUMBNAIL_SIZE as f32);
+            let response = match &specimen.texture {
+                Some(texture) => ui.add(egui::ImageButton::new((texture.id(), size)).selected(favorite)),
+                None => ui.add_sized(size, egui::Spinner::new()),
+            };
+            let response = response.on_hover_text(hover.join("\n"));
+            if response.clicked() && !self.favorites.remove(&index) {
+                self.favorites.insert(index);
+            }
+            response.context_menu(|ui| {
+                if ui.button("Apply to graph").clicked() {
+                    self.chosen = Some(self.specimens[index].graph.clone());
+                    ui.close_menu();
+                }
+            });
+            ui.small(lineage);
+        });
+    }
+
+    /// Renders thumbnails of the specimens bred since this was last called in the background.
+    fn request_thumbnails(&mut self, sender: &Sender<RecalculateRequest>) {
+        for (index, specimen) in self.specimens.iter().enumerate().skip(self.requested) {
+            let _ = sender.send(RecalculateRequest {
+                node_id: self.node,
+                target: RenderTarget::Specimen(index),
+                new_version: 0,
+                config_version: Arc::clone(&self.version),
+                sample_settings: self.sample_settings,
+                heightfield: None,
+                noise_fn: specimen.graph.compile(self.output),
+            });
+        }
+        self.requested = self.specimens.len();
+    }
+}
@@ -0,0 +1,125 @@
+//! Collapsing nodes into a group node, and expanding groups back into their contents.
+
+use std::collections::HashMap;
+
+use egui::Pos2;
+use egui_snarl::{InPinId, NodeId, OutPinId};
+
+use crate::{
+    graph::SavedWire,
+    group::{Group, GroupInput, GroupOutput, GroupPin},
+    noises::{NoiseConfig, NoiseType},
+};
+
+use super::{history::GraphState, insert_graph_node, insert_saved_graph, unique_name, GraphNode};
+
+/// Replaces the given nodes with a group containing them. Each node outside the group that feeds
+/// the selection becomes an input of the group, and each inner output used outside it becomes an
+/// output. If nothing outside uses the selection, the inner nodes nothing else uses are the
+/// outputs instead.
+pub fn collapse(state: &mut GraphState<'_>, selected: &[NodeId]) {
+    let selected: Vec<NodeId> = selected.iter().copied().filter(|&id| state.snarl.get_node(id).is_some()).collect();
+    if selected.is_empty() {
+        return;
+    }
+    let origin = selected
+        .iter()
+        .map(|&id| state.snarl.get_node_info(id).expect("Node was checked").pos)
+        .fold(Pos2::new(f32::INFINITY, f32::INFINITY), Pos2::min);
+
+    let mut group = Group { name: "Group".to_string(), ..Group::default() };
+    let indices: HashMap<NodeId, usize> = selected
+        .iter()
+        .map(|&id| {
+            let info = state.snarl.get_node_info(id).expect("Node was checked");
+            (id, group.graph.add_node(info.value.to_saved((info.pos - origin).to_pos2())))
+        })
+        .collect();
+
+    // The pins outside the group that its inputs and outputs connect to, in pin order.
+    let mut sources: Vec<OutPinId> = Vec::new();
+    let mut consumers: Vec<(usize, InPinId)> = Vec::new();
+    let mut used_outputs: Vec<OutPinId> = Vec::new();
+    let wires: Vec<(OutPinId, InPinId)> = state.snarl.wires().collect();
+    for (out_pin, in_pin) in wires {
+        match (indices.get(&out_pin.node), indices.get(&in_pin.node)) {
+            (Some(&from), Some(&to)) => {
+                group.graph.add_wire(SavedWire { from, output: out_pin.output, to, input: in_pin.input });
+            }
+            (None, Some(&to)) => {
+                let input = sources.iter().position(|&source| source == out_pin).unwrap_or_else(|| {
+                    sources.push(out_pin);
+                    group.inputs.push(GroupInput { name: state.snarl[out_pin.node].name.clone(), targets: Vec::new() });
+                    sources.len() - 1
+                });
+                group.inputs[input].targets.push(GroupPin { node: to, input: in_pin.input });
+            }
+            (Some(&from), None) => {
+                let output = used_outputs.iter().position(|&used| used == out_pin).unwrap_or_else(|| {
+                    used_outputs.push(out_pin);
+                    group.outputs.push(GroupOutput {
+                        name: state.snarl[out_pin.node].name.clone(),
+                        node: from,
+                        output: out_pin.output,
+                    });
+                    used_outputs.len() - 1
+                });
+                consumers.push((output, in_pin));
+            }
+            (None, None) => {}
+        }
+    }
+    if group.outputs.is_empty() {
+        let unused = (0..group.graph.nodes().len())
+            .filter(|&node| !group.graph.wires().iter().any(|wire| wire.from == node))
+            .collect::<Vec<_>>();
+        // Nodes in a cycle are all used, so fall back to the first node.
+        for node in if unused.is_empty() { vec![0] } else { unused } {
+            group.outputs.push(GroupOutput { name: group.graph.nodes()[node].name.clone(), node, output: 0 });
+        }
+    }
+
+    for &id in &selected {
+        let key = state.snarl[id].node_id_key;
+        state.remove(key);
+    }
+    let name = unique_name(state.snarl, &NoiseType::GROUP.lowercase_name());
+    let group_id = insert_graph_node(state.snarl, state.slot_to_node, state.changed_nodes, origin, |key| {
+        GraphNode::new(key, name, NoiseType::GROUP, NoiseConfig::Group(Box::new(group)))
+    });
+    for (input, source) in sources.into_iter().enumerate() {
+        state.snarl.connect(source, InPinId { node: group_id, input });
+    }
+    for (output, consumer) in consumers {
+        state.snarl.connect(OutPinId { node: group_id, output }, consumer);
+    }
+}
+
+/// Replaces a group node with the nodes inside it, rewiring its inputs and outputs to them.
+pub fn expand(state: &mut GraphState<'_>, node_id: NodeId) {
+    let Some(info) = state.snarl.get_node_info(node_id) else { return };
+    let NoiseConfig::Group(group) = &info.value.config else { return };
+    let group = group.clone();
+    let origin = info.pos.to_vec2();
+    let key = info.value.node_id_key;
+    let wires: Vec<(OutPinId, InPinId)> = state.snarl
+        .wires()
+        .filter(|(out_pin, in_pin)| (out_pin.node == node_id) != (in_pin.node == node_id))
+        .collect();
+    state.remove(key);
+
+    let inner = insert_saved_graph(state.snarl, state.slot_to_node, state.changed_nodes, &group.graph, origin);
+    for (out_pin, in_pin) in wires {
+        if in_pin.node == node_id {
+            let Some(input) = group.inputs.get(in_pin.input) else { continue };
+            for target in &input.targets {
+                let Some(&target_node) = inner.get(target.node) else { continue };
+                state.snarl.connect(out_pin, InPinId { node: target_node, input: target.input });
+            }
+        } else {
+            let Some(output) = group.outputs.get(out_pin.output) else { continue };
+            let Some(&output_node) = inner.get(output.node) else { continue };
+            state.snarl.connect(OutPinId { node: output_node, output: output.output }, in_pin);
+        }
+    }
+}
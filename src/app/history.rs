@@ -0,0 +1,596 @@
+//! Undo and redo for graph edits.
+//!
+//! Rather than every widget recording its own edits, the graph is captured before each frame that
+//! could edit it and compared afterwards. The differences become the commands of one undo step, so edits made by
+//! `egui_snarl` itself, like dragging nodes around, are covered too.
+
+use std::collections::{HashMap, HashSet};
+
+use egui::Pos2;
+use egui_snarl::{InPinId, NodeId, OutPinId, Snarl};
+use slotmap::SlotMap;
+
+use crate::graph::{SavedNode, SeedMode};
+
+use super::{insert_graph_node, GraphNode, NodeSlotKey};
+
+/// Most undo steps kept before the oldest are forgotten.
+const MAX_STEPS: usize = 200;
+
+/// Consecutive edits to the same nodes within this many seconds are merged into one step, so that
+/// typing a name is undone in one go.
+const COALESCE_SECONDS: f64 = 0.75;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Wire {
+    from: NodeSlotKey,
+    output: usize,
+    to: NodeSlotKey,
+    input: usize,
+}
+
+enum Command {
+    InsertNode { key: NodeSlotKey, node: SavedNode },
+    /// Removing a node also removes its wires, which are restored with it.
+    RemoveNode { key: NodeSlotKey, node: SavedNode, wires: Vec<Wire> },
+    MoveNode { key: NodeSlotKey, from: Pos2, to: Pos2 },
+    Connect(Wire),
+    Disconnect(Wire),
+    EditNode { key: NodeSlotKey, before: SavedNode, after: SavedNode },
+    SetMasterSeed { from: u32, to: u32 },
+}
+
+/// The parts of the app that commands change.
+pub struct GraphState<'a> {
+    pub snarl: &'a mut Snarl<GraphNode>,
+    pub slot_to_node: &'a mut SlotMap<NodeSlotKey, NodeId>,
+    pub changed_nodes: &'a mut HashSet<NodeSlotKey>,
+    pub master_seed: &'a mut u32,
+}
+
+impl GraphState<'_> {
+    fn node_id(&self, key: NodeSlotKey) -> Option<NodeId> {
+        self.slot_to_node.get(key).copied()
+    }
+
+    fn insert(&mut self, node: &SavedNode) -> NodeSlotKey {
+        let node_id = insert_graph_node(
+            self.snarl,
+            self.slot_to_node,
+            self.changed_nodes,
+            Pos2::from(node.position),
+            |key| GraphNode::from_saved(key, node));
+        self.snarl[node_id].node_id_key
+    }
+
+    /// Removes a node, marking the nodes that depended on it for recalculation.
+    pub fn remove(&mut self, key: NodeSlotKey) {
+        let Some(node_id) = self.slot_to_node.remove(key) else { return };
+        // Nodes that were reading from this one now have an unconnected input.
+        let dependents: Vec<NodeId> = self.snarl
+            .wires()
+            .filter(|(from, _)| from.node == node_id)
+            .map(|(_, to)| to.node)
+            .collect();
+        for dependent in dependents {
+            self.changed_nodes.insert(self.snarl[dependent].node_id_key);
+        }
+        self.snarl.remove_node(node_id);
+    }
+
+    fn wire_pins(&self, wire: &Wire) -> Option<(OutPinId, InPinId)> {
+        Some((
+            OutPinId { node: self.node_id(wire.from)?, output: wire.output },
+            InPinId { node: self.node_id(wire.to)?, input: wire.input },
+        ))
+    }
+
+    fn connect(&mut self, wire: &Wire) {
+        let Some((from, to)) = self.wire_pins(wire) else { return };
+        self.snarl.connect(from, to);
+        self.changed_nodes.insert(wire.to);
+    }
+
+    fn disconnect(&mut self, wire: &Wire) {
+        let Some((from, to)) = self.wire_pins(wire) else { return };
+        self.snarl.disconnect(from, to);
+        self.changed_nodes.insert(wire.to);
+    }
+
+    fn set_position(&mut self, key: NodeSlotKey, position: Pos2) {
+        let Some(node_id) = self.node_id(key) else { return };
+        if let Some(info) = self.snarl.get_node_info_mut(node_id) {
+            info.pos = position;
+        }
+    }
+
+    /// Restores a node's edits, keeping how it's currently previewed.
+    fn restore(&mut self, key: NodeSlotKey, node: &SavedNode) {
+        let Some(node_id) = self.node_id(key) else { return };
+        let graph_node = &mut self.snarl[node_id];
+        let mut node = node.clone();
+        keep_view(&mut node, &graph_node.to_saved(Pos2::ZERO));
+        graph_node.restore(&node);
+        self.changed_nodes.insert(key);
+    }
+
+    /// Sets the master seed, marking the nodes whose seeds are offset from it for recalculation.
+    pub fn set_master_seed(&mut self, master_seed: u32) {
+        *self.master_seed = master_seed;
+        self.changed_nodes.extend(self.snarl
+            .nodes()
+            .filter(|node| node.seed_mode == SeedMode::Offset)
+            .map(|node| node.node_id_key));
+    }
+}
+
+impl Command {
+    /// Applies the command, or reverts it if `undo` is set. Returns the old and new key of a node
+    /// that was inserted again, as nodes get a new key each time they're inserted.
+    fn apply(&self, undo: bool, state: &mut GraphState<'_>) -> Option<(NodeSlotKey, NodeSlotKey)> {
+        match (self, undo) {
+            (Command::InsertNode { key, node }, false) | (Command::RemoveNode { key, node, .. }, true) => {
+                let new_key = state.insert(node);
+                if let Command::RemoveNode { wires, .. } = self {
+                    for wire in wires {
+                        state.connect(&wire.replace_key(*key, new_key));
+                    }
+                }
+                return Some((*key, new_key));
+            }
+            (Command::InsertNode { key, .. }, true) | (Command::RemoveNode { key, .. }, false) => state.remove(*key),
+            (Command::MoveNode { key, from, to }, _) => state.set_position(*key, if undo { *from } else { *to }),
+            (Command::Connect(wire), false) | (Command::Disconnect(wire), true) => state.connect(wire),
+            (Command::Connect(wire), true) | (Command::Disconnect(wire), false) => state.disconnect(wire),
+            (Command::EditNode { key, before, after }, _) => state.restore(*key, if undo { before } else { after }),
+            (Command::SetMasterSeed { from, to }, _) => state.set_master_seed(if undo { *from } else { *to }),
+        }
+        None
+    }
+
+    fn replace_key(&mut self, old: NodeSlotKey, new: NodeSlotKey) {
+        match self {
+            Command::InsertNode { key, .. } | Command::MoveNode { key, .. } | Command::EditNode { key, .. } => {
+                key.replace_if(old, new);
+            }
+            Command::RemoveNode { key, wires, .. } => {
+                key.replace_if(old, new);
+                for wire in wires {
+                    *wire = wire.replace_key(old, new);
+                }
+            }
+            Command::Connect(wire) | Command::Disconnect(wire) => *wire = wire.replace_key(old, new),
+            Command::SetMasterSeed { .. } => {}
+        }
+    }
+
+    /// Merges a later change to the same node into this one. See [`can_coalesce`].
+    fn coalesce(&mut self, later: &Command) {
+        match (self, later) {
+            (Command::MoveNode { to, .. }, Command::MoveNode { to: later_to, .. }) => *to = *later_to,
+            (Command::EditNode { after, .. }, Command::EditNode { after: later_after, .. }) => after.clone_from(later_after),
+            (Command::SetMasterSeed { to, .. }, Command::SetMasterSeed { to: later_to, .. }) => *to = *later_to,
+            _ => {}
+        }
+    }
+}
+
+impl Wire {
+    fn replace_key(&self, old: NodeSlotKey, new: NodeSlotKey) -> Wire {
+        let mut wire = *self;
+        wire.from.replace_if(old, new);
+        wire.to.replace_if(old, new);
+        wire
+    }
+}
+
+impl NodeSlotKey {
+    fn replace_if(&mut self, old: NodeSlotKey, new: NodeSlotKey) {
+        if *self == old {
+            *self = new;
+        }
+    }
+}
+
+/// The undoable state of the graph at one moment.
+pub struct Snapshot {
+    nodes: HashMap<NodeSlotKey, SavedNode>,
+    wires: HashSet<Wire>,
+    master_seed: u32,
+}
+
+impl Snapshot {
+    pub fn capture(snarl: &Snarl<GraphNode>, master_seed: u32) -> Self {
+        Self {
+            nodes: snarl
+                .nodes_pos_ids()
+                .map(|(_, pos, node)| (node.node_id_key, node.to_saved(pos)))
+                .collect(),
+            wires: snarl
+                .wires()
+                .map(|(from, to)| Wire {
+                    from: snarl[from.node].node_id_key,
+                    output: from.output,
+                    to: snarl[to.node].node_id_key,
+                    input: to.input,
+                })
+                .collect(),
+            master_seed,
+        }
+    }
+
+    /// Lists the commands that turn this snapshot into `after`, in the order they should be
+    /// applied.
+    fn commands_to(&self, after: &Snapshot) -> Vec<Command> {
+        let mut commands = Vec::new();
+        if after.master_seed != self.master_seed {
+            commands.push(Command::SetMasterSeed { from: self.master_seed, to: after.master_seed });
+        }
+        let removed = |key: &NodeSlotKey| !after.nodes.contains_key(key);
+        // Wires of removed nodes are restored along with the node.
+        commands.extend(self.wires
+            .difference(&after.wires)
+            .filter(|wire| !removed(&wire.from) && !removed(&wire.to))
+            .map(|wire| Command::Disconnect(*wire)));
+        for (key, node) in &self.nodes {
+            match after.nodes.get(key) {
+                None => commands.push(Command::RemoveNode {
+                    key: *key,
+                    node: node.clone(),
+                    wires: self.wires.iter().filter(|wire| wire.from == *key || wire.to == *key).copied().collect(),
+                }),
+                Some(after_node) => {
+                    if after_node.position != node.position {
+                        commands.push(Command::MoveNode {
+                            key: *key,
+                            from: Pos2::from(node.position),
+                            to: Pos2::from(after_node.position),
+                        });
+                    }
+                    let mut edited = SavedNode { position: node.position, ..after_node.clone() };
+                    keep_view(&mut edited, node);
+                    if edited != *node {
+                        commands.push(Command::EditNode { key: *key, before: node.clone(), after: edited });
+                    }
+                }
+            }
+        }
+        for (key, node) in &after.nodes {
+            if !self.nodes.contains_key(key) {
+                commands.push(Command::InsertNode { key: *key, node: node.clone() });
+            }
+        }
+        commands.extend(after.wires.difference(&self.wires).map(|wire| Command::Connect(*wire)));
+        commands
+    }
+}
+
+pub struct History {
+    undo: Vec<Vec<Command>>,
+    redo: Vec<Vec<Command>>,
+    /// When the last step was recorded, for coalescing. Steps that were undone or redone since
+    /// aren't continued.
+    last_recorded: f64,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self { undo: Vec::new(), redo: Vec::new(), last_recorded: f64::NEG_INFINITY }
+    }
+}
+
+impl History {
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.last_recorded = f64::NEG_INFINITY;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Records the edits made since `before` was captured as one step. Edits continuing the
+    /// previous step, like the rest of a slider drag, are merged into it instead.
+    pub fn record(&mut self, before: &Snapshot, after: &Snapshot, time: f64, pointer_down: bool) {
+        let commands = before.commands_to(after);
+        if commands.is_empty() {
+            return;
+        }
+        let continuing = pointer_down || time - self.last_recorded < COALESCE_SECONDS;
+        self.last_recorded = time;
+        self.redo.clear();
+        if continuing {
+            if let Some(last) = self.undo.last_mut() {
+                if last.len() == commands.len() && last.iter().zip(&commands).all(|(a, b)| can_coalesce(a, b)) {
+                    for (command, later) in last.iter_mut().zip(&commands) {
+                        command.coalesce(later);
+                    }
+                    return;
+                }
+            }
+        }
+        self.undo.push(commands);
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn undo(&mut self, state: &mut GraphState<'_>) {
+        let Some(mut step) = self.undo.pop() else { return };
+        for index in (0..step.len()).rev() {
+            self.apply(&mut step, index, true, state);
+        }
+        self.redo.push(step);
+        self.last_recorded = f64::NEG_INFINITY;
+    }
+
+    pub fn redo(&mut self, state: &mut GraphState<'_>) {
+        let Some(mut step) = self.redo.pop() else { return };
+        for index in 0..step.len() {
+            self.apply(&mut step, index, false, state);
+        }
+        self.undo.push(step);
+        self.last_recorded = f64::NEG_INFINITY;
+    }
+
+    fn apply(&mut self, step: &mut [Command], index: usize, undo: bool, state: &mut GraphState<'_>) {
+        let Some((old, new)) = step[index].apply(undo, state) else { return };
+        for command in step.iter_mut().chain(self.undo.iter_mut().flatten()).chain(self.redo.iter_mut().flatten()) {
+            command.replace_key(old, new);
+        }
+    }
+}
+
+/// Copies the parts of a node that only change how it's previewed, like the heightfield's camera,
+/// which aren't undoable edits.
+fn keep_view(node: &mut SavedNode, view: &SavedNode) {
+    node.projection = view.projection;
+    node.animated = view.animated;
+    node.show_heightfield = view.show_heightfield;
+    node.heightfield_view = view.heightfield_view;
+    node.z = view.z;
+    node.w = view.w;
+}
+
+/// Whether two commands are the same kind of change to the same node, or both set the master seed.
+fn can_coalesce(a: &Command, b: &Command) -> bool {
+    match (a, b) {
+        (Command::MoveNode { key, .. }, Command::MoveNode { key: b_key, .. }) => key == b_key,
+        (Command::EditNode { key, .. }, Command::EditNode { key: b_key, .. }) => key == b_key,
+        (Command::SetMasterSeed { .. }, Command::SetMasterSeed { .. }) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+
+    use crate::{noises::NoiseType, params::ParamValue};
+
+    use super::*;
+
+    /// The parts of the app that history works on.
+    struct Editor {
+        snarl: Snarl<GraphNode>,
+        slot_to_node: SlotMap<NodeSlotKey, NodeId>,
+        changed_nodes: HashSet<NodeSlotKey>,
+        master_seed: u32,
+    }
+
+    impl Editor {
+        /// Perlin noise "p" and a constant "c" wired into a max "m".
+        fn new() -> Self {
+            let mut editor = Self { snarl: Snarl::new(), slot_to_node: SlotMap::with_key(), changed_nodes: HashSet::new(), master_seed: 0 };
+            let p = editor.add("p", NoiseType::PERLIN, pos2(0.0, 0.0));
+            let c = editor.add("c", NoiseType::CONSTANT, pos2(0.0, 100.0));
+            let m = editor.add("m", NoiseType::MAX, pos2(200.0, 50.0));
+            editor.snarl.connect(OutPinId { node: p, output: 0 }, InPinId { node: m, input: 0 });
+            editor.snarl.connect(OutPinId { node: c, output: 0 }, InPinId { node: m, input: 1 });
+            editor
+        }
+
+        fn add(&mut self, name: &str, noise_type: NoiseType, pos: Pos2) -> NodeId {
+            insert_graph_node(&mut self.snarl, &mut self.slot_to_node, &mut self.changed_nodes, pos, |key| {
+                GraphNode::new(key, name.to_string(), noise_type, noise_type.config())
+            })
+        }
+
+        fn node(&self, name: &str) -> NodeId {
+            self.snarl.node_ids().find(|(_, node)| node.name == name).expect("Missing node").0
+        }
+
+        fn state(&mut self) -> GraphState<'_> {
+            GraphState {
+                snarl: &mut self.snarl,
+                slot_to_node: &mut self.slot_to_node,
+                changed_nodes: &mut self.changed_nodes,
+                master_seed: &mut self.master_seed,
+            }
+        }
+
+        fn snapshot(&self) -> Snapshot {
+            Snapshot::capture(&self.snarl, self.master_seed)
+        }
+
+        fn set_constant(&mut self, value: f64) {
+            let c = self.node("c");
+            self.snarl[c].config.values_mut().set("value", ParamValue::Float(value));
+        }
+
+        fn undo(&mut self, history: &mut History) {
+            history.undo(&mut self.state());
+        }
+
+        fn redo(&mut self, history: &mut History) {
+            history.redo(&mut self.state());
+        }
+    }
+
+    /// Wires by the names of their nodes, as nodes get new keys when their removal is undone.
+    type NamedWire = (String, usize, String, usize);
+
+    /// A snapshot's nodes and wires, independent of the nodes' keys.
+    fn contents(snapshot: &Snapshot) -> (Vec<SavedNode>, Vec<NamedWire>, u32) {
+        let mut nodes: Vec<SavedNode> = snapshot.nodes.values().cloned().collect();
+        nodes.sort_by(|a, b| a.name.cmp(&b.name));
+        let name = |key: &NodeSlotKey| snapshot.nodes[key].name.clone();
+        let mut wires: Vec<NamedWire> = snapshot.wires
+            .iter()
+            .map(|wire| (name(&wire.from), wire.output, name(&wire.to), wire.input))
+            .collect();
+        wires.sort();
+        (nodes, wires, snapshot.master_seed)
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_snapshots() {
+        let mut editor = Editor::new();
+        let mut history = History::default();
+        let before = editor.snapshot();
+
+        // One of every kind of command.
+        let (p, c, m) = (editor.node("p"), editor.node("c"), editor.node("m"));
+        editor.snarl.get_node_info_mut(c).unwrap().pos = pos2(50.0, 150.0);
+        editor.set_constant(0.5);
+        editor.snarl.disconnect(OutPinId { node: c, output: 0 }, InPinId { node: m, input: 1 });
+        let p_key = editor.snarl[p].node_id_key;
+        editor.state().remove(p_key);
+        let n = editor.add("n", NoiseType::CHECKERBOARD, pos2(0.0, 0.0));
+        editor.snarl.connect(OutPinId { node: n, output: 0 }, InPinId { node: m, input: 0 });
+        editor.master_seed = 5;
+        let after = editor.snapshot();
+        history.record(&before, &after, 0.0, false);
+
+        editor.undo(&mut history);
+        assert!(contents(&editor.snapshot()) == contents(&before));
+        assert!(!history.can_undo());
+        editor.redo(&mut history);
+        assert!(contents(&editor.snapshot()) == contents(&after));
+        // The removed node came back with a new key, which the redo step has to follow.
+        editor.undo(&mut history);
+        assert!(contents(&editor.snapshot()) == contents(&before));
+    }
+
+    #[test]
+    fn nothing_is_recorded_without_changes() {
+        let editor = Editor::new();
+        let mut history = History::default();
+        history.record(&editor.snapshot(), &editor.snapshot(), 0.0, false);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn quick_edits_to_the_same_node_are_one_step() {
+        let mut editor = Editor::new();
+        let mut history = History::default();
+        let original = editor.snapshot();
+        for (index, time) in [0.0, 0.5, 1.0].into_iter().enumerate() {
+            let before = editor.snapshot();
+            editor.set_constant(index as f64 + 1.0);
+            history.record(&before, &editor.snapshot(), time, false);
+        }
+        editor.undo(&mut history);
+        assert!(contents(&editor.snapshot()) == contents(&original));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn edits_after_a_pause_are_separate_steps() {
+        let mut editor = Editor::new();
+        let mut history = History::default();
+        let original = editor.snapshot();
+        let before = editor.snapshot();
+        editor.set_constant(1.0);
+        history.record(&before, &editor.snapshot(), 0.0, false);
+        let first = editor.snapshot();
+        editor.set_constant(2.0);
+        history.record(&first, &editor.snapshot(), COALESCE_SECONDS + 0.1, false);
+
+        editor.undo(&mut history);
+        assert!(contents(&editor.snapshot()) == contents(&first));
+        editor.undo(&mut history);
+        assert!(contents(&editor.snapshot()) == contents(&original));
+    }
+
+    #[test]
+    fn drags_continue_a_step_however_long_they_take() {
+        let mut editor = Editor::new();
+        let mut history = History::default();
+        let original = editor.snapshot();
+        let c = editor.node("c");
+        for time in [0.0, 5.0, 10.0] {
+            let before = editor.snapshot();
+            editor.snarl.get_node_info_mut(c).unwrap().pos.x += 10.0;
+            history.record(&before, &editor.snapshot(), time, true);
+        }
+        editor.undo(&mut history);
+        assert!(contents(&editor.snapshot()) == contents(&original));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn edits_right_after_an_undo_are_a_new_step() {
+        let mut editor = Editor::new();
+        let mut history = History::default();
+        let before = editor.snapshot();
+        editor.set_constant(1.0);
+        history.record(&before, &editor.snapshot(), 0.0, false);
+        let first = editor.snapshot();
+        editor.set_constant(2.0);
+        history.record(&first, &editor.snapshot(), COALESCE_SECONDS + 0.1, false);
+
+        // Redoing the second step, then editing at once, mustn't fold the edit into it.
+        editor.undo(&mut history);
+        editor.redo(&mut history);
+        let second = editor.snapshot();
+        editor.set_constant(3.0);
+        history.record(&second, &editor.snapshot(), COALESCE_SECONDS + 0.2, false);
+        editor.undo(&mut history);
+        assert!(contents(&editor.snapshot()) == contents(&second));
+    }
+
+    #[test]
+    fn previews_are_not_edits() {
+        let mut editor = Editor::new();
+        let mut history = History::default();
+        let before = editor.snapshot();
+        let p = editor.node("p");
+        editor.snarl[p].heightfield_view.yaw += 1.0;
+        editor.snarl[p].z = 0.5;
+        history.record(&before, &editor.snapshot(), 0.0, false);
+        assert!(!history.can_undo());
+
+        // Undoing an edit leaves the preview as it is.
+        let before = editor.snapshot();
+        editor.set_constant(1.0);
+        history.record(&before, &editor.snapshot(), 1.0, false);
+        let c = editor.node("c");
+        editor.snarl[c].w = 0.25;
+        editor.undo(&mut history);
+        assert_eq!(editor.snarl[c].w, 0.25);
+        assert!(editor.snarl[c].config == before.nodes[&editor.snarl[c].node_id_key].config);
+    }
+
+    #[test]
+    fn quick_edits_to_different_nodes_are_separate_steps() {
+        let mut editor = Editor::new();
+        let mut history = History::default();
+        let before = editor.snapshot();
+        editor.set_constant(1.0);
+        history.record(&before, &editor.snapshot(), 0.0, false);
+        let first = editor.snapshot();
+        let p = editor.node("p");
+        editor.snarl[p].name = "q".to_string();
+        history.record(&first, &editor.snapshot(), 0.1, false);
+
+        editor.undo(&mut history);
+        assert!(contents(&editor.snapshot()) == contents(&first));
+        assert!(history.can_undo());
+    }
+}
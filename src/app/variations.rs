@@ -0,0 +1,327 @@
+//! A window sweeping one or two parameters, or the master seed, across a grid of thumbnails of a
+//! node. Clicking a thumbnail applies its values to the graph.
+
+use std::{collections::{HashMap, HashSet}, sync::{atomic::{AtomicUsize, Ordering}, mpsc::Sender, Arc}};
+
+use egui::{TextureHandle, Vec2};
+use egui_snarl::{NodeId, Snarl};
+use slotmap::SlotMap;
+
+use crate::{graph::{NoiseGraph, SeedMode}, params::ParamKind};
+
+use super::{to_noise_graph, GraphNode, NodeSlotKey, RecalculateRequest, RecalculateResult, RenderTarget, PREVIEW_TEXTURE_OPTIONS};
+
+/// Resolution of each thumbnail.
+const THUMBNAIL_SIZE: usize = 96;
+
+/// The most values an axis can sweep.
+const MAX_STEPS: usize = 12;
+
+/// A number that can be swept.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SweepTarget {
+    MasterSeed,
+    /// A parameter of a node that can be driven by a scalar, see [`ParamSpec::is_scalar`].
+    ///
+    /// [`ParamSpec::is_scalar`]: crate::params::ParamSpec::is_scalar
+    Param { node: NodeSlotKey, name: &'static str },
+}
+
+impl SweepTarget {
+    fn name(&self, snarl: &Snarl<GraphNode>, slot_to_node: &SlotMap<NodeSlotKey, NodeId>) -> String {
+        match *self {
+            SweepTarget::MasterSeed => "Master seed".to_string(),
+            SweepTarget::Param { node, name } => {
+                let Some(node) = slot_to_node.get(node).and_then(|&id| snarl.get_node(id)) else { return name.to_string() };
+                let label = node.noise_type.param(name).map_or(name, |spec| spec.label);
+                format!("{}: {label}", node.name)
+            }
+        }
+    }
+
+    /// Seeds and integers are swept in whole steps.
+    fn is_integer(&self, snarl: &Snarl<GraphNode>, slot_to_node: &SlotMap<NodeSlotKey, NodeId>) -> bool {
+        match *self {
+            SweepTarget::MasterSeed => true,
+            SweepTarget::Param { node, name } => slot_to_node
+                .get(node)
+                .and_then(|&id| snarl.get_node(id)?.noise_type.param(name))
+                .is_some_and(|spec| !matches!(spec.kind, ParamKind::Float { .. })),
+        }
+    }
+
+    /// The target's current value, which an axis sweeps from when the target is picked.
+    fn current(&self, snarl: &Snarl<GraphNode>, slot_to_node: &SlotMap<NodeSlotKey, NodeId>, master_seed: u32) -> f64 {
+        match *self {
+            SweepTarget::MasterSeed => master_seed.into(),
+            SweepTarget::Param { node, name } => slot_to_node
+                .get(node)
+                .and_then(|&id| {
+                    let node = snarl.get_node(id)?;
+                    Some(node.noise_type.param(name)?.float(node.config.values()))
+                })
+                .unwrap_or(0.0),
+        }
+    }
+
+    /// Sets the target in a saved graph. `indices` maps the editor's nodes to the saved graph's.
+    /// Parameters that have since been exposed as pins are set by their wires instead.
+    fn apply_to(
+            &self,
+            graph: &mut NoiseGraph,
+            indices: &HashMap<NodeId, usize>,
+            slot_to_node: &SlotMap<NodeSlotKey, NodeId>,
+            value: f64) {
+        match *self {
+            SweepTarget::MasterSeed => graph.set_master_seed(value.round() as i64 as u32),
+            SweepTarget::Param { node, name } => {
+                let Some(&index) = slot_to_node.get(node).and_then(|id| indices.get(id)) else { return };
+                let saved = &mut graph.nodes_mut()[index];
+                if saved.param_pins.iter().any(|pin| pin == name) {
+                    return;
+                }
+                let Some(value) = saved.noise_type.param(name).and_then(|spec| spec.from_scalar(value)) else { return };
+                saved.config.values_mut().set(name, value);
+            }
+        }
+    }
+}
+
+struct SweepAxis {
+    target: Option<SweepTarget>,
+    from: f64,
+    to: f64,
+    steps: usize,
+}
+
+impl SweepAxis {
+    fn value(&self, step: usize) -> f64 {
+        if self.steps <= 1 {
+            self.from
+        } else {
+            self.from + (self.to - self.from) * step as f64 / (self.steps - 1) as f64
+        }
+    }
+}
+
+struct Cell {
+    values: Vec<(SweepTarget, f64)>,
+    texture: Option<TextureHandle>,
+}
+
+pub struct VariationGrid {
+    node: NodeSlotKey,
+    axes: [SweepAxis; 2],
+    /// Bumped for each sweep, so that thumbnails of earlier sweeps are dropped.
+    version: Arc<AtomicUsize>,
+    /// Whether the graph changed since the thumbnails were requested.
+    stale: bool,
+    cells: Vec<Cell>,
+    /// Values of the thumbnail the user clicked, waiting to be applied to the graph.
+    chosen: Option<Vec<(SweepTarget, f64)>>,
+}
+
+impl VariationGrid {
+    /// Starts with the columns sweeping eight seeds: the master seed if any node is offset from
+    /// it, otherwise the first seed parameter in the graph.
+    pub fn new(node: NodeSlotKey, snarl: &Snarl<GraphNode>, master_seed: u32) -> Self {
+        let uses_master_seed = snarl
+            .nodes()
+            .any(|node| node.seed_mode == SeedMode::Offset && node.seed_params().next().is_some());
+        let (target, from) = match snarl.nodes().find_map(|node| Some((node, node.seed_params().next()?))) {
+            Some((node, spec)) if !uses_master_seed => (
+                SweepTarget::Param { node: node.node_id_key, name: spec.name },
+                spec.float(node.config.values()),
+            ),
+            _ => (SweepTarget::MasterSeed, master_seed.into()),
+        };
+        Self {
+            node,
+            axes: [
+                SweepAxis { target: Some(target), from, to: from + 7.0, steps: 8 },
+                SweepAxis { target: None, from: 0.0, to: 1.0, steps: 4 },
+            ],
+            version: Arc::new(AtomicUsize::new(0)),
+            stale: true,
+            cells: Vec::new(),
+            chosen: None,
+        }
+    }
+
+    /// The values of the thumbnail the user clicked since this was last called.
+    pub fn take_chosen(&mut self) -> Option<Vec<(SweepTarget, f64)>> {
+        self.chosen.take()
+    }
+
+    /// Sweeps again if the node's output changed.
+    pub fn invalidate(&mut self, dirty_nodes: &HashSet<NodeSlotKey>) {
+        self.stale |= dirty_nodes.contains(&self.node);
+    }
+
+    /// Shows a rendered thumbnail, if it's from the latest sweep.
+    pub fn receive(&mut self, ctx: &egui::Context, cell: usize, result: RecalculateResult) {
+        if result.new_version != self.version.load(Ordering::SeqCst) {
+            return;
+        }
+        let Some(cell) = self.cells.get_mut(cell) else { return };
+        match &mut cell.texture {
+            Some(texture) => texture.set(result.texture, PREVIEW_TEXTURE_OPTIONS),
+            None => {
+                cell.texture = Some(ctx.load_texture("variation", result.texture, PREVIEW_TEXTURE_OPTIONS));
+            }
+        }
+    }
+
+    /// Shows the window, returning false once it has been closed or its node removed.
+    pub fn show(
+            &mut self,
+            ctx: &egui::Context,
+            snarl: &Snarl<GraphNode>,
+            slot_to_node: &SlotMap<NodeSlotKey, NodeId>,
+            master_seed: u32,
+            sender: &Sender<RecalculateRequest>) -> bool {
+        let Some(&node_id) = slot_to_node.get(self.node) else { return false };
+        let node = &snarl[node_id];
+        let mut open = true;
+        let mut resweep = self.stale;
+        let targets = sweep_targets(snarl);
+        egui::Window::new(format!("Variations: {}", node.name))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("axes").num_columns(5).show(ui, |ui| {
+                    for (index, axis) in self.axes.iter_mut().enumerate() {
+                        ui.label(if index == 0 { "Columns:" } else { "Rows:" });
+                        let selected = axis.target.map_or("None".to_string(), |target| target.name(snarl, slot_to_node));
+                        let mut picked = None;
+                        egui::ComboBox::from_id_salt(("sweep_target", index))
+                            .selected_text(selected)
+                            .show_ui(ui, |ui| {
+                                // Only the rows can be left out, leaving a single row.
+                                if index > 0 && ui.selectable_label(axis.target.is_none(), "None").clicked() {
+                                    picked = Some(None);
+                                }
+                                for target in &targets {
+                                    let name = target.name(snarl, slot_to_node);
+                                    if ui.selectable_label(axis.target == Some(*target), name).clicked() {
+                                        picked = Some(Some(*target));
+                                    }
+                                }
+                            });
+                        if let Some(target) = picked {
+                            if target != axis.target {
+                                axis.target = target;
+                                if let Some(target) = target {
+                                    axis.from = target.current(snarl, slot_to_node, master_seed);
+                                    axis.to = if target.is_integer(snarl, slot_to_node) {
+                                        axis.from + axis.steps as f64 - 1.0
+                                    } else {
+                                        axis.from + 1.0
+                                    };
+                                }
+                                resweep = true;
+                            }
+                        }
+                        ui.add_enabled_ui(axis.target.is_some(), |ui| {
+                            resweep |= ui.add(egui::DragValue::new(&mut axis.from).speed(0.05).prefix("from ")).changed();
+                            resweep |= ui.add(egui::DragValue::new(&mut axis.to).speed(0.05).prefix("to ")).changed();
+                            resweep |= ui
+                                .add(egui::DragValue::new(&mut axis.steps).range(1..=MAX_STEPS).suffix(" steps"))
+                                .changed();
+                        });
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+
+                let columns = self.axes[0].steps;
+                egui::Grid::new("thumbnails").spacing(Vec2::splat(4.0)).show(ui, |ui| {
+                    for (index, cell) in self.cells.iter().enumerate() {
+                        let label = cell.values
+                            .iter()
+                            .map(|(target, value)| format!("{}: {}", target.name(snarl, slot_to_node), format_value(*value)))
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        let size = Vec2::splat(THUMBNAIL_SIZE as f32);
+                        let response = match &cell.texture {
+                            Some(texture) => ui.add(egui::ImageButton::new((texture.id(), size))),
+                            None => ui.add_sized(size, egui::Spinner::new()),
+                        };
+                        if response.on_hover_text(format!("{label}\nClick to apply")).clicked() {
+                            self.chosen = Some(cell.values.clone());
+                        }
+                        if (index + 1) % columns == 0 {
+                            ui.end_row();
+                        }
+                    }
+                });
+            });
+        if open && resweep {
+            self.sweep(snarl, slot_to_node, node_id, master_seed, sender);
+        }
+        open
+    }
+
+    /// Renders a thumbnail for every combination of the axes' values in the background.
+    fn sweep(
+            &mut self,
+            snarl: &Snarl<GraphNode>,
+            slot_to_node: &SlotMap<NodeSlotKey, NodeId>,
+            node_id: NodeId,
+            master_seed: u32,
+            sender: &Sender<RecalculateRequest>) {
+        let node = &snarl[node_id];
+        self.stale = false;
+        let new_version = self.version.fetch_add(1, Ordering::SeqCst) + 1;
+        let (graph, indices) = to_noise_graph(snarl, master_seed);
+        let mut values = Vec::new();
+        let rows = if self.axes[1].target.is_some() { self.axes[1].steps } else { 1 };
+        for row in 0..rows {
+            for column in 0..self.axes[0].steps {
+                values.push([(&self.axes[0], column), (&self.axes[1], row)]
+                    .into_iter()
+                    .filter_map(|(axis, step)| Some((axis.target?, axis.value(step))))
+                    .map(|(target, value)| {
+                        let integer = target.is_integer(snarl, slot_to_node);
+                        (target, if integer { value.round() } else { value })
+                    })
+                    .collect::<Vec<_>>());
+            }
+        }
+        // Keep the old thumbnails until the new ones arrive, which avoids flickering while dragging.
+        self.cells.resize_with(values.len(), || Cell { values: Vec::new(), texture: None });
+        for (index, (cell, values)) in self.cells.iter_mut().zip(values).enumerate() {
+            let mut variation = graph.clone();
+            for &(target, value) in &values {
+                target.apply_to(&mut variation, &indices, slot_to_node, value);
+            }
+            cell.values = values;
+            let _ = sender.send(RecalculateRequest {
+                node_id: self.node,
+                target: RenderTarget::Variation(index),
+                new_version,
+                config_version: Arc::clone(&self.version),
+                sample_settings: node.sample_settings(THUMBNAIL_SIZE),
+                heightfield: None,
+                noise_fn: variation.compile(indices[&node_id]),
+            });
+        }
+    }
+}
+
+/// The master seed and every parameter in the graph that a sweep can vary, which excludes those
+/// set through input pins.
+fn sweep_targets(snarl: &Snarl<GraphNode>) -> Vec<SweepTarget> {
+    let mut targets = vec![SweepTarget::MasterSeed];
+    for node in snarl.nodes() {
+        targets.extend(node.noise_type
+            .params()
+            .iter()
+            .filter(|spec| spec.is_scalar() && !node.param_pins.iter().any(|name| name == spec.name))
+            .map(|spec| SweepTarget::Param { node: node.node_id_key, name: spec.name }));
+    }
+    targets
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 { format!("{value}") } else { format!("{value:.3}") }
+}
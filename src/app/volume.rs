@@ -0,0 +1,176 @@
+//! A window showing orthogonal slices through a node's 3D volume.
+
+use std::sync::atomic::Ordering;
+
+use egui::{Color32, ColorImage, Pos2, Sense, Stroke, TextureHandle, Ui, Vec2};
+use egui_snarl::{NodeId, Snarl};
+use noise::utils::NoiseMap;
+use slotmap::SlotMap;
+use strum::VariantArray;
+
+use crate::render::{self, Dimensions, SampleSettings, SliceAxis};
+
+use super::{to_noise_graph, GraphNode, NodeSlotKey, PREVIEW_TEXTURE_OPTIONS};
+
+/// Resolution of each slice. Slices are rendered on the UI thread, so this is kept small.
+const SLICE_SIZE: usize = 128;
+
+/// Colour of the region above the isosurface threshold.
+const THRESHOLD_COLOR: Color32 = Color32::from_rgb(255, 150, 0);
+
+pub struct VolumeViewer {
+    node: NodeSlotKey,
+    /// Where the YZ, XZ and XY slices cut the x, y and z axes.
+    position: [f64; 3],
+    /// Size of the cube the slices span.
+    extent: f64,
+    show_threshold: bool,
+    threshold: f64,
+    /// Config version of the node when the slices were sampled.
+    sampled_version: Option<usize>,
+    maps: Vec<NoiseMap>,
+    textures: Vec<TextureHandle>,
+}
+
+impl VolumeViewer {
+    pub fn new(node: NodeSlotKey) -> Self {
+        Self {
+            node,
+            position: [0.5; 3],
+            extent: 1.0,
+            show_threshold: false,
+            threshold: 0.0,
+            sampled_version: None,
+            maps: Vec::new(),
+            textures: Vec::new(),
+        }
+    }
+
+    /// Shows the window, returning false once it has been closed or its node removed.
+    pub fn show(
+            &mut self,
+            ctx: &egui::Context,
+            snarl: &Snarl<GraphNode>,
+            slot_to_node: &SlotMap<NodeSlotKey, NodeId>,
+            master_seed: u32) -> bool {
+        let Some(&node_id) = slot_to_node.get(self.node) else { return false };
+        let node = &snarl[node_id];
+        let mut open = true;
+        let mut resample = self.sampled_version != Some(node.config_version.load(Ordering::SeqCst));
+        let mut recolor = false;
+        egui::Window::new(format!("Volume slices: {}", node.name))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for (axis, name) in ["X", "Y", "Z"].iter().enumerate() {
+                        resample |= ui
+                            .add(egui::Slider::new(&mut self.position[axis], 0.0..=self.extent).text(*name))
+                            .changed();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Extent:");
+                    resample |= ui
+                        .add(egui::DragValue::new(&mut self.extent).range(0.01..=1000.0).speed(0.05))
+                        .changed();
+                    recolor |= ui.checkbox(&mut self.show_threshold, "Isosurface").changed();
+                    if self.show_threshold {
+                        recolor |= ui.add(egui::Slider::new(&mut self.threshold, -1.0..=1.0).text("Threshold")).changed();
+                    }
+                });
+                if resample {
+                    self.position = self.position.map(|position| position.min(self.extent));
+                    self.sample(snarl, node_id, master_seed);
+                }
+                if resample || recolor || self.textures.is_empty() {
+                    self.update_textures(ui.ctx());
+                }
+                ui.horizontal(|ui| {
+                    for (axis, texture) in SliceAxis::VARIANTS.iter().zip(self.textures.clone()) {
+                        ui.vertical(|ui| {
+                            ui.label(axis.name());
+                            self.show_slice(ui, *axis, &texture);
+                        });
+                    }
+                });
+            });
+        open
+    }
+
+    fn sample(&mut self, snarl: &Snarl<GraphNode>, node_id: NodeId, master_seed: u32) {
+        let node = &snarl[node_id];
+        self.sampled_version = Some(node.config_version.load(Ordering::SeqCst));
+        let (graph, indices) = to_noise_graph(snarl, master_seed);
+        let noise = graph.compile(indices[&node_id]);
+        let settings = SampleSettings {
+            noise_width: self.extent,
+            noise_height: self.extent,
+            dimensions: node.dimensions.max(Dimensions::Three),
+            w: node.w,
+            ..SampleSettings::new(SLICE_SIZE, false, render::Projection::Plane)
+        };
+        self.maps = SliceAxis::VARIANTS
+            .iter()
+            .map(|axis| render::sample_slice(&noise, &settings, *axis, self.position[axis.normal()]))
+            .collect();
+    }
+
+    fn update_textures(&mut self, ctx: &egui::Context) {
+        let images: Vec<ColorImage> = self.maps.iter().map(|map| self.to_image(map)).collect();
+        if self.textures.len() == images.len() {
+            for (texture, image) in self.textures.iter_mut().zip(images) {
+                texture.set(image, PREVIEW_TEXTURE_OPTIONS);
+            }
+        } else {
+            self.textures = images
+                .into_iter()
+                .enumerate()
+                .map(|(index, image)| ctx.load_texture(format!("volume_slice_{index}"), image, PREVIEW_TEXTURE_OPTIONS))
+                .collect();
+        }
+    }
+
+    /// Converts a slice to grayscale, tinting the region above the threshold and outlining where
+    /// the isosurface crosses the slice.
+    fn to_image(&self, map: &NoiseMap) -> ColorImage {
+        let (width, height) = map.size();
+        let inside = |x: usize, y: usize| map.get_value(x, y) >= self.threshold;
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let gray = Color32::from_gray(render::to_gray(map.get_value(x, y)));
+                if !self.show_threshold || !inside(x, y) {
+                    return gray;
+                }
+                let on_edge = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)]
+                    .into_iter()
+                    .any(|(nx, ny)| nx < width && ny < height && !inside(nx, ny));
+                if on_edge { THRESHOLD_COLOR } else { gray.lerp_to_gamma(THRESHOLD_COLOR, 0.35) }
+            })
+            .collect();
+        ColorImage { size: [width, height], pixels }
+    }
+
+    /// Shows a slice with lines where the other two slices cross it. Dragging on the slice moves
+    /// those two slices.
+    fn show_slice(&mut self, ui: &mut Ui, axis: SliceAxis, texture: &TextureHandle) {
+        let size = Vec2::splat(SLICE_SIZE as f32 * 1.5);
+        let response = ui.add(egui::Image::new(texture).fit_to_exact_size(size).sense(Sense::click_and_drag()));
+        let rect = response.rect;
+        let [u, v] = axis.plane_axes();
+        if let Some(pointer) = response.interact_pointer_pos() {
+            let relative = ((pointer - rect.min) / rect.size()).clamp(Vec2::ZERO, Vec2::splat(1.0));
+            self.position[u] = relative.x as f64 * self.extent;
+            self.position[v] = relative.y as f64 * self.extent;
+            // Picked up by the next frame's resample.
+            self.sampled_version = None;
+            ui.ctx().request_repaint();
+        }
+        let to_screen = |position: f64| (position / self.extent) as f32;
+        let stroke = Stroke::new(1.0, Color32::from_rgba_unmultiplied(255, 255, 255, 160));
+        let x = rect.left() + to_screen(self.position[u]) * rect.width();
+        let y = rect.top() + to_screen(self.position[v]) * rect.height();
+        ui.painter().line_segment([Pos2::new(x, rect.top()), Pos2::new(x, rect.bottom())], stroke);
+        ui.painter().line_segment([Pos2::new(rect.left(), y), Pos2::new(rect.right(), y)], stroke);
+    }
+}
@@ -0,0 +1,209 @@
+#![warn(clippy::all, rust_2018_idioms)]
+
+//! Renders a node of a saved graph to image files without opening a window.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use noise_explorer::{
+    codegen::shader::{ShaderLanguage, ShaderProgram},
+    graph::NoiseGraph,
+    render::{self, Animation, Dimensions, Projection, SampleSettings},
+};
+use strum::VariantArray;
+
+const USAGE: &str = "\
+Usage: noise_render <GRAPH> --node <NAME> [OPTIONS]
+
+Options:
+  --node <NAME>          Name of the node to render
+  --output <FILE>        Write an 8 bit grayscale image, format chosen by extension
+  --heightmap <FILE>     Write a 16 bit grayscale PNG
+  --raw <FILE>           Write raw little endian 32 bit floats
+  --size <PIXELS>        Width of the output [default: 1024]
+  --projection <NAME>    plane, cylinder or sphere [default: the node's projection]
+  --tileable             Make plane projected output wrap seamlessly
+  --dimensions <N>       Evaluate the noise in 2, 3 or 4 dimensions [default: the node's dimensions]
+  --z <POSITION>         Position of the slice along the z axis [default: the node's slice]
+  --w <POSITION>         Position of the slice along the w axis [default: the node's slice]
+  --gif <FILE>           Write an animated GIF, moving through the noise's z axis over time
+  --png-sequence <FILE>  Write the animation's frames as numbered PNGs, e.g. FILE_0000.png
+  --duration <SECONDS>   Length of the animation [default: 4]
+  --fps <RATE>           Frames per second of the animation [default: 25]
+  --speed <DISTANCE>     Distance moved along the z axis per second [default: 0.25]
+  --wgsl <FILE>          Write the node as a WGSL function
+  --glsl <FILE>          Write the node as a GLSL function
+  --validate-shader      Compare the shader translation against the CPU noise over the plane
+  --help                 Print this message";
+
+#[derive(Default)]
+struct Args {
+    graph: Option<PathBuf>,
+    node: Option<String>,
+    output: Option<PathBuf>,
+    heightmap: Option<PathBuf>,
+    raw: Option<PathBuf>,
+    size: Option<usize>,
+    projection: Option<Projection>,
+    tileable: bool,
+    dimensions: Option<Dimensions>,
+    z: Option<f64>,
+    w: Option<f64>,
+    gif: Option<PathBuf>,
+    png_sequence: Option<PathBuf>,
+    animation: Animation,
+    wgsl: Option<PathBuf>,
+    glsl: Option<PathBuf>,
+    validate_shader: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or_else(|| format!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--node" => args.node = Some(value()?),
+            "--output" => args.output = Some(value()?.into()),
+            "--heightmap" => args.heightmap = Some(value()?.into()),
+            "--raw" => args.raw = Some(value()?.into()),
+            "--size" => {
+                let size = value()?;
+                args.size = Some(size.parse().map_err(|_| format!("Invalid size: {size}"))?);
+            }
+            "--projection" => {
+                let name = value()?;
+                let projection = Projection::VARIANTS
+                    .iter()
+                    .find(|projection| projection.name().eq_ignore_ascii_case(&name))
+                    .ok_or_else(|| format!("Unknown projection: {name}"))?;
+                args.projection = Some(*projection);
+            }
+            "--tileable" => args.tileable = true,
+            "--dimensions" => {
+                let count = value()?;
+                let dimensions = Dimensions::VARIANTS
+                    .iter()
+                    .find(|dimensions| dimensions.count().to_string() == count)
+                    .ok_or_else(|| format!("Invalid dimensions: {count}"))?;
+                args.dimensions = Some(*dimensions);
+            }
+            "--z" => args.z = Some(parse_number(&value()?)?),
+            "--w" => args.w = Some(parse_number(&value()?)?),
+            "--gif" => args.gif = Some(value()?.into()),
+            "--png-sequence" => args.png_sequence = Some(value()?.into()),
+            "--duration" => args.animation.duration = parse_number(&value()?)?,
+            "--fps" => args.animation.frames_per_second = parse_number(&value()?)?,
+            "--speed" => args.animation.speed = parse_number(&value()?)?,
+            "--wgsl" => args.wgsl = Some(value()?.into()),
+            "--glsl" => args.glsl = Some(value()?.into()),
+            "--validate-shader" => args.validate_shader = true,
+            "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {arg}\n\n{USAGE}")),
+            _ if args.graph.is_none() => args.graph = Some(arg.into()),
+            _ => return Err(format!("Unexpected argument: {arg}\n\n{USAGE}")),
+        }
+    }
+    Ok(args)
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    value.parse().map_err(|_| format!("Invalid number: {value}"))
+}
+
+fn run() -> Result<(), String> {
+    let args = parse_args()?;
+    let graph_path = args.graph.ok_or_else(|| USAGE.to_string())?;
+    let node_name = args.node.ok_or_else(|| format!("Missing --node\n\n{USAGE}"))?;
+    let render_image = args.output.is_some() || args.heightmap.is_some() || args.raw.is_some();
+    let animate = args.gif.is_some() || args.png_sequence.is_some();
+    let shader = args.wgsl.is_some() || args.glsl.is_some() || args.validate_shader;
+    if !render_image && !animate && !shader {
+        return Err(format!(
+            "Nothing to do, pass at least one of --output, --heightmap, --raw, --gif, --png-sequence, --wgsl, --glsl or \
+            --validate-shader\n\n{USAGE}"));
+    }
+    if animate && !(args.animation.duration > 0.0 && args.animation.frames_per_second > 0.0) {
+        return Err("--duration and --fps must be positive".to_string());
+    }
+
+    let graph = NoiseGraph::load(&graph_path)
+        .map_err(|err| format!("Failed to load {}: {err}", graph_path.display()))?;
+    let node_index = graph.node_index(&node_name).ok_or_else(|| {
+        let names: Vec<&str> = graph.nodes().iter().map(|node| node.name.as_str()).collect();
+        match names.iter().filter(|name| **name == node_name).count() {
+            0 => format!("No node named {node_name:?}, the graph contains: {}", names.join(", ")),
+            count => format!("{count} nodes are named {node_name:?}, rename all but one of them to pick it"),
+        }
+    })?;
+    let node = &graph.nodes()[node_index];
+    let size = args.size.unwrap_or(1024);
+
+    if shader {
+        let program = ShaderProgram::compile(&graph, node_index).map_err(|err| err.to_string())?;
+        for (path, language) in [(&args.wgsl, ShaderLanguage::Wgsl), (&args.glsl, ShaderLanguage::Glsl)] {
+            if let Some(path) = path {
+                std::fs::write(path, program.generate(language))
+                    .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+            }
+        }
+        if args.validate_shader {
+            // The shaders only cover the plane, sampled without seamless blending.
+            let settings = SampleSettings::new(size, false, Projection::Plane);
+            println!("{}", program.validate(&graph.build_noise_fn::<2>(node_index), &settings));
+        }
+    }
+    if !render_image && !animate {
+        return Ok(());
+    }
+
+    let settings = SampleSettings {
+        dimensions: args.dimensions.unwrap_or(node.dimensions),
+        z: args.z.unwrap_or(node.z),
+        w: args.w.unwrap_or(node.w),
+        ..SampleSettings::new(size, args.tileable || node.tileable, args.projection.unwrap_or(node.projection))
+    };
+    let noise = graph.compile(node_index);
+
+    if animate && settings.projection != Projection::Plane {
+        return Err("Only the plane projection can be animated".to_string());
+    }
+    if let Some(path) = args.gif {
+        render::write_gif(args.animation.sample_frames(&noise, &settings), args.animation.frames_per_second, &path)
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+    }
+    if let Some(path) = args.png_sequence {
+        render::write_png_sequence(args.animation.sample_frames(&noise, &settings), &path)
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+    }
+    if !render_image {
+        return Ok(());
+    }
+
+    let noise_map = render::sample(&noise, &settings);
+
+    if let Some(path) = args.output {
+        render::to_gray_image(&noise_map)
+            .save(&path)
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+    }
+    if let Some(path) = args.heightmap {
+        render::to_heightmap_image(&noise_map)
+            .save_with_format(&path, image::ImageFormat::Png)
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+    }
+    if let Some(path) = args.raw {
+        std::fs::write(&path, render::to_raw_f32(&noise_map))
+            .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
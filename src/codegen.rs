@@ -0,0 +1,61 @@
+//! Translates graphs into source code that no longer depends on this crate.
+
+pub mod rust;
+pub mod shader;
+
+/// Rust's keywords, including those reserved for later use, which can't name a function.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro", "match", "mod",
+    "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true",
+    "try", "type", "typeof", "union", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Turns a node name into a valid snake case identifier.
+fn identifier(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    // A lone underscore is a pattern rather than a name.
+    if ident.is_empty() || ident == "_" {
+        ident = "noise_".to_string();
+    } else if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert_str(0, "noise_");
+    } else if KEYWORDS.contains(&ident.as_str()) {
+        ident.push('_');
+    }
+    ident
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_become_snake_case() {
+        assert_eq!(identifier("Base Terrain"), "base_terrain");
+        assert_eq!(identifier("ridge-mask 2"), "ridge_mask_2");
+    }
+
+    #[test]
+    fn keywords_get_a_trailing_underscore() {
+        assert_eq!(identifier("fn"), "fn_");
+        assert_eq!(identifier("Type"), "type_");
+        assert_eq!(identifier("Self"), "self_");
+    }
+
+    #[test]
+    fn underscores_and_empty_names_are_prefixed() {
+        assert_eq!(identifier(""), "noise_");
+        assert_eq!(identifier("_"), "noise_");
+        assert_eq!(identifier("?"), "noise_");
+        assert_eq!(identifier("_private"), "_private");
+    }
+
+    #[test]
+    fn leading_digits_are_prefixed() {
+        assert_eq!(identifier("2d"), "noise_2d");
+        assert_eq!(identifier("3 octaves"), "noise_3_octaves");
+    }
+}
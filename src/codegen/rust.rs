@@ -0,0 +1,166 @@
+//! Generates Rust source that builds a node's noise function using only the `noise` crate.
+
+use std::{fmt, fmt::Write};
+
+use crate::graph::NoiseGraph;
+
+use super::identifier;
+
+const INDENT: &str = "    ";
+
+#[derive(Debug)]
+pub enum RustError {
+    /// The node, or one of the nodes it depends on, has no equivalent in the `noise` crate.
+    Unsupported { node: String, noise_type: &'static str },
+}
+
+impl fmt::Display for RustError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RustError::Unsupported { node, noise_type } => {
+                write!(f, "Node {node:?} is a {noise_type} node, which has no equivalent in the noise crate")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RustError {}
+
+/// Generates a function, named after the node, that returns the node's noise function with all of
+/// its parameters baked in.
+///
+/// Nodes whose output feeds several inputs are constructed once per use, since the generated tree
+/// owns each of its sources.
+pub fn generate(graph: &NoiseGraph, node: usize, dimensions: usize) -> Result<String, RustError> {
+    let name = &graph.nodes()[node].name;
+    let mut out = String::new();
+    writeln!(out, "/// Generated by Noise Explorer from node {name:?}.").unwrap();
+    writeln!(out, "pub fn {}() -> impl noise::NoiseFn<f64, {dimensions}> {{", identifier(name)).unwrap();
+    writeln!(out, "{INDENT}use noise::*;").unwrap();
+    out.push_str(INDENT);
+    let flat = graph.flatten();
+    match flat.output_node(node) {
+        Some(node) => write_expression(&flat.graph, node, 1, &mut Vec::new(), &mut out)?,
+        // A group without outputs, which evaluates to 0 like an unconnected input.
+        None => out.push_str("Constant::new(0.0)"),
+    }
+    out.push_str("\n}\n");
+    Ok(out)
+}
+
+fn write_expression(graph: &NoiseGraph, node: usize, depth: usize, visiting: &mut Vec<usize>, out: &mut String) -> Result<(), RustError> {
+    let saved = &graph.nodes()[node];
+    if visiting.contains(&node) {
+        // Matches the compiler, which also breaks cycles with a constant 0.
+        out.push_str("Constant::new(0.0)");
+        return Ok(());
+    }
+    let (constructor, args) = saved.noise_type
+        .rust_constructor(&saved.config)
+        .ok_or_else(|| RustError::Unsupported { node: saved.name.clone(), noise_type: saved.noise_type.name() })?;
+    let methods: String = saved.noise_type.rust_methods(&saved.config).iter().flat_map(|method| [".", method]).collect();
+    let input_count = saved.noise_input_count();
+    if input_count == 0 {
+        write!(out, "{constructor}({}){methods}", args.join(", ")).unwrap();
+        return Ok(());
+    }
+
+    visiting.push(node);
+    writeln!(out, "{constructor}(").unwrap();
+    let inner_indent = INDENT.repeat(depth + 1);
+    for arg in args {
+        writeln!(out, "{inner_indent}{arg},").unwrap();
+    }
+    for input in 0..input_count {
+        out.push_str(&inner_indent);
+        match graph.input_source(node, input) {
+            Some(source) => write_expression(graph, source, depth + 1, visiting, out)?,
+            None => out.push_str("Constant::new(0.0)"),
+        }
+        out.push_str(",\n");
+    }
+    write!(out, "{}){methods}", INDENT.repeat(depth)).unwrap();
+    visiting.pop();
+    Ok(())
+}
+
+/// Formats a value as a Rust `f64` expression.
+pub fn f64_literal(value: f64) -> String {
+    if value.is_nan() {
+        "f64::NAN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "f64::INFINITY" } else { "f64::NEG_INFINITY" }.to_string()
+    } else {
+        // Debug formatting always includes a decimal point or exponent, and round trips exactly.
+        format!("{value:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generated(json: &str, node: usize) -> String {
+        generate(&NoiseGraph::from_json(json).unwrap(), node, 2).unwrap()
+    }
+
+    #[test]
+    fn unconnected_inputs_are_zero() {
+        let source = generated(r#"{"nodes": [
+            {"name": "Base", "noise_type": "Perlin", "config": {"Params": {"seed": 7}}},
+            {"name": "max", "noise_type": "Max", "config": "Empty"}
+        ], "wires": [{"from": 0, "to": 1, "input": 0}]}"#, 1);
+        assert_eq!(source, r#"/// Generated by Noise Explorer from node "max".
+pub fn max() -> impl noise::NoiseFn<f64, 2> {
+    use noise::*;
+    Max::new(
+        Perlin::new(7),
+        Constant::new(0.0),
+    )
+}
+"#);
+    }
+
+    #[test]
+    fn cycles_are_broken_with_zero() {
+        let source = generated(r#"{"nodes": [
+            {"name": "a", "noise_type": "Max", "config": "Empty"},
+            {"name": "b", "noise_type": "Min", "config": "Empty"}
+        ], "wires": [{"from": 0, "to": 1, "input": 0}, {"from": 1, "to": 0, "input": 1}]}"#, 1);
+        assert_eq!(source, r#"/// Generated by Noise Explorer from node "b".
+pub fn b() -> impl noise::NoiseFn<f64, 2> {
+    use noise::*;
+    Min::new(
+        Max::new(
+            Constant::new(0.0),
+            Constant::new(0.0),
+        ),
+        Constant::new(0.0),
+    )
+}
+"#);
+    }
+
+    #[test]
+    fn groups_are_replaced_by_their_contents() {
+        // The group's input feeds both of the inner node's inputs.
+        let source = generated(r#"{"nodes": [
+            {"name": "c", "noise_type": "Constant", "config": {"Params": {"value": 0.5}}},
+            {"name": "g", "noise_type": "Group", "config": {"Group": {
+                "name": "Doubled",
+                "graph": {"nodes": [{"name": "m", "noise_type": "Max", "config": "Empty"}], "wires": []},
+                "inputs": [{"name": "In", "targets": [{"node": 0, "input": 0}, {"node": 0, "input": 1}]}],
+                "outputs": [{"name": "Out", "node": 0}]
+            }}}
+        ], "wires": [{"from": 0, "to": 1, "input": 0}]}"#, 1);
+        assert_eq!(source, r#"/// Generated by Noise Explorer from node "g".
+pub fn g() -> impl noise::NoiseFn<f64, 2> {
+    use noise::*;
+    Max::new(
+        Constant::new(0.5),
+        Constant::new(0.5),
+    )
+}
+"#);
+    }
+}
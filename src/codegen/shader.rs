@@ -0,0 +1,505 @@
+//! Generates WGSL and GLSL functions that evaluate a node's 2D noise on the GPU.
+//!
+//! A node is first flattened into a [`ShaderProgram`] of straight-line operations, which is then
+//! printed in either language. The program can also be evaluated on the CPU, in single precision
+//! and with exactly the arithmetic the generated shaders use, so that it can be checked against the
+//! `noise` crate without a GPU.
+
+use std::{collections::HashMap, fmt, fmt::Write};
+
+use noise::{permutationtable::{NoiseHasher, PermutationTable}, NoiseFn};
+
+use crate::{graph::NoiseGraph, render::SampleSettings};
+
+use super::identifier;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderLanguage {
+    Wgsl,
+    Glsl,
+}
+
+impl ShaderLanguage {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            ShaderLanguage::Wgsl => "WGSL",
+            ShaderLanguage::Glsl => "GLSL",
+        }
+    }
+}
+
+/// One operation of a shader program. Operands are the indices of earlier operations.
+#[derive(Debug, Clone, Copy)]
+pub enum ShaderOp {
+    Constant(f32),
+    Checkerboard,
+    /// Perlin noise using the program's permutation table with the given index.
+    Perlin { table: usize },
+    Blend { a: usize, b: usize, control: usize },
+    Max(usize, usize),
+    Min(usize, usize),
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    /// The node, or one of the nodes it depends on, has no shader translation.
+    Unsupported { node: String, noise_type: &'static str },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Unsupported { node, noise_type } => {
+                write!(f, "Node {node:?} is a {noise_type} node, which can't be translated to a shader")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// A node's noise function flattened into operations that map directly onto shader code.
+pub struct ShaderProgram {
+    node_name: String,
+    function_name: String,
+    tables: Vec<[u8; 256]>,
+    ops: Vec<ShaderOp>,
+}
+
+/// How closely a [`ShaderProgram`] reproduces the `noise` crate's output.
+#[derive(Debug, Clone, Copy)]
+pub struct Validation {
+    pub max_error: f64,
+    pub mean_error: f64,
+    /// Where the largest error occurred.
+    pub worst_point: [f64; 2],
+}
+
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Max error {:.3e} at ({:.4}, {:.4}), mean error {:.3e}",
+            self.max_error, self.worst_point[0], self.worst_point[1], self.mean_error)
+    }
+}
+
+impl ShaderProgram {
+    pub fn compile(graph: &NoiseGraph, node: usize) -> Result<Self, ShaderError> {
+        let mut program = ShaderProgram {
+            node_name: graph.nodes()[node].name.clone(),
+            // Prefixed, as node names such as "max" would otherwise shadow shader builtins.
+            function_name: format!("noise_{}", identifier(&graph.nodes()[node].name).trim_start_matches("noise_")),
+            tables: Vec::new(),
+            ops: Vec::new(),
+        };
+        let mut table_seeds = Vec::new();
+        let flat = graph.flatten();
+        match flat.output_node(node) {
+            Some(node) => {
+                program.add_node(&flat.graph, node, &mut HashMap::new(), &mut Vec::new(), &mut table_seeds)?;
+            }
+            // A group without outputs, which evaluates to 0 like an unconnected input.
+            None => program.ops.push(ShaderOp::Constant(0.0)),
+        }
+        program.tables = table_seeds.into_iter().map(permutation_table).collect();
+        Ok(program)
+    }
+
+    /// Appends the operations for a node after those of its inputs, returning the index of the
+    /// node's own operation. Nodes feeding several inputs are only evaluated once.
+    fn add_node(
+            &mut self,
+            graph: &NoiseGraph,
+            node: usize,
+            added: &mut HashMap<usize, usize>,
+            visiting: &mut Vec<usize>,
+            table_seeds: &mut Vec<u32>) -> Result<usize, ShaderError> {
+        if let Some(&op) = added.get(&node) {
+            return Ok(op);
+        }
+        // Matches the compiler, which also breaks cycles with a constant 0.
+        if visiting.contains(&node) {
+            self.ops.push(ShaderOp::Constant(0.0));
+            return Ok(self.ops.len() - 1);
+        }
+        visiting.push(node);
+        let saved = &graph.nodes()[node];
+        let mut inputs = Vec::new();
+        for input in 0..saved.noise_input_count() {
+            inputs.push(match graph.input_source(node, input) {
+                Some(source) => self.add_node(graph, source, added, visiting, table_seeds)?,
+                None => {
+                    self.ops.push(ShaderOp::Constant(0.0));
+                    self.ops.len() - 1
+                }
+            });
+        }
+        visiting.pop();
+
+        let mut perlin_table = |seed: u32| match table_seeds.iter().position(|&s| s == seed) {
+            Some(table) => table,
+            None => {
+                table_seeds.push(seed);
+                table_seeds.len() - 1
+            }
+        };
+        let op = saved.noise_type
+            .shader_op(&saved.config, &inputs, &mut perlin_table)
+            .ok_or_else(|| ShaderError::Unsupported { node: saved.name.clone(), noise_type: saved.noise_type.name() })?;
+        self.ops.push(op);
+        added.insert(node, self.ops.len() - 1);
+        Ok(self.ops.len() - 1)
+    }
+
+    pub fn generate(&self, language: ShaderLanguage) -> String {
+        match language {
+            ShaderLanguage::Wgsl => self.wgsl(),
+            ShaderLanguage::Glsl => self.glsl(),
+        }
+    }
+
+    pub fn wgsl(&self) -> String {
+        let mut out = format!("// Generated by Noise Explorer from node {:?}.\n\n", self.node_name);
+        if !self.tables.is_empty() {
+            writeln!(out, "var<private> nx_perm: array<u32, {}> = array<u32, {}>(", self.tables.len() * 256, self.tables.len() * 256).unwrap();
+            write_table_values(&self.tables, &mut out);
+            out.push_str(");\n\n");
+        }
+        out.push_str(WGSL_COMMON);
+        if !self.tables.is_empty() {
+            out.push_str(WGSL_PERLIN);
+        }
+        writeln!(out, "\nfn {}(point: vec2<f32>) -> f32 {{", self.function_name).unwrap();
+        for (index, op) in self.ops.iter().enumerate() {
+            let expression = match *op {
+                ShaderOp::Constant(value) => float_literal(value),
+                ShaderOp::Checkerboard => "nx_checkerboard2(point)".to_string(),
+                ShaderOp::Perlin { table } => format!("nx_perlin2({table}u, point)"),
+                ShaderOp::Blend { a, b, control } => format!("nx_lerp(v{a}, v{b}, v{control})"),
+                ShaderOp::Max(a, b) => format!("max(v{a}, v{b})"),
+                ShaderOp::Min(a, b) => format!("min(v{a}, v{b})"),
+            };
+            writeln!(out, "    let v{index} = {expression};").unwrap();
+        }
+        writeln!(out, "    return v{};\n}}", self.ops.len() - 1).unwrap();
+        out
+    }
+
+    pub fn glsl(&self) -> String {
+        let mut out = format!("// Generated by Noise Explorer from node {:?}.\n\n", self.node_name);
+        if !self.tables.is_empty() {
+            writeln!(out, "const uint nx_perm[{}] = uint[{}](", self.tables.len() * 256, self.tables.len() * 256).unwrap();
+            write_table_values(&self.tables, &mut out);
+            out.push_str(");\n\n");
+        }
+        out.push_str(GLSL_COMMON);
+        if !self.tables.is_empty() {
+            out.push_str(GLSL_PERLIN);
+        }
+        writeln!(out, "\nfloat {}(vec2 point) {{", self.function_name).unwrap();
+        for (index, op) in self.ops.iter().enumerate() {
+            let expression = match *op {
+                ShaderOp::Constant(value) => float_literal(value),
+                ShaderOp::Checkerboard => "nx_checkerboard2(point)".to_string(),
+                ShaderOp::Perlin { table } => format!("nx_perlin2({table}u, point)"),
+                ShaderOp::Blend { a, b, control } => format!("nx_lerp(v{a}, v{b}, v{control})"),
+                ShaderOp::Max(a, b) => format!("max(v{a}, v{b})"),
+                ShaderOp::Min(a, b) => format!("min(v{a}, v{b})"),
+            };
+            writeln!(out, "    float v{index} = {expression};").unwrap();
+        }
+        writeln!(out, "    return v{};\n}}", self.ops.len() - 1).unwrap();
+        out
+    }
+
+    /// Evaluates the program on the CPU, mirroring the generated shader code in single precision.
+    pub fn evaluate(&self, point: [f32; 2]) -> f32 {
+        let mut values: Vec<f32> = Vec::with_capacity(self.ops.len());
+        for op in &self.ops {
+            let value = match *op {
+                ShaderOp::Constant(value) => value,
+                ShaderOp::Checkerboard => cpu::checkerboard2(point),
+                ShaderOp::Perlin { table } => cpu::perlin2(&self.tables[table], point),
+                ShaderOp::Blend { a, b, control } => cpu::lerp(values[a], values[b], values[control]),
+                ShaderOp::Max(a, b) => values[a].max(values[b]),
+                ShaderOp::Min(a, b) => values[a].min(values[b]),
+            };
+            values.push(value);
+        }
+        *values.last().expect("Program has no operations")
+    }
+
+    /// Compares the CPU interpretation of the shader against the noise function over a rectangle
+    /// the size of the plane the settings describe. The rectangle is centred on the origin, so that
+    /// negative coordinates, which round differently, are checked too.
+    pub fn validate(&self, noise_fn: &impl NoiseFn<f64, 2>, settings: &SampleSettings) -> Validation {
+        let mut validation = Validation { max_error: 0.0, mean_error: 0.0, worst_point: [0.0, 0.0] };
+        for y in 0..settings.height {
+            for x in 0..settings.width {
+                let point = [
+                    (x as f64 / settings.width as f64 - 0.5) * settings.noise_width,
+                    (y as f64 / settings.height as f64 - 0.5) * settings.noise_height,
+                ];
+                let expected = noise_fn.get(point);
+                let actual = self.evaluate([point[0] as f32, point[1] as f32]) as f64;
+                let error = (expected - actual).abs();
+                validation.mean_error += error;
+                if error > validation.max_error {
+                    validation.max_error = error;
+                    validation.worst_point = point;
+                }
+            }
+        }
+        validation.mean_error /= (settings.width * settings.height).max(1) as f64;
+        validation
+    }
+}
+
+/// Reads the permutation table the `noise` crate generates for a seed.
+fn permutation_table(seed: u32) -> [u8; 256] {
+    // The table's values are private, but hashing a single coordinate looks them up directly.
+    let table = PermutationTable::new(seed);
+    std::array::from_fn(|i| table.hash(&[i as isize]) as u8)
+}
+
+/// Writes the tables as unsigned literals, 16 to a line. GLSL doesn't allow a trailing comma.
+fn write_table_values(tables: &[[u8; 256]], out: &mut String) {
+    let values: Vec<String> = tables.iter().flatten().map(|value| format!("{value}u")).collect();
+    let lines: Vec<String> = values.chunks(16).map(|chunk| format!("    {}", chunk.join(", "))).collect();
+    out.push_str(&lines.join(",\n"));
+    out.push('\n');
+}
+
+/// Formats a value so that both languages parse it as a float.
+fn float_literal(value: f32) -> String {
+    let literal = format!("{value:?}");
+    if literal.contains(['.', 'e']) { literal } else { format!("{literal}.0") }
+}
+
+/// The operations the shader libraries implement, written the same way.
+mod cpu {
+    /// Matches the `noise` crate, which rounds values at or below zero down one further.
+    pub fn floor(x: f32) -> i32 {
+        if x <= 0.0 { x as i32 - 1 } else { x as i32 }
+    }
+
+    pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+        b * t + a * (1.0 - t)
+    }
+
+    pub fn checkerboard2(point: [f32; 2]) -> f32 {
+        if (floor(point[0]) & 1) ^ (floor(point[1]) & 1) == 0 { -1.0 } else { 1.0 }
+    }
+
+    fn hash2(table: &[u8; 256], x: i32, y: i32) -> u32 {
+        let a = table[(x & 255) as usize] as u32;
+        table[(a ^ (y & 255) as u32) as usize] as u32
+    }
+
+    fn gradient2(hash: u32, p: [f32; 2]) -> f32 {
+        match hash & 3 {
+            0 => p[0] + p[1],
+            1 => -p[0] + p[1],
+            2 => p[0] - p[1],
+            _ => -p[0] - p[1],
+        }
+    }
+
+    fn quintic(t: f32) -> f32 {
+        let x = t.clamp(0.0, 1.0);
+        x * x * x * (x * (x * 6.0 - 15.0) + 10.0)
+    }
+
+    pub fn perlin2(table: &[u8; 256], point: [f32; 2]) -> f32 {
+        let corner = [floor(point[0]), floor(point[1])];
+        let d = [point[0] - corner[0] as f32, point[1] - corner[1] as f32];
+        let g00 = gradient2(hash2(table, corner[0], corner[1]), d);
+        let g10 = gradient2(hash2(table, corner[0] + 1, corner[1]), [d[0] - 1.0, d[1]]);
+        let g01 = gradient2(hash2(table, corner[0], corner[1] + 1), [d[0], d[1] - 1.0]);
+        let g11 = gradient2(hash2(table, corner[0] + 1, corner[1] + 1), [d[0] - 1.0, d[1] - 1.0]);
+        let curve = [quintic(d[0]), quintic(d[1])];
+        let result = lerp(lerp(g00, g01, curve[1]), lerp(g10, g11, curve[1]), curve[0]) * std::f32::consts::SQRT_2;
+        result.clamp(-1.0, 1.0)
+    }
+}
+
+const WGSL_COMMON: &str = "\
+// Matches the noise crate, which rounds values at or below zero down one further.
+fn nx_floor(x: f32) -> i32 {
+    if (x <= 0.0) {
+        return i32(x) - 1;
+    }
+    return i32(x);
+}
+
+fn nx_lerp(a: f32, b: f32, t: f32) -> f32 {
+    return b * t + a * (1.0 - t);
+}
+
+fn nx_checkerboard2(point: vec2<f32>) -> f32 {
+    if (((nx_floor(point.x) & 1) ^ (nx_floor(point.y) & 1)) == 0) {
+        return -1.0;
+    }
+    return 1.0;
+}
+";
+
+const WGSL_PERLIN: &str = "
+fn nx_hash2(table: u32, x: i32, y: i32) -> u32 {
+    let a = nx_perm[table * 256u + u32(x & 255)];
+    return nx_perm[table * 256u + (a ^ u32(y & 255))];
+}
+
+fn nx_gradient2(hash: u32, p: vec2<f32>) -> f32 {
+    switch (hash & 3u) {
+        case 0u: { return p.x + p.y; }
+        case 1u: { return -p.x + p.y; }
+        case 2u: { return p.x - p.y; }
+        default: { return -p.x - p.y; }
+    }
+}
+
+fn nx_quintic(t: vec2<f32>) -> vec2<f32> {
+    let x = clamp(t, vec2<f32>(0.0), vec2<f32>(1.0));
+    return x * x * x * (x * (x * 6.0 - 15.0) + 10.0);
+}
+
+fn nx_perlin2(table: u32, point: vec2<f32>) -> f32 {
+    let corner = vec2<i32>(nx_floor(point.x), nx_floor(point.y));
+    let d = point - vec2<f32>(corner);
+    let g00 = nx_gradient2(nx_hash2(table, corner.x, corner.y), d);
+    let g10 = nx_gradient2(nx_hash2(table, corner.x + 1, corner.y), d - vec2<f32>(1.0, 0.0));
+    let g01 = nx_gradient2(nx_hash2(table, corner.x, corner.y + 1), d - vec2<f32>(0.0, 1.0));
+    let g11 = nx_gradient2(nx_hash2(table, corner.x + 1, corner.y + 1), d - vec2<f32>(1.0, 1.0));
+    let curve = nx_quintic(d);
+    let result = nx_lerp(nx_lerp(g00, g01, curve.y), nx_lerp(g10, g11, curve.y), curve.x) * 1.4142135;
+    return clamp(result, -1.0, 1.0);
+}
+";
+
+const GLSL_COMMON: &str = "\
+// Matches the noise crate, which rounds values at or below zero down one further.
+int nx_floor(float x) {
+    if (x <= 0.0) {
+        return int(x) - 1;
+    }
+    return int(x);
+}
+
+float nx_lerp(float a, float b, float t) {
+    return b * t + a * (1.0 - t);
+}
+
+float nx_checkerboard2(vec2 point) {
+    if (((nx_floor(point.x) & 1) ^ (nx_floor(point.y) & 1)) == 0) {
+        return -1.0;
+    }
+    return 1.0;
+}
+";
+
+const GLSL_PERLIN: &str = "
+uint nx_hash2(uint table, int x, int y) {
+    uint a = nx_perm[table * 256u + uint(x & 255)];
+    return nx_perm[table * 256u + (a ^ uint(y & 255))];
+}
+
+float nx_gradient2(uint hash, vec2 p) {
+    switch (hash & 3u) {
+        case 0u: return p.x + p.y;
+        case 1u: return -p.x + p.y;
+        case 2u: return p.x - p.y;
+        default: return -p.x - p.y;
+    }
+}
+
+vec2 nx_quintic(vec2 t) {
+    vec2 x = clamp(t, vec2(0.0), vec2(1.0));
+    return x * x * x * (x * (x * 6.0 - 15.0) + 10.0);
+}
+
+float nx_perlin2(uint table, vec2 point) {
+    ivec2 corner = ivec2(nx_floor(point.x), nx_floor(point.y));
+    vec2 d = point - vec2(corner);
+    float g00 = nx_gradient2(nx_hash2(table, corner.x, corner.y), d);
+    float g10 = nx_gradient2(nx_hash2(table, corner.x + 1, corner.y), d - vec2(1.0, 0.0));
+    float g01 = nx_gradient2(nx_hash2(table, corner.x, corner.y + 1), d - vec2(0.0, 1.0));
+    float g11 = nx_gradient2(nx_hash2(table, corner.x + 1, corner.y + 1), d - vec2(1.0, 1.0));
+    vec2 curve = nx_quintic(d);
+    float result = nx_lerp(nx_lerp(g00, g01, curve.y), nx_lerp(g10, g11, curve.y), curve.x) * 1.4142135;
+    return clamp(result, -1.0, 1.0);
+}
+";
+
+#[cfg(test)]
+mod tests {
+    use crate::render::Projection;
+
+    use super::*;
+
+    /// Perlin noise and a checkerboard blended by a constant, with the blend as node 3.
+    fn blend_graph() -> NoiseGraph {
+        NoiseGraph::from_json(r#"{
+            "nodes": [
+                {"name": "p", "noise_type": "Perlin", "config": {"Params": {"seed": 7}}},
+                {"name": "c", "noise_type": "Checkerboard", "config": "Empty"},
+                {"name": "t", "noise_type": "Constant", "config": {"Params": {"value": 0.25}}},
+                {"name": "mix", "noise_type": "Blend", "config": "Empty"}
+            ],
+            "wires": [
+                {"from": 0, "to": 3, "input": 0},
+                {"from": 1, "to": 3, "input": 1},
+                {"from": 2, "to": 3, "input": 2}
+            ]
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn programs_match_the_noise_on_both_sides_of_the_origin() {
+        let graph = blend_graph();
+        let mut settings = SampleSettings::new(128, false, Projection::Plane);
+        (settings.noise_width, settings.noise_height) = (9.0, 9.0);
+        for node in [0, 1, 3] {
+            let program = ShaderProgram::compile(&graph, node).unwrap();
+            let validation = program.validate(&graph.build_noise_fn::<2>(node), &settings);
+            assert!(validation.max_error < 1e-5, "{}: {validation}", graph.nodes()[node].name);
+        }
+    }
+
+    #[test]
+    fn wgsl_matches_the_golden_output() {
+        let program = ShaderProgram::compile(&blend_graph(), 3).unwrap();
+        let wgsl = program.wgsl();
+        let (tables, function) = wgsl.split_at(wgsl.find("\nfn noise_mix").unwrap());
+        assert!(tables.starts_with("// Generated by Noise Explorer from node \"mix\".\n\nvar<private> nx_perm: array<u32, 256> = array<u32, 256>(\n"));
+        assert!(tables.ends_with(&format!("{WGSL_COMMON}{WGSL_PERLIN}")));
+        assert_eq!(function, "
+fn noise_mix(point: vec2<f32>) -> f32 {
+    let v0 = nx_perlin2(0u, point);
+    let v1 = nx_checkerboard2(point);
+    let v2 = 0.25;
+    let v3 = nx_lerp(v0, v1, v2);
+    return v3;
+}
+");
+    }
+
+    #[test]
+    fn glsl_matches_the_golden_output() {
+        let program = ShaderProgram::compile(&blend_graph(), 3).unwrap();
+        let glsl = program.glsl();
+        let (tables, function) = glsl.split_at(glsl.find("\nfloat noise_mix").unwrap());
+        assert!(tables.starts_with("// Generated by Noise Explorer from node \"mix\".\n\nconst uint nx_perm[256] = uint[256](\n"));
+        assert!(tables.ends_with(&format!("{GLSL_COMMON}{GLSL_PERLIN}")));
+        assert_eq!(function, "
+float noise_mix(vec2 point) {
+    float v0 = nx_perlin2(0u, point);
+    float v1 = nx_checkerboard2(point);
+    float v2 = 0.25;
+    float v3 = nx_lerp(v0, v1, v2);
+    return v3;
+}
+");
+    }
+}
@@ -0,0 +1,287 @@
+//! Random changes to graphs, for exploring a graph by breeding the variations that look best.
+//!
+//! Changes only touch the nodes an output depends on, and never remove or rename nodes, so node
+//! indices stay valid and nodes can be matched between related graphs by index.
+
+use std::fmt;
+
+use crate::{graph::{NoiseGraph, SavedNode, SavedWire, SeedMode}, noises::{NoiseClassification, NoiseType, PinType}, params::{CurveShape, ParamKind, ParamSpec, ParamValue}, random::Rng};
+
+/// A change made by [`mutate`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mutation {
+    /// A parameter was given a nearby value, or a new one for seeds and options.
+    Perturbed { node: String, param: &'static str },
+    /// A node was replaced by another kind with the same inputs.
+    Swapped { node: String, from: &'static str, to: &'static str },
+    /// A combinator was inserted on the wire into `into`, with new sources on its other inputs.
+    Inserted { node: String, into: String },
+    /// An input was connected to another node.
+    Rewired { node: String, input: usize, source: String },
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mutation::Perturbed { node, param } => write!(f, "Changed {param} of {node}"),
+            Mutation::Swapped { node, from, to } => write!(f, "Swapped {node} from {from} to {to}"),
+            Mutation::Inserted { node, into } => write!(f, "Inserted {node} before {into}"),
+            Mutation::Rewired { node, input, source } => write!(f, "Connected input {} of {node} to {source}", input + 1),
+        }
+    }
+}
+
+/// Returns a copy of the graph with random changes to the nodes that `output` depends on, along
+/// with the changes made. `strength` from 0 to 1 scales how many changes are made and how far
+/// parameters move.
+pub fn mutate(graph: &NoiseGraph, output: usize, strength: f64, rng: &mut Rng) -> (NoiseGraph, Vec<Mutation>) {
+    let strength = strength.clamp(0.0, 1.0);
+    let mut child = graph.clone();
+    let mut mutations = Vec::new();
+    let count = 1 + (rng.next_f64() * strength * 4.0).round() as usize;
+    for _ in 0..count {
+        // Most changes are small, and structural changes fall back to one when there's no room
+        // for them.
+        let roll = rng.next_f64();
+        let mutation = if roll < 0.15 {
+            insert(&mut child, output, rng)
+        } else if roll < 0.3 {
+            rewire(&mut child, output, rng)
+        } else if roll < 0.5 {
+            swap(&mut child, output, rng)
+        } else {
+            None
+        };
+        mutations.extend(mutation.or_else(|| perturb(&mut child, output, strength, rng)));
+    }
+    (child, mutations)
+}
+
+/// Combines two graphs: the structure of `a`, with each parameter of the nodes also in `b` taken
+/// from either. Nodes are the same if they have the same index, name and kind, as names alone
+/// needn't be unique.
+pub fn breed(a: &NoiseGraph, b: &NoiseGraph, rng: &mut Rng) -> NoiseGraph {
+    let mut child = a.clone();
+    if rng.chance(0.5) {
+        child.set_master_seed(b.master_seed());
+    }
+    for (index, node) in child.nodes_mut().iter_mut().enumerate() {
+        let Some(other) = b.nodes().get(index).filter(|other| other.name == node.name && other.noise_type == node.noise_type) else { continue };
+        for spec in node.noise_type.params() {
+            if rng.chance(0.5) {
+                node.config.values_mut().set(spec.name, spec.value(other.config.values()));
+            }
+        }
+    }
+    child
+}
+
+fn perturb(graph: &mut NoiseGraph, output: usize, strength: f64, rng: &mut Rng) -> Option<Mutation> {
+    // Parameters set through pins would just be overwritten, locked seeds are meant to stay, and
+    // random text would hardly ever be valid.
+    let candidates: Vec<(usize, &'static ParamSpec)> = upstream(graph, output)
+        .into_iter()
+        .flat_map(|node| {
+            let saved = &graph.nodes()[node];
+            saved.noise_type
+                .params()
+                .iter()
+                .filter(|spec| !saved.param_pins.iter().any(|name| name == spec.name))
+                .filter(|spec| !(saved.seed_locked && matches!(spec.kind, ParamKind::Seed { .. })))
+                .filter(|spec| !matches!(spec.kind, ParamKind::Text { .. }))
+                .map(move |spec| (node, spec))
+        })
+        .collect();
+    let &(node, spec) = rng.pick(&candidates)?;
+    let saved = &mut graph.nodes_mut()[node];
+    let values = saved.config.values();
+    // Up to `strength` of the way across the parameter's range, in either direction.
+    let offset = (rng.next_f64() * 2.0 - 1.0) * strength;
+    let value = match spec.kind {
+        ParamKind::Int { min, max, .. } => {
+            let step = ((max - min) as f64 * offset).round() as i64;
+            let step = if step == 0 { if offset < 0.0 { -1 } else { 1 } } else { step };
+            ParamValue::Int((spec.int(values) + step).clamp(min, max))
+        }
+        ParamKind::Float { min, max, logarithmic, .. } => {
+            let value = spec.float(values);
+            // Logarithmic parameters such as frequencies move by factors instead.
+            let value = if logarithmic && value > 0.0 {
+                value * 8f64.powf(offset)
+            } else {
+                value + (max - min) * offset
+            };
+            ParamValue::Float(value.clamp(min, max))
+        }
+        ParamKind::Enum { options, .. } => ParamValue::Text(rng.pick(options)?.to_string()),
+        ParamKind::Bool { .. } => ParamValue::Bool(!spec.bool(values)),
+        ParamKind::Seed { .. } => ParamValue::Int(rng.next_u32().into()),
+        ParamKind::Curve { shape, .. } => {
+            let mut points = spec.points(values);
+            let index = rng.below(points.len().max(1));
+            let point = points.get_mut(index)?;
+            match shape {
+                CurveShape::Spline => point[1] += offset,
+                // Terraces only depend on inputs, which stay on the diagonal.
+                CurveShape::Terraces => *point = [point[0] + offset; 2],
+            }
+            points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+            ParamValue::Points(points)
+        }
+        ParamKind::Text { .. } => return None,
+    };
+    saved.config.values_mut().set(spec.name, value);
+    Some(Mutation::Perturbed { node: saved.name.clone(), param: spec.name })
+}
+
+fn swap(graph: &mut NoiseGraph, output: usize, rng: &mut Rng) -> Option<Mutation> {
+    let candidates: Vec<(usize, Vec<NoiseType>)> = upstream(graph, output)
+        .into_iter()
+        .filter_map(|node| {
+            let saved = &graph.nodes()[node];
+            let classification = saved.noise_type.classification();
+            if !matches!(classification, NoiseClassification::Source | NoiseClassification::Combinator | NoiseClassification::Modifier) {
+                return None;
+            }
+            let alternatives: Vec<NoiseType> = NoiseType::all()
+                .into_iter()
+                .filter(|kind| *kind != saved.noise_type && kind.classification() == classification)
+                .filter(|kind| kind.input_count(&kind.config()) == saved.noise_input_count())
+                .collect();
+            (!alternatives.is_empty()).then_some((node, alternatives))
+        })
+        .collect();
+    let (node, alternatives) = rng.pick(&candidates)?;
+    let (node, to) = (*node, *rng.pick(alternatives)?);
+    // Parameter pins belong to the old kind, so their wires go with them.
+    let noise_inputs = graph.nodes()[node].noise_input_count();
+    graph.wires_mut().retain(|wire| wire.to != node || wire.input < noise_inputs);
+    let saved = &mut graph.nodes_mut()[node];
+    let from = saved.noise_type.name();
+    // Parameters both kinds have keep their values, such as seeds.
+    let old_values = saved.config.values().clone();
+    saved.noise_type = to;
+    saved.config = to.config();
+    saved.param_pins.clear();
+    for spec in to.params() {
+        if old_values.get(spec.name).is_some() {
+            saved.config.values_mut().set(spec.name, spec.value(&old_values));
+        }
+    }
+    Some(Mutation::Swapped { node: saved.name.clone(), from, to: to.name() })
+}
+
+fn insert(graph: &mut NoiseGraph, output: usize, rng: &mut Rng) -> Option<Mutation> {
+    let wires = noise_wires(graph, output);
+    let &index = rng.pick(&wires)?;
+    let combinators: Vec<NoiseType> = NoiseType::combinators()
+        .chain(NoiseType::modifiers())
+        .filter(|kind| {
+            let config = kind.config();
+            let inputs = kind.input_count(&config);
+            inputs > 0 && (0..inputs).all(|input| kind.input_type(&config, input) == PinType::Noise)
+        })
+        .collect();
+    let sources: Vec<NoiseType> = NoiseType::sources()
+        .filter(|kind| kind.input_count(&kind.config()) == 0)
+        .collect();
+    let kind = *rng.pick(&combinators)?;
+    let wire = graph.wires()[index];
+    let template = graph.nodes()[wire.to].clone();
+    let [x, y] = template.position;
+    let combinator = graph.add_node(new_node(graph, kind, [x - 150.0, y], &template, rng));
+    // The wire's source feeds the first input, and new sources the rest.
+    graph.wires_mut()[index].to = combinator;
+    graph.wires_mut()[index].input = 0;
+    graph.add_wire(SavedWire { from: combinator, output: 0, to: wire.to, input: wire.input });
+    for input in 1..kind.input_count(&kind.config()) {
+        let Some(&source_kind) = rng.pick(&sources) else { break };
+        let position = [x - 300.0, y + 150.0 * input as f32];
+        let source = graph.add_node(new_node(graph, source_kind, position, &template, rng));
+        graph.add_wire(SavedWire { from: source, output: 0, to: combinator, input });
+    }
+    Some(Mutation::Inserted { node: graph.nodes()[combinator].name.clone(), into: template.name })
+}
+
+fn rewire(graph: &mut NoiseGraph, output: usize, rng: &mut Rng) -> Option<Mutation> {
+    let wires = noise_wires(graph, output);
+    let &index = rng.pick(&wires)?;
+    let wire = graph.wires()[index];
+    // Nodes depending on the wire's target can't feed it without making a cycle.
+    let downstream = reachable(graph, wire.to, |wire| (wire.from, wire.to));
+    let sources: Vec<usize> = (0..graph.nodes().len())
+        .filter(|&node| node != wire.from && !downstream.contains(&node))
+        .filter(|&node| {
+            let saved = &graph.nodes()[node];
+            saved.noise_type.output_count(&saved.config) > 0 && saved.output_type(0) == PinType::Noise
+        })
+        .collect();
+    let &source = rng.pick(&sources)?;
+    graph.wires_mut()[index].from = source;
+    graph.wires_mut()[index].output = 0;
+    Some(Mutation::Rewired {
+        node: graph.nodes()[wire.to].name.clone(),
+        input: wire.input,
+        source: graph.nodes()[source].name.clone(),
+    })
+}
+
+/// A new node of the given kind with random seeds, previewed the same way as `template`.
+fn new_node(graph: &NoiseGraph, kind: NoiseType, position: [f32; 2], template: &SavedNode, rng: &mut Rng) -> SavedNode {
+    let mut config = kind.config();
+    for spec in kind.params().iter().filter(|spec| matches!(spec.kind, ParamKind::Seed { .. })) {
+        config.values_mut().set(spec.name, ParamValue::Int(rng.next_u32().into()));
+    }
+    SavedNode {
+        name: unique_name(graph, &kind.lowercase_name()),
+        noise_type: kind,
+        config,
+        position,
+        param_pins: Vec::new(),
+        seed_mode: SeedMode::Absolute,
+        seed_locked: false,
+        ..template.clone()
+    }
+}
+
+/// Returns `name` if no node is using it, otherwise numbers it.
+fn unique_name(graph: &NoiseGraph, name: &str) -> String {
+    let taken = |name: &str| graph.nodes().iter().any(|node| node.name == name);
+    if !taken(name) {
+        return name.to_string();
+    }
+    (2..).map(|n| format!("{name}_{n}")).find(|name| !taken(name)).expect("Ran out of names")
+}
+
+/// Indices of the wires into noise inputs of the nodes `output` depends on.
+fn noise_wires(graph: &NoiseGraph, output: usize) -> Vec<usize> {
+    let upstream = upstream(graph, output);
+    graph.wires()
+        .iter()
+        .enumerate()
+        .filter(|(_, wire)| upstream.contains(&wire.to) && graph.nodes()[wire.to].input_type(wire.input) == PinType::Noise)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// The node and every node it depends on.
+fn upstream(graph: &NoiseGraph, node: usize) -> Vec<usize> {
+    reachable(graph, node, |wire| (wire.to, wire.from))
+}
+
+/// The nodes reachable from `start` by following wires, where `step` gives the ends of a wire to
+/// follow from and to.
+fn reachable(graph: &NoiseGraph, start: usize, step: impl Fn(&SavedWire) -> (usize, usize)) -> Vec<usize> {
+    let mut found = vec![start];
+    let mut next = 0;
+    while let Some(&node) = found.get(next) {
+        next += 1;
+        for wire in graph.wires() {
+            let (from, to) = step(wire);
+            if from == node && !found.contains(&to) {
+                found.push(to);
+            }
+        }
+    }
+    found
+}
@@ -0,0 +1,414 @@
+use std::{fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+use strum::VariantArray;
+
+use crate::{group::Group, heightfield::HeightfieldView, noises::{BuildNoise, DynNoise, NodeKind, NoiseConfig, NoiseType, PinType}, params::{ParamKind, ParamValue}, random::Rng, render::{CompiledNoise, Dimensions, Projection}};
+
+/// File extension used for saved graphs.
+pub const FILE_EXTENSION: &str = "noisegraph";
+
+/// The saved form of a noise graph. Unlike the editor's graph this has no dependency on the GUI,
+/// so it can be loaded and rendered headlessly.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NoiseGraph {
+    nodes: Vec<SavedNode>,
+    wires: Vec<SavedWire>,
+    /// Groups saved for reuse from the graph menu.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<Group>,
+    /// Added to the seeds of nodes in [`SeedMode::Offset`], so one number picks a variation of
+    /// the whole graph.
+    #[serde(default)]
+    master_seed: u32,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedNode {
+    pub name: String,
+    pub noise_type: NoiseType,
+    pub config: NoiseConfig,
+    #[serde(default)]
+    pub position: [f32; 2],
+    #[serde(default)]
+    pub tileable: bool,
+    #[serde(default)]
+    pub projection: Projection,
+    #[serde(default)]
+    pub animated: bool,
+    #[serde(default)]
+    pub show_heightfield: bool,
+    #[serde(default)]
+    pub heightfield_view: HeightfieldView,
+    #[serde(default)]
+    pub dimensions: Dimensions,
+    #[serde(default)]
+    pub z: f64,
+    #[serde(default)]
+    pub w: f64,
+    /// Names of the parameters exposed as input pins, which follow the node's other inputs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub param_pins: Vec<String>,
+    #[serde(default)]
+    pub seed_mode: SeedMode,
+    /// Keep the node's seeds when reseeding the whole graph.
+    #[serde(default)]
+    pub seed_locked: bool,
+}
+
+/// How a node's seed parameters are used.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, VariantArray, Serialize, Deserialize)]
+pub enum SeedMode {
+    /// Seeds are used as they are.
+    #[default]
+    Absolute,
+    /// Seeds are added to the graph's master seed.
+    Offset,
+}
+
+impl SeedMode {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            SeedMode::Absolute => "Absolute",
+            SeedMode::Offset => "Offset from master",
+        }
+    }
+}
+
+/// Gives a node new seeds after the master seed was bumped by one, as part of reseeding the whole
+/// graph. Nodes in [`SeedMode::Offset`] already change with the master seed, while nodes in
+/// [`SeedMode::Absolute`] draw their seeds from `rng`, which should be seeded from the new master
+/// seed so that reseeding is repeatable. Locked nodes keep their seeds, and groups reseed the
+/// nodes inside them.
+///
+/// Takes the node's parts, as the editor keeps its nodes in its own type. Returns whether the
+/// node's noise changes.
+pub fn reseed_node(noise_type: NoiseType, config: &mut NoiseConfig, seed_mode: SeedMode, seed_locked: bool, rng: &mut Rng) -> bool {
+    if let NoiseConfig::Group(group) = config {
+        return group.graph.nodes.iter_mut().fold(false, |changed, node| {
+            reseed_node(node.noise_type, &mut node.config, node.seed_mode, node.seed_locked, rng) || changed
+        });
+    }
+    let seeds = noise_type.params().iter().filter(|spec| matches!(spec.kind, ParamKind::Seed { .. }));
+    match (seed_mode, seed_locked) {
+        (SeedMode::Absolute, false) => {
+            let mut changed = false;
+            for spec in seeds {
+                config.values_mut().set(spec.name, ParamValue::Int(rng.next_u32().into()));
+                changed = true;
+            }
+            changed
+        }
+        (SeedMode::Offset, false) => seeds.count() > 0,
+        (SeedMode::Absolute, true) => false,
+        // Moved back against the master seed, which adds up to the same seed.
+        (SeedMode::Offset, true) => {
+            for spec in seeds {
+                let seed = spec.seed(config.values()).wrapping_sub(1);
+                config.values_mut().set(spec.name, ParamValue::Int(seed.into()));
+            }
+            false
+        }
+    }
+}
+
+impl SavedNode {
+    /// The number of input pins, including parameter pins.
+    pub fn input_count(&self) -> usize {
+        self.noise_input_count() + self.param_pins.len()
+    }
+
+    /// The number of input pins before the parameter pins.
+    pub fn noise_input_count(&self) -> usize {
+        self.noise_type.input_count(&self.config)
+    }
+
+    pub fn input_type(&self, input: usize) -> PinType {
+        if input < self.noise_input_count() {
+            self.noise_type.input_type(&self.config, input)
+        } else {
+            PinType::Scalar
+        }
+    }
+
+    pub fn output_type(&self, output: usize) -> PinType {
+        self.noise_type.output_type(&self.config, output)
+    }
+
+    pub fn group(&self) -> Option<&Group> {
+        match &self.config {
+            NoiseConfig::Group(group) => Some(group),
+            _ => None,
+        }
+    }
+}
+
+/// Connects output pin `output` of node `from` to input pin `input` of node `to`. Nodes are
+/// referred to by their index in the graph.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SavedWire {
+    pub from: usize,
+    /// Only groups have more than one output.
+    #[serde(default)]
+    pub output: usize,
+    pub to: usize,
+    pub input: usize,
+}
+
+#[derive(Debug)]
+pub enum GraphFileError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+}
+
+impl fmt::Display for GraphFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphFileError::Io(err) => write!(f, "{err}"),
+            GraphFileError::Format(err) => write!(f, "Invalid graph file: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for GraphFileError {}
+
+impl NoiseGraph {
+    pub fn nodes(&self) -> &[SavedNode] {
+        &self.nodes
+    }
+
+    pub fn nodes_mut(&mut self) -> &mut [SavedNode] {
+        &mut self.nodes
+    }
+
+    pub fn wires(&self) -> &[SavedWire] {
+        &self.wires
+    }
+
+    /// Adds a node and returns its index.
+    pub fn add_node(&mut self, node: SavedNode) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    pub fn add_wire(&mut self, wire: SavedWire) {
+        self.wires.push(wire);
+    }
+
+    pub fn wires_mut(&mut self) -> &mut Vec<SavedWire> {
+        &mut self.wires
+    }
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    pub fn set_groups(&mut self, groups: Vec<Group>) {
+        self.groups = groups;
+    }
+
+    pub fn master_seed(&self) -> u32 {
+        self.master_seed
+    }
+
+    pub fn set_master_seed(&mut self, master_seed: u32) {
+        self.master_seed = master_seed;
+    }
+
+    /// Gives every node that isn't locked new seeds, including nodes inside groups, by bumping the
+    /// master seed. See [`reseed_node`].
+    pub fn reseed(&mut self) {
+        self.master_seed = self.master_seed.wrapping_add(1);
+        let mut rng = Rng::new(self.master_seed.into());
+        for node in &mut self.nodes {
+            reseed_node(node.noise_type, &mut node.config, node.seed_mode, node.seed_locked, &mut rng);
+        }
+    }
+
+    /// Adds the master seed to the seeds of nodes in [`SeedMode::Offset`].
+    pub fn apply_master_seed(&mut self) {
+        let master_seed = self.master_seed;
+        for node in &mut self.nodes {
+            if node.seed_mode != SeedMode::Offset {
+                continue;
+            }
+            for spec in node.noise_type.params().iter().filter(|spec| matches!(spec.kind, ParamKind::Seed { .. })) {
+                let seed = spec.seed(node.config.values()).wrapping_add(master_seed);
+                node.config.values_mut().set(spec.name, ParamValue::Int(seed.into()));
+            }
+        }
+    }
+
+    /// Returns the index of the node with the given name, or `None` if no node or several nodes
+    /// have it.
+    pub fn node_index(&self, name: &str) -> Option<usize> {
+        let mut matches = self.nodes.iter().enumerate().filter(|(_, node)| node.name == name);
+        match (matches.next(), matches.next()) {
+            (Some((index, _)), None) => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Returns the index of the node connected to the given input pin, if any.
+    pub fn input_source(&self, node: usize, input: usize) -> Option<usize> {
+        self.wires
+            .iter()
+            .find(|wire| wire.to == node && wire.input == input)
+            .map(|wire| wire.from)
+    }
+
+    /// Sets parameters exposed as input pins to the values of the scalar nodes wired to them, so
+    /// that building the graph only needs each node's config. Parameter pins without a scalar
+    /// node keep the node's own value.
+    pub fn resolve_params(&mut self) {
+        for node in 0..self.nodes.len() {
+            self.resolve_node_params(node, &mut Vec::new());
+        }
+    }
+
+    fn resolve_node_params(&mut self, node: usize, visiting: &mut Vec<usize>) {
+        // Scalar nodes can drive each other's parameters, so resolve sources first.
+        if visiting.contains(&node) {
+            return;
+        }
+        visiting.push(node);
+        let noise_inputs = self.nodes[node].noise_input_count();
+        for (pin, name) in self.nodes[node].param_pins.clone().iter().enumerate() {
+            let Some(source) = self.input_source(node, noise_inputs + pin) else { continue };
+            self.resolve_node_params(source, visiting);
+            let source = &self.nodes[source];
+            let Some(scalar) = source.noise_type.scalar(&source.config) else { continue };
+            let saved = &mut self.nodes[node];
+            let Some(value) = saved.noise_type.param(name).and_then(|spec| spec.from_scalar(scalar)) else { continue };
+            saved.config.values_mut().set(name, value);
+        }
+        visiting.pop();
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, GraphFileError> {
+        serde_json::from_str(json).map_err(GraphFileError::Format)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Graph should always serialize")
+    }
+
+    pub fn load(path: &Path) -> Result<Self, GraphFileError> {
+        Self::from_json(&std::fs::read_to_string(path).map_err(GraphFileError::Io)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), GraphFileError> {
+        std::fs::write(path, self.to_json()).map_err(GraphFileError::Io)
+    }
+
+    /// Builds the noise function for a node by recursively building the nodes connected to its
+    /// inputs. Unconnected inputs are treated as a constant 0, as are groups without outputs.
+    pub fn build_noise_fn<const DIM: usize>(&self, node: usize) -> DynNoise<DIM>
+    where
+        dyn NodeKind: BuildNoise<DIM>,
+    {
+        let flat = self.flatten();
+        flat.graph.build_flat(flat.output_node(node))
+    }
+
+    /// Builds a node of a graph without groups.
+    fn build_flat<const DIM: usize>(&self, node: Option<usize>) -> DynNoise<DIM>
+    where
+        dyn NodeKind: BuildNoise<DIM>,
+    {
+        match node {
+            Some(node) => self.build(node, &mut Vec::new()),
+            None => DynNoise::new(noise::Constant::new(0.0)),
+        }
+    }
+
+    fn build<const DIM: usize>(&self, node: usize, visiting: &mut Vec<usize>) -> DynNoise<DIM>
+    where
+        dyn NodeKind: BuildNoise<DIM>,
+    {
+        let Some(saved) = self.nodes.get(node) else { return DynNoise::new(noise::Constant::new(0.0)) };
+        // Break cycles rather than recursing forever.
+        if visiting.contains(&node) {
+            return DynNoise::new(noise::Constant::new(0.0));
+        }
+        visiting.push(node);
+        let inputs = (0..saved.noise_input_count())
+            .map(|input| match self.input_source(node, input) {
+                Some(source) => self.build(source, visiting),
+                None => DynNoise::new(noise::Constant::new(0.0)),
+            })
+            .collect();
+        visiting.pop();
+        saved.noise_type.build(&saved.config, inputs)
+    }
+
+    /// Builds a node's noise functions, ready to be sampled.
+    pub fn compile(&self, node: usize) -> CompiledNoise {
+        let flat = self.flatten();
+        let node = flat.output_node(node);
+        CompiledNoise {
+            noise_2d: flat.graph.build_flat(node),
+            noise_3d: flat.graph.build_flat(node),
+            noise_4d: flat.graph.build_flat(node),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::noises::Perlin;
+
+    use super::*;
+
+    /// Perlin noise seeded absolutely as "a", and from the master seed as "c", plus locked copies of
+    /// both. The locked offset node's seed wraps when it's moved back.
+    fn seeded_graph() -> NoiseGraph {
+        NoiseGraph::from_json(r#"{"nodes": [
+            {"name": "a", "noise_type": "Perlin", "config": {"Params": {"seed": 5}}},
+            {"name": "a_locked", "noise_type": "Perlin", "config": {"Params": {"seed": 6}}, "seed_locked": true},
+            {"name": "c", "noise_type": "Perlin", "config": {"Params": {"seed": 7}}, "seed_mode": "Offset"},
+            {"name": "c_locked", "noise_type": "Perlin", "config": {"Params": {"seed": 0}}, "seed_mode": "Offset", "seed_locked": true}
+        ], "wires": [], "master_seed": 10}"#).unwrap()
+    }
+
+    /// The seeds the nodes are built with, after adding the master seed.
+    fn seeds(graph: &NoiseGraph) -> Vec<u32> {
+        let mut graph = graph.clone();
+        graph.apply_master_seed();
+        graph.nodes().iter().map(|node| Perlin::SEED.seed(node.config.values())).collect()
+    }
+
+    #[test]
+    fn reseeding_is_repeatable() {
+        let (mut a, mut b) = (seeded_graph(), seeded_graph());
+        a.reseed();
+        b.reseed();
+        assert!(a == b);
+        assert_eq!(a.master_seed(), 11);
+        assert_ne!(seeds(&a)[0], 5);
+    }
+
+    #[test]
+    fn locked_nodes_keep_their_seeds() {
+        let mut graph = seeded_graph();
+        let before = seeds(&graph);
+        assert_eq!(before, [5, 6, 17, 10]);
+        for _ in 0..3 {
+            graph.reseed();
+            let after = seeds(&graph);
+            assert_eq!([after[1], after[3]], [before[1], before[3]]);
+        }
+        // Unlocked offset nodes follow the master seed.
+        assert_eq!(seeds(&graph)[2], 20);
+    }
+
+    #[test]
+    fn groups_reseed_their_nodes() {
+        let mut group = crate::group::Group::default();
+        group.graph.add_node(seeded_graph().nodes()[0].clone());
+        let mut config = NoiseConfig::Group(Box::new(group));
+        assert!(reseed_node(NoiseType::GROUP, &mut config, SeedMode::Absolute, false, &mut Rng::new(1)));
+        let NoiseConfig::Group(group) = &config else { unreachable!() };
+        assert_ne!(Perlin::SEED.seed(group.graph.nodes()[0].config.values()), 5);
+    }
+}
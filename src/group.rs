@@ -0,0 +1,201 @@
+//! Group nodes, which wrap a graph of their own behind a single node.
+//!
+//! Groups only exist for editing. Before a graph is built or translated to code it is flattened,
+//! replacing every group with the nodes inside it, so the rest of the crate only deals with plain
+//! nodes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{graph::{NoiseGraph, SavedWire}, noises::NoiseType};
+
+/// The contents of a group node.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+    /// Shown in the node's header, and in the graph menu once the group is added to it.
+    pub name: String,
+    pub graph: NoiseGraph,
+    pub inputs: Vec<GroupInput>,
+    pub outputs: Vec<GroupOutput>,
+    /// Indices of the inner nodes whose parameters are shown on the group node.
+    #[serde(default)]
+    pub exposed: Vec<usize>,
+}
+
+/// An input pin of a group, which feeds any number of input pins inside it.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupInput {
+    pub name: String,
+    pub targets: Vec<GroupPin>,
+}
+
+/// Input pin `input` of the inner node `node`.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GroupPin {
+    pub node: usize,
+    pub input: usize,
+}
+
+/// An output pin of a group, passing on output `output` of the inner node `node`.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupOutput {
+    pub name: String,
+    pub node: usize,
+    #[serde(default)]
+    pub output: usize,
+}
+
+/// A graph with its groups replaced by their contents.
+pub struct FlatGraph {
+    pub graph: NoiseGraph,
+    /// For each node of the original graph, the flattened node behind each of its outputs.
+    outputs: Vec<Vec<usize>>,
+    /// For each node of the original graph, the flattened input pins behind each of its inputs.
+    inputs: Vec<Vec<Vec<GroupPin>>>,
+}
+
+impl FlatGraph {
+    /// The flattened node producing the first output of a node of the original graph. This is
+    /// `None` for groups without outputs.
+    pub fn output_node(&self, node: usize) -> Option<usize> {
+        self.outputs.get(node)?.first().copied()
+    }
+}
+
+impl NoiseGraph {
+    /// Replaces every group, including groups inside groups, with the nodes it contains, then
+    /// settles every node's parameters with [`NoiseGraph::resolve_params`] and
+    /// [`NoiseGraph::apply_master_seed`].
+    pub fn flatten(&self) -> FlatGraph {
+        let mut flat = self.flatten_groups();
+        flat.graph.set_master_seed(self.master_seed());
+        flat.graph.resolve_params();
+        flat.graph.apply_master_seed();
+        flat
+    }
+
+    fn flatten_groups(&self) -> FlatGraph {
+        let mut flat = FlatGraph { graph: NoiseGraph::default(), outputs: Vec::new(), inputs: Vec::new() };
+        // Group nodes saved with some other config, as in hand-edited files, have no pins.
+        let empty = Group::default();
+        for node in self.nodes() {
+            let group = node.group().or((node.noise_type == NoiseType::GROUP).then_some(&empty));
+            match group {
+                Some(group) => {
+                    let inner = group.graph.flatten_groups();
+                    let offset = flat.graph.nodes().len();
+                    for inner_node in inner.graph.nodes() {
+                        flat.graph.add_node(inner_node.clone());
+                    }
+                    for wire in inner.graph.wires() {
+                        flat.graph.add_wire(SavedWire { from: wire.from + offset, to: wire.to + offset, ..*wire });
+                    }
+                    flat.outputs.push(group.outputs
+                        .iter()
+                        .filter_map(|output| inner.outputs.get(output.node)?.get(output.output))
+                        .map(|node| node + offset)
+                        .collect());
+                    flat.inputs.push(group.inputs
+                        .iter()
+                        .map(|input| input.targets
+                            .iter()
+                            .filter_map(|target| inner.inputs.get(target.node)?.get(target.input))
+                            .flatten()
+                            .map(|pin| GroupPin { node: pin.node + offset, input: pin.input })
+                            .collect())
+                        .collect());
+                }
+                None => {
+                    let index = flat.graph.add_node(node.clone());
+                    flat.outputs.push(vec![index]);
+                    flat.inputs.push((0..node.input_count())
+                        .map(|input| vec![GroupPin { node: index, input }])
+                        .collect());
+                }
+            }
+        }
+        for wire in self.wires() {
+            let Some(&from) = flat.outputs.get(wire.from).and_then(|outputs| outputs.get(wire.output)) else { continue };
+            let Some(targets) = flat.inputs.get(wire.to).and_then(|inputs| inputs.get(wire.input)) else { continue };
+            for target in targets.clone() {
+                flat.graph.add_wire(SavedWire { from, output: 0, to: target.node, input: target.input });
+            }
+        }
+        flat
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A constant "c" wired into the first input of `group`, the graph's node 1.
+    fn feeding(group: &str) -> NoiseGraph {
+        NoiseGraph::from_json(&format!(r#"{{"nodes": [
+            {{"name": "c", "noise_type": "Constant", "config": "Empty"}},
+            {{"name": "g", "noise_type": "Group", "config": {{"Group": {group}}}}}
+        ], "wires": [{{"from": 0, "to": 1, "input": 0}}]}}"#)).unwrap()
+    }
+
+    /// The flattened graph's nodes by name, and its wires as (from, to, input).
+    fn contents(flat: &FlatGraph) -> (Vec<&str>, Vec<(usize, usize, usize)>) {
+        let names = flat.graph.nodes().iter().map(|node| node.name.as_str()).collect();
+        let mut wires: Vec<_> = flat.graph.wires().iter().map(|wire| (wire.from, wire.to, wire.input)).collect();
+        wires.sort();
+        (names, wires)
+    }
+
+    #[test]
+    fn group_inputs_feed_every_target() {
+        let flat = feeding(r#"{
+            "name": "Fan",
+            "graph": {"nodes": [
+                {"name": "a", "noise_type": "Max", "config": "Empty"},
+                {"name": "b", "noise_type": "Min", "config": "Empty"}
+            ], "wires": [{"from": 0, "to": 1, "input": 0}]},
+            "inputs": [{"name": "In", "targets": [{"node": 0, "input": 0}, {"node": 0, "input": 1}, {"node": 1, "input": 1}]}],
+            "outputs": [{"name": "Out", "node": 1}]
+        }"#).flatten_groups();
+        assert_eq!(contents(&flat), (vec!["c", "a", "b"], vec![(0, 1, 0), (0, 1, 1), (0, 2, 1), (1, 2, 0)]));
+        assert_eq!(flat.output_node(1), Some(2));
+    }
+
+    #[test]
+    fn nested_groups_are_flattened() {
+        let inner = r#"{
+            "name": "Inner",
+            "graph": {"nodes": [{"name": "m", "noise_type": "Max", "config": "Empty"}], "wires": []},
+            "inputs": [{"name": "In", "targets": [{"node": 0, "input": 1}]}],
+            "outputs": [{"name": "Out", "node": 0}]
+        }"#;
+        let flat = feeding(&format!(r#"{{
+            "name": "Outer",
+            "graph": {{"nodes": [
+                {{"name": "p", "noise_type": "Perlin", "config": "Empty"}},
+                {{"name": "i", "noise_type": "Group", "config": {{"Group": {inner}}}}}
+            ], "wires": [{{"from": 0, "to": 1, "input": 0}}]}},
+            "inputs": [{{"name": "In", "targets": [{{"node": 1, "input": 0}}]}}],
+            "outputs": [{{"name": "Out", "node": 1}}]
+        }}"#)).flatten_groups();
+        // Both the outer graph's constant and the inner Perlin noise reach the same pin of "m".
+        assert_eq!(contents(&flat), (vec!["c", "p", "m"], vec![(0, 2, 1), (1, 2, 1)]));
+        assert_eq!(flat.output_node(1), Some(2));
+    }
+
+    #[test]
+    fn groups_without_outputs_output_nothing() {
+        let mut graph = feeding(r#"{
+            "name": "Sink",
+            "graph": {"nodes": [{"name": "m", "noise_type": "Max", "config": "Empty"}], "wires": []},
+            "inputs": [{"name": "In", "targets": [{"node": 0, "input": 0}]}],
+            "outputs": []
+        }"#);
+        let max = graph.add_node(graph.nodes()[0].clone());
+        graph.nodes_mut()[max].name = "max".to_string();
+        graph.nodes_mut()[max].noise_type = NoiseType::MAX;
+        graph.add_wire(SavedWire { from: 1, output: 0, to: max, input: 0 });
+        let flat = graph.flatten_groups();
+        assert_eq!(flat.output_node(1), None);
+        // Only the wire into the group is left.
+        assert_eq!(contents(&flat), (vec!["c", "m", "max"], vec![(0, 1, 0)]));
+    }
+}
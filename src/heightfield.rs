@@ -0,0 +1,176 @@
+//! Renders noise maps as shaded 3D terrain, rasterized on the CPU.
+
+use noise::utils::NoiseMap;
+use serde::{Deserialize, Serialize};
+
+/// The most grid cells along each side of the mesh. Larger maps are subsampled.
+const MAX_GRID_CELLS: usize = 128;
+
+const BACKGROUND: [u8; 3] = [32, 32, 36];
+const LOW_COLOR: [f32; 3] = [0.30, 0.38, 0.24];
+const HIGH_COLOR: [f32; 3] = [0.92, 0.90, 0.85];
+const AMBIENT: f32 = 0.2;
+
+/// Where the heightfield is viewed and lit from. Angles are in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightfieldView {
+    /// Rotation of the camera around the vertical axis.
+    pub yaw: f32,
+    /// Angle of the camera above the horizon.
+    pub pitch: f32,
+    /// Height of a noise value of 1, relative to the width of the terrain.
+    pub height_scale: f32,
+    /// Direction the light comes from, around the vertical axis.
+    pub light_azimuth: f32,
+    /// Angle of the light above the horizon.
+    pub light_elevation: f32,
+}
+
+impl Default for HeightfieldView {
+    fn default() -> Self {
+        Self { yaw: 30.0, pitch: 35.0, height_scale: 0.2, light_azimuth: 135.0, light_elevation: 45.0 }
+    }
+}
+
+impl HeightfieldView {
+    /// Rotates a world space point into view space, where x is right, y is up and z points
+    /// towards the camera.
+    fn view_space(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        let (sin_yaw, cos_yaw) = self.yaw.to_radians().sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.to_radians().sin_cos();
+        let (x, z) = (x * cos_yaw - z * sin_yaw, x * sin_yaw + z * cos_yaw);
+        let (y, z) = (y * cos_pitch - z * sin_pitch, y * sin_pitch + z * cos_pitch);
+        [x, y, z]
+    }
+
+    /// Unit vector pointing towards the light, in world space.
+    fn light_direction(&self) -> [f32; 3] {
+        let (sin_azimuth, cos_azimuth) = self.light_azimuth.to_radians().sin_cos();
+        let (sin_elevation, cos_elevation) = self.light_elevation.to_radians().sin_cos();
+        [cos_elevation * cos_azimuth, sin_elevation, cos_elevation * sin_azimuth]
+    }
+}
+
+struct Vertex {
+    /// Screen position and depth, with larger depths closer to the camera.
+    screen: [f32; 3],
+    color: [f32; 3],
+}
+
+/// Renders the map as a lit terrain mesh viewed from above at an angle. This assumes the noise is
+/// in the range [-1, 1].
+pub fn render(map: &NoiseMap, view: &HeightfieldView, width: usize, height: usize) -> image::RgbImage {
+    let (map_width, map_height) = map.size();
+    let mut image = image::RgbImage::from_pixel(width as u32, height as u32, image::Rgb(BACKGROUND));
+    if map_width < 2 || map_height < 2 || width == 0 || height == 0 {
+        return image;
+    }
+
+    let step = map_width.max(map_height).div_ceil(MAX_GRID_CELLS).max(1);
+    let columns = (map_width - 1) / step + 1;
+    let rows = (map_height - 1) / step + 1;
+    let value = |column: usize, row: usize| map.get_value(column * step, row * step) as f32;
+    // The terrain spans 1 unit along its longer side, centred on the origin.
+    let cell = 1.0 / (columns.max(rows) - 1) as f32;
+    let world = |column: usize, row: usize| [
+        (column as f32 - (columns - 1) as f32 / 2.0) * cell,
+        value(column, row) * view.height_scale,
+        (row as f32 - (rows - 1) as f32 / 2.0) * cell,
+    ];
+
+    let light = view.light_direction();
+    let screen_scale = width.min(height) as f32 / 1.5;
+    let vertices: Vec<Vertex> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| {
+            let point = world(column, row);
+            let [x, y, z] = view.view_space(point);
+            // Central differences, falling back to one sided ones at the edges.
+            let left = world(column.saturating_sub(1), row);
+            let right = world((column + 1).min(columns - 1), row);
+            let up = world(column, row.saturating_sub(1));
+            let down = world(column, (row + 1).min(rows - 1));
+            let normal = normalize(cross(sub(down, up), sub(right, left)));
+            let brightness = AMBIENT + (1.0 - AMBIENT) * dot(normal, light).max(0.0);
+            let t = (value(column, row) * 0.5 + 0.5).clamp(0.0, 1.0);
+            let color = std::array::from_fn(|i| (LOW_COLOR[i] + (HIGH_COLOR[i] - LOW_COLOR[i]) * t) * brightness);
+            Vertex {
+                screen: [width as f32 / 2.0 + x * screen_scale, height as f32 / 2.0 - y * screen_scale, z],
+                color,
+            }
+        })
+        .collect();
+
+    let mut depth = vec![f32::NEG_INFINITY; width * height];
+    for row in 0..rows - 1 {
+        for column in 0..columns - 1 {
+            let index = row * columns + column;
+            let [a, b, c, d] = [index, index + 1, index + columns, index + columns + 1];
+            for [i, j, k] in [[a, c, b], [b, c, d]] {
+                rasterize([&vertices[i], &vertices[j], &vertices[k]], &mut image, &mut depth);
+            }
+        }
+    }
+    image
+}
+
+/// Fills a triangle with colours interpolated between its vertices, keeping the closest surface
+/// at each pixel.
+fn rasterize(triangle: [&Vertex; 3], image: &mut image::RgbImage, depth: &mut [f32]) {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let [p0, p1, p2] = triangle.map(|vertex| vertex.screen);
+    let area = edge(p0, p1, p2);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+    let min_x = p0[0].min(p1[0]).min(p2[0]).floor().max(0.0) as usize;
+    let max_x = p0[0].max(p1[0]).max(p2[0]).ceil().min(width as f32 - 1.0);
+    let min_y = p0[1].min(p1[1]).min(p2[1]).floor().max(0.0) as usize;
+    let max_y = p0[1].max(p1[1]).max(p2[1]).ceil().min(height as f32 - 1.0);
+    if max_x < 0.0 || max_y < 0.0 {
+        return;
+    }
+    for y in min_y..=max_y as usize {
+        for x in min_x..=max_x as usize {
+            let pixel = [x as f32 + 0.5, y as f32 + 0.5, 0.0];
+            let weights = [edge(p1, p2, pixel) / area, edge(p2, p0, pixel) / area, edge(p0, p1, pixel) / area];
+            if weights.iter().any(|&weight| weight < 0.0) {
+                continue;
+            }
+            let z = weights[0] * p0[2] + weights[1] * p1[2] + weights[2] * p2[2];
+            let closest = &mut depth[y * width + x];
+            if z <= *closest {
+                continue;
+            }
+            *closest = z;
+            let color: [u8; 3] = std::array::from_fn(|i| {
+                let channel = weights[0] * triangle[0].color[i] + weights[1] * triangle[1].color[i] + weights[2] * triangle[2].color[i];
+                (channel.clamp(0.0, 1.0) * 255.0) as u8
+            });
+            image.put_pixel(x as u32, y as u32, image::Rgb(color));
+        }
+    }
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `c` on screen.
+fn edge(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = dot(v, v).sqrt();
+    if length == 0.0 { [0.0, 1.0, 0.0] } else { v.map(|component| component / length) }
+}
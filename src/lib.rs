@@ -1,5 +1,42 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
+//! Noise Explorer is a node graph editor for the [`noise`] crate.
+//!
+//! Besides the editor, the crate can load graphs saved from it and sample them directly, which
+//! doesn't need the `gui` feature:
+//!
+//! ```
+//! use noise::NoiseFn;
+//! use noise_explorer::graph::NoiseGraph;
+//!
+//! let graph = NoiseGraph::from_json(r#"{
+//!     "nodes": [
+//!         { "name": "hills", "noise_type": "Perlin", "config": { "Params": { "seed": 7 } } },
+//!         { "name": "floor", "noise_type": "Constant", "config": { "Params": { "value": 0.0 } } },
+//!         { "name": "terrain", "noise_type": "Max", "config": "Empty" }
+//!     ],
+//!     "wires": [
+//!         { "from": 0, "to": 2, "input": 0 },
+//!         { "from": 1, "to": 2, "input": 1 }
+//!     ]
+//! }"#).unwrap();
+//!
+//! let terrain = graph.compile(graph.node_index("terrain").unwrap());
+//! assert!(terrain.noise_2d.get([0.3, 0.7]) >= 0.0);
+//! ```
+
+#[cfg(feature = "gui")]
 mod app;
-mod noises;
+pub mod codegen;
+pub mod evolve;
+pub mod graph;
+pub mod group;
+pub mod heightfield;
+pub mod library;
+pub mod noises;
+pub mod params;
+pub mod random;
+pub mod raster;
+pub mod render;
+#[cfg(feature = "gui")]
 pub use app::NoiseExplorerApp;
@@ -0,0 +1,90 @@
+//! Named presets of configured nodes and subgraphs, kept in a library file that's shared between
+//! graphs. Libraries can also be exported and imported whole as preset packs.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::graph::{GraphFileError, NoiseGraph};
+
+/// File extension used for exported preset packs.
+pub const PACK_EXTENSION: &str = "noisepresets";
+
+/// A node or subgraph saved under a name. Node positions are relative to where it's inserted.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub graph: NoiseGraph,
+}
+
+/// A collection of presets, either the user's library or a pack being imported or exported.
+#[derive(Default, Serialize, Deserialize)]
+pub struct PresetLibrary {
+    presets: Vec<Preset>,
+}
+
+impl PresetLibrary {
+    pub fn presets(&self) -> &[Preset] {
+        &self.presets
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.presets.iter().any(|preset| preset.name == name)
+    }
+
+    /// Adds a preset, replacing any preset with the same name.
+    pub fn add(&mut self, preset: Preset) {
+        match self.presets.iter_mut().find(|existing| existing.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.presets.remove(index);
+    }
+
+    /// Adds every preset of a pack, replacing presets with the same names.
+    pub fn import(&mut self, pack: PresetLibrary) {
+        for preset in pack.presets {
+            self.add(preset);
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, GraphFileError> {
+        serde_json::from_str(json).map_err(GraphFileError::Format)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Library should always serialize")
+    }
+
+    pub fn load(path: &Path) -> Result<Self, GraphFileError> {
+        Self::from_json(&std::fs::read_to_string(path).map_err(GraphFileError::Io)?)
+    }
+
+    /// Saves the library, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), GraphFileError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(GraphFileError::Io)?;
+        }
+        std::fs::write(path, self.to_json()).map_err(GraphFileError::Io)
+    }
+}
+
+/// Where the user's library is kept: the path in `NOISE_EXPLORER_LIBRARY` if set, otherwise
+/// `noise_explorer/presets.json` in the platform's configuration directory.
+pub fn default_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("NOISE_EXPLORER_LIBRARY") {
+        return Some(path.into());
+    }
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let config_dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).or_else(|| home().map(|home| home.join(".config")))
+    };
+    Some(config_dir?.join("noise_explorer").join("presets.json"))
+}
@@ -1,18 +1,50 @@
-use egui::{Align, ImageSource, Layout, Vec2};
-use noise::{NoiseFn, Perlin};
-use serde::{Deserialize, Serialize};
-use strum::{IntoStaticStr, VariantArray};
+//! The node types that make up a noise graph.
+//!
+//! Each kind of node implements [`NodeKind`] and is looked up through a registry, so that crates
+//! using this one can add kinds of their own with [`register`]. The built-in kinds are always
+//! registered.
 
-pub struct DynNoise(Box<dyn NoiseFn<f64, 2> + Send + 'static>);
+use std::{
+    fmt,
+    sync::{LazyLock, RwLock},
+};
 
-impl DynNoise {
-    pub fn new(noise_fn: impl NoiseFn<f64, 2> + Send + 'static) -> Self {
+#[cfg(feature = "gui")]
+use egui::{Align, Layout};
+use noise::NoiseFn;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    codegen::shader::ShaderOp,
+    group::Group,
+    params::{ParamSpec, ParamValue, ParamValues},
+};
+#[cfg(feature = "gui")]
+use crate::params::BodyContext;
+
+mod builtin;
+mod erosion;
+mod expression;
+mod raster_ops;
+mod sampler;
+
+pub use builtin::{Blend, Checkerboard, Constant, ConstantScalar, Curve, GroupKind, Max, Min, Parameter, Perlin, Terrace};
+pub use erosion::Erosion;
+pub use expression::{Expression, ExpressionKind, ParseError};
+pub use raster_ops::{Blur, DistanceTransform, EdgeDetect, Morphology, Sharpen};
+pub use sampler::ImageSampler;
+
+/// A type-erased noise function, as produced by compiling a graph node.
+pub struct DynNoise<const DIM: usize>(Box<dyn NoiseFn<f64, DIM> + Send + Sync + 'static>);
+
+impl<const DIM: usize> DynNoise<DIM> {
+    pub fn new(noise_fn: impl NoiseFn<f64, DIM> + Send + Sync + 'static) -> Self {
         Self(Box::new(noise_fn))
     }
 }
 
-impl NoiseFn<f64, 2> for DynNoise {
-    fn get(&self, point: [f64; 2]) -> f64 {
+impl<const DIM: usize> NoiseFn<f64, DIM> for DynNoise<DIM> {
+    fn get(&self, point: [f64; DIM]) -> f64 {
         self.0.get(point)
     }
 }
@@ -20,112 +52,389 @@ impl NoiseFn<f64, 2> for DynNoise {
 #[derive(Debug, Eq, PartialEq)]
 pub enum NoiseClassification {
     Source,
-    Combinator
+    Combinator,
+    /// Reshapes the values of a single input.
+    Modifier,
+    /// Works on a grid of its input's values rather than point by point, see [`crate::raster`].
+    Raster,
+    /// Produces a single number rather than a noise field, for driving parameter pins.
+    Scalar,
+    Group,
 }
 
-#[derive(Debug, Eq, PartialEq, VariantArray, Clone, Copy, Serialize, Deserialize)]
-pub enum NoiseType {
-    // Sources
-    Checkerboard,
-    Perlin,
-    Constant,
-
-    // Combinators
-    Blend,
-    Max,
-    Min,
+/// What flows through a wire. Wires only connect pins of the same type.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PinType {
+    Noise,
+    /// A single number, which sets a parameter exposed as an input pin.
+    Scalar,
 }
 
-impl NoiseType {
-    pub fn all() -> impl Iterator<Item = &'static NoiseType> {
-        NoiseType::VARIANTS.iter()
+/// A kind of node: its metadata, pins, parameters and how it builds its noise function.
+///
+/// Only the methods without a default need implementing. The rest describe nodes with a single
+/// output, no inputs and no parameters, which can't be translated to code.
+pub trait NodeKind: Send + Sync + 'static {
+    /// Identifies the kind in saved graphs, so it must be unique and never change.
+    fn id(&self) -> &'static str;
+
+    /// Shown in the graph menu and node headers.
+    fn name(&self) -> &'static str;
+
+    /// Which graph menu the kind is listed under.
+    fn classification(&self) -> NoiseClassification;
+
+    /// The parameters shown in the node's body. Nodes start with their defaults.
+    fn params(&self) -> &'static [ParamSpec] {
+        &[]
     }
 
-    pub fn combinators() -> impl Iterator<Item = &'static NoiseType> {
-        Self::all().filter(|n| n.classification() == NoiseClassification::Combinator)
+    /// The config of a new node of this kind.
+    fn config(&self) -> NoiseConfig {
+        match self.params() {
+            [] => NoiseConfig::Empty,
+            params => NoiseConfig::Params(ParamValues::defaults(params)),
+        }
     }
 
-    pub fn sources() -> impl Iterator<Item = &'static NoiseType> {
-        Self::all().filter(|n| n.classification() == NoiseClassification::Source)
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        0
     }
 
-    pub const fn name(&self) -> &'static str {
-        use NoiseType::*;
-        match self {
-            Perlin => "Perlin",
-            Max => "Maximum",
-            Min => "Minimum",
-            Blend => "Blend",
-            Checkerboard => "Checkerboard",
-            Constant => "Constant",
+    fn output_count(&self, _config: &NoiseConfig) -> usize {
+        1
+    }
+
+    /// The label of an input pin.
+    fn input_name(&self, _config: &NoiseConfig, index: usize) -> String {
+        format!("Input {}", index + 1)
+    }
+
+    fn input_type(&self, _config: &NoiseConfig, _index: usize) -> PinType {
+        PinType::Noise
+    }
+
+    fn output_type(&self, _config: &NoiseConfig, _index: usize) -> PinType {
+        PinType::Noise
+    }
+
+    /// The number produced by scalar nodes, see [`NoiseClassification::Scalar`].
+    fn scalar(&self, _config: &NoiseConfig) -> Option<f64> {
+        None
+    }
+
+    /// Constructs the noise function for a node, given the already built noise functions connected
+    /// to each of its inputs. The [`build_in_every_dimension`](crate::build_in_every_dimension)
+    /// macro implements all three from one generic function.
+    fn build_2d(&self, config: &NoiseConfig, inputs: Vec<DynNoise<2>>) -> DynNoise<2>;
+    fn build_3d(&self, config: &NoiseConfig, inputs: Vec<DynNoise<3>>) -> DynNoise<3>;
+    fn build_4d(&self, config: &NoiseConfig, inputs: Vec<DynNoise<4>>) -> DynNoise<4>;
+
+    /// The `noise` crate constructor for this kind along with its literal arguments. Generated code
+    /// passes the node's inputs after these arguments. Returns `None` if there is no equivalent
+    /// in the `noise` crate.
+    fn rust_constructor(&self, _config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        None
+    }
+
+    /// Builder methods called on the result of [`NodeKind::rust_constructor`] in generated code,
+    /// each with its arguments, such as `add_control_point(0.5)`.
+    fn rust_methods(&self, _config: &NoiseConfig) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The shader operation for a node, given the operations of its inputs. `perlin_table`
+    /// returns the index of the permutation table for a seed. Returns `None` if the node can't be
+    /// translated to a shader.
+    fn shader_op(
+            &self,
+            _config: &NoiseConfig,
+            _inputs: &[usize],
+            _perlin_table: &mut dyn FnMut(u32) -> usize) -> Option<ShaderOp> {
+        None
+    }
+
+    /// Shows the controls for the node's parameters, returning whether any changed. By default
+    /// these are generated from [`NodeKind::params`].
+    #[cfg(feature = "gui")]
+    fn show_body(&self, config: &mut NoiseConfig, context: &BodyContext<'_>, ui: &mut egui::Ui, scale: f32) -> bool {
+        match self.params() {
+            [] => false,
+            params => crate::params::show(params, config.values_mut(), context, ui, scale),
         }
     }
+}
 
-    pub fn lowercase_name(&self) -> &'static str {
-        use NoiseType::*;
-        match self {
-            Perlin => "perlin",
-            Max => "maximum",
-            Min => "minimum",
-            Blend => "blend",
-            Checkerboard => "checkerboard",
-            Constant => "constant",
+/// Implements [`NodeKind::build_2d`], `build_3d` and `build_4d` by calling a function that's
+/// generic over the number of dimensions, with the signature
+/// `fn<const DIM: usize>(&NoiseConfig, Vec<DynNoise<DIM>>) -> DynNoise<DIM>`.
+#[macro_export]
+macro_rules! build_in_every_dimension {
+    ($build:expr) => {
+        fn build_2d(&self, config: &$crate::noises::NoiseConfig, inputs: Vec<$crate::noises::DynNoise<2>>)
+            -> $crate::noises::DynNoise<2> {
+            $build(config, inputs)
+        }
+
+        fn build_3d(&self, config: &$crate::noises::NoiseConfig, inputs: Vec<$crate::noises::DynNoise<3>>)
+            -> $crate::noises::DynNoise<3> {
+            $build(config, inputs)
         }
+
+        fn build_4d(&self, config: &$crate::noises::NoiseConfig, inputs: Vec<$crate::noises::DynNoise<4>>)
+            -> $crate::noises::DynNoise<4> {
+            $build(config, inputs)
+        }
+    };
+}
+
+/// Picks the [`NodeKind`] build method for a number of dimensions. Implemented for 2, 3 and 4.
+pub trait BuildNoise<const DIM: usize> {
+    fn build_noise(&self, config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM>;
+}
+
+impl BuildNoise<2> for dyn NodeKind {
+    fn build_noise(&self, config: &NoiseConfig, inputs: Vec<DynNoise<2>>) -> DynNoise<2> {
+        self.build_2d(config, inputs)
+    }
+}
+
+impl BuildNoise<3> for dyn NodeKind {
+    fn build_noise(&self, config: &NoiseConfig, inputs: Vec<DynNoise<3>>) -> DynNoise<3> {
+        self.build_3d(config, inputs)
+    }
+}
+
+impl BuildNoise<4> for dyn NodeKind {
+    fn build_noise(&self, config: &NoiseConfig, inputs: Vec<DynNoise<4>>) -> DynNoise<4> {
+        self.build_4d(config, inputs)
+    }
+}
+
+/// Every registered kind, in the order they're listed in the graph menu.
+static REGISTRY: LazyLock<RwLock<Vec<NoiseType>>> = LazyLock::new(|| RwLock::new(vec![
+    NoiseType::CHECKERBOARD,
+    NoiseType::PERLIN,
+    NoiseType::CONSTANT,
+    NoiseType::IMAGE_SAMPLER,
+    NoiseType::BLEND,
+    NoiseType::MAX,
+    NoiseType::MIN,
+    NoiseType::EXPRESSION,
+    NoiseType::CURVE,
+    NoiseType::TERRACE,
+    NoiseType::EROSION,
+    NoiseType::BLUR,
+    NoiseType::SHARPEN,
+    NoiseType::EDGE_DETECT,
+    NoiseType::DISTANCE_TRANSFORM,
+    NoiseType::MORPHOLOGY,
+    NoiseType::PARAMETER,
+    NoiseType::CONSTANT_SCALAR,
+    NoiseType::GROUP,
+]));
+
+/// Adds a kind of node, making it available in the graph menu and when loading graphs. Kinds must
+/// be registered before loading graphs that use them.
+///
+/// # Panics
+///
+/// If a kind with the same [`NodeKind::id`] is already registered.
+pub fn register(kind: impl NodeKind) -> NoiseType {
+    let noise_type = NoiseType(Box::leak(Box::new(kind)));
+    let mut registry = REGISTRY.write().expect("Node registry was poisoned");
+    assert!(
+        !registry.iter().any(|registered| registered.id() == noise_type.id()),
+        "A node kind with id {:?} is already registered", noise_type.id());
+    registry.push(noise_type);
+    noise_type
+}
+
+/// A registered kind of node. Saved graphs refer to kinds by their id.
+#[derive(Clone, Copy)]
+pub struct NoiseType(&'static dyn NodeKind);
+
+impl NoiseType {
+    pub const CHECKERBOARD: NoiseType = NoiseType(&Checkerboard);
+    pub const PERLIN: NoiseType = NoiseType(&Perlin);
+    pub const CONSTANT: NoiseType = NoiseType(&Constant);
+    pub const IMAGE_SAMPLER: NoiseType = NoiseType(&ImageSampler);
+    pub const BLEND: NoiseType = NoiseType(&Blend);
+    pub const MAX: NoiseType = NoiseType(&Max);
+    pub const MIN: NoiseType = NoiseType(&Min);
+    pub const EXPRESSION: NoiseType = NoiseType(&ExpressionKind);
+    pub const CURVE: NoiseType = NoiseType(&Curve);
+    pub const TERRACE: NoiseType = NoiseType(&Terrace);
+    pub const EROSION: NoiseType = NoiseType(&Erosion);
+    pub const BLUR: NoiseType = NoiseType(&Blur);
+    pub const SHARPEN: NoiseType = NoiseType(&Sharpen);
+    pub const EDGE_DETECT: NoiseType = NoiseType(&EdgeDetect);
+    pub const DISTANCE_TRANSFORM: NoiseType = NoiseType(&DistanceTransform);
+    pub const MORPHOLOGY: NoiseType = NoiseType(&Morphology);
+    pub const PARAMETER: NoiseType = NoiseType(&Parameter);
+    pub const CONSTANT_SCALAR: NoiseType = NoiseType(&ConstantScalar);
+    pub const GROUP: NoiseType = NoiseType(&GroupKind);
+
+    /// Every registered kind.
+    pub fn all() -> Vec<NoiseType> {
+        REGISTRY.read().expect("Node registry was poisoned").clone()
+    }
+
+    pub fn combinators() -> impl Iterator<Item = NoiseType> {
+        Self::all().into_iter().filter(|n| n.classification() == NoiseClassification::Combinator)
+    }
+
+    pub fn sources() -> impl Iterator<Item = NoiseType> {
+        Self::all().into_iter().filter(|n| n.classification() == NoiseClassification::Source)
+    }
+
+    pub fn modifiers() -> impl Iterator<Item = NoiseType> {
+        Self::all().into_iter().filter(|n| n.classification() == NoiseClassification::Modifier)
+    }
+
+    pub fn rasters() -> impl Iterator<Item = NoiseType> {
+        Self::all().into_iter().filter(|n| n.classification() == NoiseClassification::Raster)
+    }
+
+    pub fn scalars() -> impl Iterator<Item = NoiseType> {
+        Self::all().into_iter().filter(|n| n.classification() == NoiseClassification::Scalar)
+    }
+
+    pub fn from_id(id: &str) -> Option<NoiseType> {
+        Self::all().into_iter().find(|noise_type| noise_type.id() == id)
+    }
+
+    pub fn kind(&self) -> &'static dyn NodeKind {
+        self.0
+    }
+
+    pub fn id(&self) -> &'static str {
+        self.0.id()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    pub fn lowercase_name(&self) -> String {
+        self.0.name().to_lowercase()
     }
 
     pub fn classification(&self) -> NoiseClassification {
-        use NoiseType::*;
-        match self {
-            Perlin | Checkerboard | Constant => NoiseClassification::Source,
-            Max | Min | Blend => NoiseClassification::Combinator,
-        }
+        self.0.classification()
     }
 
     pub fn config(&self) -> NoiseConfig {
-        use NoiseType::*;
-        match self {
-            Perlin => NoiseConfig::Perlin { 
-                seed: 12345
-            },
-            Constant => NoiseConfig::Constant {
-                value: 0.5
-            },
-            _ => NoiseConfig::Empty
-        }
+        self.0.config()
     }
 
-    pub fn input_count(&self) -> usize {
-        use NoiseType::*;
-        match self {
-            Checkerboard | Perlin | Constant => 0,
-            Blend => 3,
-            Max | Min => 2,
-        }
+    /// The number of input pins, which for groups depends on their config.
+    pub fn input_count(&self, config: &NoiseConfig) -> usize {
+        self.0.input_count(config)
     }
 
-    pub fn show_input(&self, input_index: usize, ui: &mut egui::Ui, scale: f32) {
-        use NoiseType::*;
-        match self {
-            Checkerboard | Perlin | Constant => panic!("No input expected"),
-            Blend => match input_index {
-                0 => ui.label("A"),
-                1 => ui.label("B"),
-                2 => ui.label("Control"),
-                _ => panic!("Unexpected input pin index")
-            },
-            Max | Min => match input_index {
-                0 => ui.label("A"),
-                1 => ui.label("B"),
-                _ => panic!("Unexpected input pin index")
-            },
-        };
+    /// The number of output pins, which is 1 apart from groups.
+    pub fn output_count(&self, config: &NoiseConfig) -> usize {
+        self.0.output_count(config)
+    }
+
+    pub fn params(&self) -> &'static [ParamSpec] {
+        self.0.params()
+    }
+
+    /// Finds a parameter by name.
+    pub fn param(&self, name: &str) -> Option<&'static ParamSpec> {
+        self.params().iter().find(|spec| spec.name == name)
+    }
+
+    pub fn input_name(&self, config: &NoiseConfig, index: usize) -> String {
+        self.0.input_name(config, index)
+    }
+
+    pub fn input_type(&self, config: &NoiseConfig, index: usize) -> PinType {
+        self.0.input_type(config, index)
+    }
+
+    pub fn output_type(&self, config: &NoiseConfig, index: usize) -> PinType {
+        self.0.output_type(config, index)
+    }
+
+    pub fn scalar(&self, config: &NoiseConfig) -> Option<f64> {
+        self.0.scalar(config)
+    }
+
+    /// Constructs the noise function for this node, given the already built noise functions connected
+    /// to each of its inputs.
+    pub fn build<const DIM: usize>(&self, config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM>
+    where
+        dyn NodeKind: BuildNoise<DIM>,
+    {
+        assert_eq!(inputs.len(), self.input_count(config), "Unexpected input count");
+        self.0.build_noise(config, inputs)
+    }
+
+    /// See [`NodeKind::rust_constructor`].
+    pub fn rust_constructor(&self, config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        self.0.rust_constructor(config)
+    }
+
+    /// See [`NodeKind::rust_methods`].
+    pub fn rust_methods(&self, config: &NoiseConfig) -> Vec<String> {
+        self.0.rust_methods(config)
+    }
+
+    /// See [`NodeKind::shader_op`].
+    pub fn shader_op(
+            &self,
+            config: &NoiseConfig,
+            inputs: &[usize],
+            perlin_table: &mut dyn FnMut(u32) -> usize) -> Option<ShaderOp> {
+        self.0.shader_op(config, inputs, perlin_table)
+    }
+}
+
+impl PartialEq for NoiseType {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for NoiseType {}
+
+impl fmt::Debug for NoiseType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+impl Serialize for NoiseType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+impl<'de> Deserialize<'de> for NoiseType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        NoiseType::from_id(&id).ok_or_else(|| de::Error::custom(format!("Unknown node type {id:?}")))
+    }
+}
+
+#[cfg(feature = "gui")]
+impl NoiseType {
+    pub fn show_input(&self, config: &NoiseConfig, input_index: usize, ui: &mut egui::Ui, _scale: f32) {
+        ui.label(self.0.input_name(config, input_index));
     }
 
     pub fn show_header(&self, config: &mut NoiseConfig, ui: &mut egui::Ui, scale: f32) -> HeaderResponse {
         ui.set_height(16.0 * scale);
         ui.set_min_width(128.0 * scale);
+        let title = match config {
+            NoiseConfig::Group(group) => group.name.as_str(),
+            _ => self.name(),
+        };
         ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
-            ui.add(egui::Label::new(self.name()).selectable(false));
+            ui.add(egui::Label::new(title).selectable(false));
         });
         ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
             if ui.button(" x ").clicked() {
@@ -136,30 +445,111 @@ impl NoiseType {
         }).inner
     }
 
-    pub fn show_body(&self, config: &mut NoiseConfig, ui: &mut egui::Ui, scale: f32) -> bool {
-        use NoiseConfig::*;
-        match config {
-            Empty => false,
-            Perlin { seed } => ui.add(egui::Slider::new(seed, 0 ..= std::u32::MAX)).changed(),
-            Constant { value } => ui.add(egui::Slider::new(value, 0.0 ..= 1.0)).changed(),
+    pub fn show_body(&self, config: &mut NoiseConfig, context: &BodyContext<'_>, ui: &mut egui::Ui, scale: f32) -> bool {
+        let changed = self.0.show_body(config, context, ui, scale);
+        if self.classification() == NoiseClassification::Raster {
+            ui.weak("Fixed area ℹ").on_hover_text(crate::raster::AREA_NOTE);
         }
+        changed
     }
 }
 
-
-#[derive(Serialize, Deserialize)]
+/// The editable parameters of a node. Node types without parameters use [`NoiseConfig::Empty`].
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SavedConfig")]
 pub enum NoiseConfig {
     Empty,
-    Perlin {
-        seed: u32
-    },
-    Constant {
-        value: f64
+    /// Values of the parameters described by [`NodeKind::params`].
+    Params(ParamValues),
+    Group(Box<Group>),
+    /// Parameters of kinds registered outside this crate that don't fit [`ParamSpec`]s, in
+    /// whatever form the kind chooses.
+    Custom(serde_json::Value),
+}
+
+impl NoiseConfig {
+    /// The parameter values, which are all defaults for configs without any.
+    pub fn values(&self) -> &ParamValues {
+        static EMPTY: ParamValues = ParamValues::EMPTY;
+        match self {
+            NoiseConfig::Params(values) => values,
+            _ => &EMPTY,
+        }
+    }
+
+    /// The parameter values, replacing the config with them if it has none.
+    pub fn values_mut(&mut self) -> &mut ParamValues {
+        if !matches!(self, NoiseConfig::Params(_)) {
+            *self = NoiseConfig::Params(ParamValues::default());
+        }
+        let NoiseConfig::Params(values) = self else { unreachable!() };
+        values
+    }
+}
+
+/// Configs as saved, including the per-kind variants used before parameters were described by
+/// [`ParamSpec`]s.
+#[derive(Deserialize)]
+enum SavedConfig {
+    Empty,
+    Params(ParamValues),
+    Group(Box<Group>),
+    Custom(serde_json::Value),
+    Perlin { seed: u32 },
+    Constant { value: f64 },
+}
+
+impl From<SavedConfig> for NoiseConfig {
+    fn from(saved: SavedConfig) -> Self {
+        let param = |name: &str, value| {
+            let mut values = ParamValues::default();
+            values.set(name, value);
+            NoiseConfig::Params(values)
+        };
+        match saved {
+            SavedConfig::Empty => NoiseConfig::Empty,
+            SavedConfig::Params(values) => NoiseConfig::Params(values),
+            SavedConfig::Group(group) => NoiseConfig::Group(group),
+            SavedConfig::Custom(value) => NoiseConfig::Custom(value),
+            SavedConfig::Perlin { seed } => param("seed", ParamValue::Int(seed.into())),
+            SavedConfig::Constant { value } => param("value", ParamValue::Float(value)),
+        }
     }
 }
 
+#[cfg(feature = "gui")]
 pub enum HeaderResponse {
     Remove,
     Changed,
     None
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::NoiseFn;
+
+    use super::*;
+
+    /// Reads a node as the first versions of the app saved it, with its type and config.
+    fn saved(noise_type: &str, config: &str) -> DynNoise<2> {
+        let noise_type: NoiseType = serde_json::from_str(noise_type).unwrap();
+        let config: NoiseConfig = serde_json::from_str(config).unwrap();
+        noise_type.build(&config, Vec::new())
+    }
+
+    fn assert_same(saved: DynNoise<2>, expected: impl NoiseFn<f64, 2>) {
+        for point in [[0.0, 0.0], [0.3, -1.7], [12.5, 4.25], [-3.1, 8.9]] {
+            assert_eq!(saved.get(point), expected.get(point), "at {point:?}");
+        }
+    }
+
+    #[test]
+    fn configs_saved_before_params_build_the_same_noise() {
+        assert_same(saved(r#""Perlin""#, r#"{"Perlin":{"seed":12345}}"#), noise::Perlin::new(12345));
+        assert_same(saved(r#""Constant""#, r#"{"Constant":{"value":0.5}}"#), noise::Constant::new(0.5));
+        assert_same(
+            saved(r#""Checkerboard""#, r#""Empty""#),
+            NoiseType::CHECKERBOARD.build::<2>(&NoiseType::CHECKERBOARD.config(), Vec::new()),
+        );
+    }
+}
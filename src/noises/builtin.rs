@@ -0,0 +1,654 @@
+//! The node kinds that come with the editor.
+
+use noise::NoiseFn;
+
+use crate::{
+    build_in_every_dimension,
+    codegen::{rust::f64_literal, shader::ShaderOp},
+    params::{CurveShape, ParamKind, ParamSpec},
+};
+
+use super::{DynNoise, NodeKind, NoiseClassification, NoiseConfig, PinType};
+
+/// Names the inputs of combinators, which take their inputs in this order.
+fn combinator_input_name(index: usize) -> String {
+    ["A", "B", "Control"].get(index).expect("Unexpected input pin index").to_string()
+}
+
+pub struct Checkerboard;
+
+impl NodeKind for Checkerboard {
+    fn id(&self) -> &'static str {
+        "Checkerboard"
+    }
+
+    fn name(&self) -> &'static str {
+        "Checkerboard"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Source
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    fn rust_constructor(&self, _config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        Some(("Checkerboard::default".to_string(), vec![]))
+    }
+
+    fn shader_op(&self, _config: &NoiseConfig, _inputs: &[usize], _perlin_table: &mut dyn FnMut(u32) -> usize)
+        -> Option<ShaderOp> {
+        Some(ShaderOp::Checkerboard)
+    }
+}
+
+impl Checkerboard {
+    fn build<const DIM: usize>(_config: &NoiseConfig, _inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM>
+    where
+        noise::Checkerboard: NoiseFn<f64, DIM>,
+    {
+        DynNoise::new(noise::Checkerboard::default())
+    }
+}
+
+pub struct Perlin;
+
+impl NodeKind for Perlin {
+    fn id(&self) -> &'static str {
+        "Perlin"
+    }
+
+    fn name(&self) -> &'static str {
+        "Perlin"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Source
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[Self::SEED]
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    fn rust_constructor(&self, config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        Some(("Perlin::new".to_string(), vec![Self::SEED.seed(config.values()).to_string()]))
+    }
+
+    fn shader_op(&self, config: &NoiseConfig, _inputs: &[usize], perlin_table: &mut dyn FnMut(u32) -> usize)
+        -> Option<ShaderOp> {
+        Some(ShaderOp::Perlin { table: perlin_table(Self::SEED.seed(config.values())) })
+    }
+}
+
+impl Perlin {
+    pub const SEED: ParamSpec = ParamSpec { name: "seed", label: "Seed", kind: ParamKind::Seed { default: 12345 } };
+
+    fn build<const DIM: usize>(config: &NoiseConfig, _inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM>
+    where
+        noise::Perlin: NoiseFn<f64, DIM>,
+    {
+        DynNoise::new(noise::Perlin::new(Self::SEED.seed(config.values())))
+    }
+}
+
+pub struct Constant;
+
+impl NodeKind for Constant {
+    fn id(&self) -> &'static str {
+        "Constant"
+    }
+
+    fn name(&self) -> &'static str {
+        "Constant"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Source
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[Self::VALUE]
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    fn rust_constructor(&self, config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        Some(("Constant::new".to_string(), vec![f64_literal(Self::VALUE.float(config.values()))]))
+    }
+
+    fn shader_op(&self, config: &NoiseConfig, _inputs: &[usize], _perlin_table: &mut dyn FnMut(u32) -> usize)
+        -> Option<ShaderOp> {
+        Some(ShaderOp::Constant(Self::VALUE.float(config.values()) as f32))
+    }
+}
+
+impl Constant {
+    pub const VALUE: ParamSpec = ParamSpec {
+        name: "value",
+        label: "Value",
+        kind: ParamKind::Float { default: 0.5, min: 0.0, max: 1.0, logarithmic: false },
+    };
+
+    fn build<const DIM: usize>(config: &NoiseConfig, _inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        DynNoise::new(noise::Constant::new(Self::VALUE.float(config.values())))
+    }
+}
+
+pub struct Blend;
+
+impl NodeKind for Blend {
+    fn id(&self) -> &'static str {
+        "Blend"
+    }
+
+    fn name(&self) -> &'static str {
+        "Blend"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Combinator
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        3
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, index: usize) -> String {
+        combinator_input_name(index)
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    fn rust_constructor(&self, _config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        Some(("Blend::new".to_string(), vec![]))
+    }
+
+    fn shader_op(&self, _config: &NoiseConfig, inputs: &[usize], _perlin_table: &mut dyn FnMut(u32) -> usize)
+        -> Option<ShaderOp> {
+        Some(ShaderOp::Blend { a: inputs[0], b: inputs[1], control: inputs[2] })
+    }
+}
+
+impl Blend {
+    fn build<const DIM: usize>(_config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [a, b, control] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        DynNoise::new(noise::Blend::new(a, b, control))
+    }
+}
+
+pub struct Max;
+
+impl NodeKind for Max {
+    fn id(&self) -> &'static str {
+        "Max"
+    }
+
+    fn name(&self) -> &'static str {
+        "Maximum"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Combinator
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        2
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, index: usize) -> String {
+        combinator_input_name(index)
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    fn rust_constructor(&self, _config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        Some(("Max::new".to_string(), vec![]))
+    }
+
+    fn shader_op(&self, _config: &NoiseConfig, inputs: &[usize], _perlin_table: &mut dyn FnMut(u32) -> usize)
+        -> Option<ShaderOp> {
+        Some(ShaderOp::Max(inputs[0], inputs[1]))
+    }
+}
+
+impl Max {
+    fn build<const DIM: usize>(_config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [a, b] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        DynNoise::new(noise::Max::new(a, b))
+    }
+}
+
+pub struct Min;
+
+impl NodeKind for Min {
+    fn id(&self) -> &'static str {
+        "Min"
+    }
+
+    fn name(&self) -> &'static str {
+        "Minimum"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Combinator
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        2
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, index: usize) -> String {
+        combinator_input_name(index)
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    fn rust_constructor(&self, _config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        Some(("Min::new".to_string(), vec![]))
+    }
+
+    fn shader_op(&self, _config: &NoiseConfig, inputs: &[usize], _perlin_table: &mut dyn FnMut(u32) -> usize)
+        -> Option<ShaderOp> {
+        Some(ShaderOp::Min(inputs[0], inputs[1]))
+    }
+}
+
+impl Min {
+    fn build<const DIM: usize>(_config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [a, b] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        DynNoise::new(noise::Min::new(a, b))
+    }
+}
+
+/// Remaps its input through a spline, like the `noise` crate's `Curve`.
+pub struct Curve;
+
+impl NodeKind for Curve {
+    fn id(&self) -> &'static str {
+        "Curve"
+    }
+
+    fn name(&self) -> &'static str {
+        "Curve"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Modifier
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[Self::POINTS]
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        1
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, _index: usize) -> String {
+        "Source".to_string()
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    fn rust_constructor(&self, _config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        Some(("Curve::new".to_string(), vec![]))
+    }
+
+    fn rust_methods(&self, config: &NoiseConfig) -> Vec<String> {
+        Self::POINTS
+            .points(config.values())
+            .into_iter()
+            .map(|[x, y]| format!("add_control_point({}, {})", f64_literal(x), f64_literal(y)))
+            .collect()
+    }
+}
+
+impl Curve {
+    pub const POINTS: ParamSpec = ParamSpec {
+        name: "points",
+        label: "Curve",
+        kind: ParamKind::Curve {
+            default: &[[-1.0, -1.0], [-0.5, -0.75], [0.5, 0.75], [1.0, 1.0]],
+            shape: CurveShape::Spline,
+        },
+    };
+
+    /// Curves with too few distinct points pass their input through, as `noise::Curve` would panic.
+    fn build<const DIM: usize>(config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [source] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        let Some(points) = CurveShape::Spline.usable_points(&Self::POINTS.points(config.values())) else {
+            return source;
+        };
+        let curve = points.into_iter().fold(noise::Curve::new(source), |curve, [x, y]| curve.add_control_point(x, y));
+        DynNoise::new(curve)
+    }
+}
+
+/// Flattens its input into terraces between control values, like the `noise` crate's `Terrace`.
+pub struct Terrace;
+
+impl NodeKind for Terrace {
+    fn id(&self) -> &'static str {
+        "Terrace"
+    }
+
+    fn name(&self) -> &'static str {
+        "Terrace"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Modifier
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[Self::POINTS, Self::INVERT]
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        1
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, _index: usize) -> String {
+        "Source".to_string()
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    fn rust_constructor(&self, _config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        Some(("Terrace::new".to_string(), vec![]))
+    }
+
+    fn rust_methods(&self, config: &NoiseConfig) -> Vec<String> {
+        let values = config.values();
+        let mut methods: Vec<String> = Self::POINTS
+            .points(values)
+            .into_iter()
+            .map(|[x, _]| format!("add_control_point({})", f64_literal(x)))
+            .collect();
+        if Self::INVERT.bool(values) {
+            methods.push("invert_terraces(true)".to_string());
+        }
+        methods
+    }
+
+    /// Draws the curve with the terraces inverted or not.
+    #[cfg(feature = "gui")]
+    fn show_body(&self, config: &mut NoiseConfig, context: &crate::params::BodyContext<'_>, ui: &mut egui::Ui, scale: f32) -> bool {
+        let values = config.values_mut();
+        let invert = Self::INVERT.bool(values);
+        let mut points = Self::POINTS.points(values);
+        let evaluate = |points: &[[f64; 2]], input: f64| match CurveShape::Terraces.usable_points(points) {
+            Some(points) => Self::terrace(noise::Constant::new(input), &points, invert).get([0.0, 0.0]),
+            None => input,
+        };
+        let changed = crate::params::show_curve(
+            &mut points, CurveShape::Terraces, context.input_histogram, &evaluate, ui, scale);
+        if changed {
+            values.set(Self::POINTS.name, crate::params::ParamValue::Points(points));
+        }
+        changed | crate::params::show(&[Self::INVERT], values, context, ui, scale)
+    }
+}
+
+impl Terrace {
+    /// Only the inputs of the points are used, as terraces are flat.
+    pub const POINTS: ParamSpec = ParamSpec {
+        name: "points",
+        label: "Terraces",
+        kind: ParamKind::Curve {
+            default: &[[-1.0, -1.0], [-0.25, -0.25], [0.5, 0.5], [1.0, 1.0]],
+            shape: CurveShape::Terraces,
+        },
+    };
+    /// Curves the terraces' slopes the other way, so they rise steeply out of each flat.
+    pub const INVERT: ParamSpec = ParamSpec { name: "invert", label: "Invert", kind: ParamKind::Bool { default: false } };
+
+    /// Terraces with too few distinct points pass their input through, as `noise::Terrace` would panic.
+    fn build<const DIM: usize>(config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [source] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        let values = config.values();
+        let Some(points) = CurveShape::Terraces.usable_points(&Self::POINTS.points(values)) else {
+            return source;
+        };
+        DynNoise::new(Self::terrace(source, &points, Self::INVERT.bool(values)))
+    }
+
+    fn terrace<Source, const DIM: usize>(source: Source, points: &[[f64; 2]], invert: bool) -> noise::Terrace<f64, Source, DIM>
+    where
+        Source: NoiseFn<f64, DIM>,
+    {
+        points
+            .iter()
+            .fold(noise::Terrace::new(source), |terrace, &[x, _]| terrace.add_control_point(x))
+            .invert_terraces(invert)
+    }
+}
+
+/// A named number that can drive parameter pins on any number of nodes, with a slider over a
+/// range of its own.
+pub struct Parameter;
+
+impl NodeKind for Parameter {
+    fn id(&self) -> &'static str {
+        "Parameter"
+    }
+
+    fn name(&self) -> &'static str {
+        "Parameter"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Scalar
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[Self::VALUE, Self::MIN, Self::MAX]
+    }
+
+    fn output_type(&self, _config: &NoiseConfig, _index: usize) -> PinType {
+        PinType::Scalar
+    }
+
+    fn scalar(&self, config: &NoiseConfig) -> Option<f64> {
+        Some(Self::VALUE.float(config.values()))
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    fn rust_constructor(&self, config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        Some(("Constant::new".to_string(), vec![f64_literal(Self::VALUE.float(config.values()))]))
+    }
+
+    fn shader_op(&self, config: &NoiseConfig, _inputs: &[usize], _perlin_table: &mut dyn FnMut(u32) -> usize)
+        -> Option<ShaderOp> {
+        Some(ShaderOp::Constant(Self::VALUE.float(config.values()) as f32))
+    }
+
+    #[cfg(feature = "gui")]
+    fn show_body(&self, config: &mut NoiseConfig, context: &crate::params::BodyContext<'_>, ui: &mut egui::Ui, _scale: f32) -> bool {
+        let values = config.values_mut();
+        let [mut value, mut min, mut max] = [Self::VALUE, Self::MIN, Self::MAX].map(|spec| spec.float(values));
+        let is_driven = |spec: &ParamSpec| context.is_driven(spec);
+        let mut changed = ui
+            .add_enabled(
+                !is_driven(&Self::VALUE),
+                egui::Slider::new(&mut value, min..=max).clamping(egui::SliderClamping::Never))
+            .changed();
+        ui.horizontal(|ui| {
+            ui.label("Range:");
+            changed |= ui.add_enabled(!is_driven(&Self::MIN), egui::DragValue::new(&mut min).speed(0.1)).changed();
+            changed |= ui.add_enabled(!is_driven(&Self::MAX), egui::DragValue::new(&mut max).speed(0.1)).changed();
+        });
+        if changed {
+            for (spec, number) in [(Self::VALUE, value), (Self::MIN, min), (Self::MAX, max)] {
+                values.set(spec.name, crate::params::ParamValue::Float(number));
+            }
+        }
+        changed
+    }
+}
+
+impl Parameter {
+    pub const VALUE: ParamSpec = ParamSpec {
+        name: "value",
+        label: "Value",
+        kind: ParamKind::Float { default: 1.0, min: 0.0, max: 10.0, logarithmic: false },
+    };
+    /// Only sets the slider's range. Values outside it can be typed in.
+    pub const MIN: ParamSpec = ParamSpec {
+        name: "min",
+        label: "Minimum",
+        kind: ParamKind::Float { default: 0.0, min: -10.0, max: 10.0, logarithmic: false },
+    };
+    pub const MAX: ParamSpec = ParamSpec {
+        name: "max",
+        label: "Maximum",
+        kind: ParamKind::Float { default: 10.0, min: -10.0, max: 10.0, logarithmic: false },
+    };
+
+    fn build<const DIM: usize>(config: &NoiseConfig, _inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        DynNoise::new(noise::Constant::new(Self::VALUE.float(config.values())))
+    }
+}
+
+/// A fixed number for driving parameter pins.
+pub struct ConstantScalar;
+
+impl NodeKind for ConstantScalar {
+    fn id(&self) -> &'static str {
+        "ConstantScalar"
+    }
+
+    fn name(&self) -> &'static str {
+        "Constant scalar"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Scalar
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[Self::VALUE]
+    }
+
+    fn output_type(&self, _config: &NoiseConfig, _index: usize) -> PinType {
+        PinType::Scalar
+    }
+
+    fn scalar(&self, config: &NoiseConfig) -> Option<f64> {
+        Some(Self::VALUE.float(config.values()))
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    fn rust_constructor(&self, config: &NoiseConfig) -> Option<(String, Vec<String>)> {
+        Some(("Constant::new".to_string(), vec![f64_literal(Self::VALUE.float(config.values()))]))
+    }
+
+    fn shader_op(&self, config: &NoiseConfig, _inputs: &[usize], _perlin_table: &mut dyn FnMut(u32) -> usize)
+        -> Option<ShaderOp> {
+        Some(ShaderOp::Constant(Self::VALUE.float(config.values()) as f32))
+    }
+}
+
+impl ConstantScalar {
+    pub const VALUE: ParamSpec = ParamSpec {
+        name: "value",
+        label: "Value",
+        kind: ParamKind::Float { default: 1.0, min: -10.0, max: 10.0, logarithmic: false },
+    };
+
+    fn build<const DIM: usize>(config: &NoiseConfig, _inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        DynNoise::new(noise::Constant::new(Self::VALUE.float(config.values())))
+    }
+}
+
+/// A nested graph, see [`crate::group`]. Groups are flattened before graphs are built or
+/// translated to code, so this kind only describes the group's pins and exposed parameters.
+/// Group nodes with any other config act as empty groups, without pins.
+pub struct GroupKind;
+
+impl NodeKind for GroupKind {
+    fn id(&self) -> &'static str {
+        "Group"
+    }
+
+    fn name(&self) -> &'static str {
+        "Group"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Group
+    }
+
+    fn config(&self) -> NoiseConfig {
+        NoiseConfig::Group(Box::default())
+    }
+
+    fn input_count(&self, config: &NoiseConfig) -> usize {
+        match config {
+            NoiseConfig::Group(group) => group.inputs.len(),
+            _ => 0,
+        }
+    }
+
+    fn output_count(&self, config: &NoiseConfig) -> usize {
+        match config {
+            NoiseConfig::Group(group) => group.outputs.len(),
+            _ => 0,
+        }
+    }
+
+    fn input_name(&self, config: &NoiseConfig, index: usize) -> String {
+        match config {
+            NoiseConfig::Group(group) => group.inputs[index].name.clone(),
+            _ => format!("Input {}", index + 1),
+        }
+    }
+
+    /// The type of the first pin the input feeds. Collapsing a group only feeds pins of one type
+    /// from each input.
+    fn input_type(&self, config: &NoiseConfig, index: usize) -> PinType {
+        let NoiseConfig::Group(group) = config else { return PinType::Noise };
+        group.inputs
+            .get(index)
+            .and_then(|input| input.targets.first())
+            .and_then(|target| Some(group.graph.nodes().get(target.node)?.input_type(target.input)))
+            .unwrap_or(PinType::Noise)
+    }
+
+    fn output_type(&self, config: &NoiseConfig, index: usize) -> PinType {
+        let NoiseConfig::Group(group) = config else { return PinType::Noise };
+        group.outputs
+            .get(index)
+            .and_then(|output| Some(group.graph.nodes().get(output.node)?.output_type(output.output)))
+            .unwrap_or(PinType::Noise)
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    #[cfg(feature = "gui")]
+    fn show_body(&self, config: &mut NoiseConfig, _context: &crate::params::BodyContext<'_>, ui: &mut egui::Ui, scale: f32) -> bool {
+        let NoiseConfig::Group(group) = config else { return false };
+        // Exposed parameters are edited in place on the inner nodes.
+        let crate::group::Group { graph, exposed, .. } = &mut **group;
+        let mut changed = false;
+        for &index in exposed.iter() {
+            let Some(node) = graph.nodes_mut().get_mut(index) else { continue };
+            ui.label(&node.name);
+            ui.push_id(index, |ui| {
+                let context = crate::params::BodyContext { driven: &[], input_histogram: None };
+                changed |= node.noise_type.show_body(&mut node.config, &context, ui, scale);
+            });
+        }
+        changed
+    }
+}
+
+impl GroupKind {
+    fn build<const DIM: usize>(_config: &NoiseConfig, _inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        panic!("Groups are flattened before building")
+    }
+}
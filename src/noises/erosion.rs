@@ -0,0 +1,274 @@
+//! The erosion node, which wears a heightfield down with simulated rain and crumbling slopes so
+//! that it looks more like real terrain. Heights are in the same units as the eroded square, so
+//! larger squares have gentler slopes.
+
+use crate::{
+    build_in_every_dimension,
+    params::{ParamKind, ParamSpec, ParamValues},
+    random::Rng,
+    raster::{self, Grid, Raster, RasterArea},
+};
+
+use super::{DynNoise, NodeKind, NoiseClassification, NoiseConfig};
+
+/// The most iterations, matching the slider's range.
+const MAX_ITERATIONS: i64 = 100;
+
+pub struct Erosion;
+
+impl NodeKind for Erosion {
+    fn id(&self) -> &'static str {
+        "Erosion"
+    }
+
+    fn name(&self) -> &'static str {
+        "Erosion"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Raster
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[
+            raster::RESOLUTION,
+            raster::SIZE,
+            Self::SEED,
+            Self::ITERATIONS,
+            Self::RAIN,
+            Self::EVAPORATION,
+            Self::CAPACITY,
+            Self::TALUS,
+        ]
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        1
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, _index: usize) -> String {
+        "Heights".to_string()
+    }
+
+    build_in_every_dimension!(Self::build);
+}
+
+impl Erosion {
+    pub const SEED: ParamSpec = ParamSpec { name: "seed", label: "Seed", kind: ParamKind::Seed { default: 1 } };
+    /// Each iteration rains on the terrain and then lets its steep slopes crumble.
+    pub const ITERATIONS: ParamSpec = ParamSpec {
+        name: "iterations",
+        label: "Iterations",
+        kind: ParamKind::Int { default: 10, min: 0, max: MAX_ITERATIONS },
+    };
+    /// Droplets per cell in each iteration.
+    pub const RAIN: ParamSpec = ParamSpec {
+        name: "rain",
+        label: "Rain",
+        kind: ParamKind::Float { default: 0.1, min: 0.0, max: 1.0, logarithmic: false },
+    };
+    /// The fraction of a droplet's water lost at each step. Less evaporation carves longer
+    /// channels.
+    pub const EVAPORATION: ParamSpec = ParamSpec {
+        name: "evaporation",
+        label: "Evaporation",
+        kind: ParamKind::Float { default: 0.02, min: 0.001, max: 0.5, logarithmic: true },
+    };
+    /// How much sediment fast, heavy droplets can carry, relative to the slope they flow down.
+    pub const CAPACITY: ParamSpec = ParamSpec {
+        name: "capacity",
+        label: "Sediment capacity",
+        kind: ParamKind::Float { default: 4.0, min: 0.1, max: 32.0, logarithmic: true },
+    };
+    /// The steepest stable slope in degrees. Steeper slopes crumble onto their lower neighbours.
+    pub const TALUS: ParamSpec = ParamSpec {
+        name: "talus",
+        label: "Talus angle",
+        kind: ParamKind::Float { default: 40.0, min: 0.0, max: 90.0, logarithmic: false },
+    };
+
+    fn build<const DIM: usize>(config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [heights] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        let settings = ErosionSettings::new(config.values());
+        let resolution = settings.area.resolution;
+        DynNoise::new(Raster::new(heights, settings.area, move |mut grid| {
+            let mut rng = Rng::new(settings.seed.into());
+            for _ in 0..settings.iterations {
+                let droplets = (settings.rain * (resolution * resolution) as f64).round() as usize;
+                for _ in 0..droplets {
+                    settings.droplet(&mut grid, &mut rng);
+                }
+                settings.crumble(&mut grid);
+            }
+            grid
+        }))
+    }
+}
+
+/// How much of its direction a droplet keeps at each step rather than turning downhill.
+const INERTIA: f64 = 0.05;
+const EROSION_RATE: f64 = 0.3;
+const DEPOSITION_RATE: f64 = 0.3;
+const GRAVITY: f64 = 4.0;
+/// Droplets that haven't evaporated yet stop after this many steps.
+const MAX_STEPS: usize = 64;
+/// Sediment even a still droplet can carry, so that droplets on flat ground keep eroding.
+const MIN_CAPACITY: f64 = 0.01;
+/// The fraction of a cell's largest excess over the talus angle that crumbles in each iteration.
+/// Moving half of it would level the two cells, so this stays below that to avoid overshooting.
+const CRUMBLING: f64 = 0.25;
+
+#[derive(Clone, Copy)]
+struct ErosionSettings {
+    area: RasterArea,
+    seed: u32,
+    iterations: i64,
+    rain: f64,
+    evaporation: f64,
+    capacity: f64,
+    /// The steepest stable height difference between neighbouring cells.
+    talus: f64,
+}
+
+impl ErosionSettings {
+    fn new(values: &ParamValues) -> Self {
+        // Some padding lets droplets flow in from past the square's edges.
+        let area = RasterArea::from_params(values, raster::SIZE.float(values) / 16.0);
+        Self {
+            area,
+            seed: Erosion::SEED.seed(values),
+            // Typed or driven values past the sliders' ranges would keep the recalculator busy
+            // for as long as they like.
+            iterations: Erosion::ITERATIONS.int(values).clamp(0, MAX_ITERATIONS),
+            rain: Erosion::RAIN.float(values).clamp(0.0, 1.0),
+            evaporation: Erosion::EVAPORATION.float(values).clamp(0.0, 1.0),
+            capacity: Erosion::CAPACITY.float(values),
+            talus: Erosion::TALUS.float(values).clamp(0.0, 90.0).to_radians().tan() * area.cell_size(),
+        }
+    }
+
+    /// Drops a droplet on a random cell and follows it downhill, eroding where it speeds up and
+    /// depositing where it slows down or carries more than it can.
+    fn droplet(&self, grid: &mut Grid, rng: &mut Rng) {
+        let (width, height) = (grid.width() as f64, grid.height() as f64);
+        let mut position = [rng.next_f64() * (width - 1.0), rng.next_f64() * (height - 1.0)];
+        let mut direction = [0.0, 0.0];
+        let (mut speed, mut water, mut sediment) = (1.0, 1.0, 0.0);
+        for _ in 0..MAX_STEPS {
+            let (old_height, gradient) = height_and_gradient(grid, position);
+            direction = [0, 1].map(|axis| direction[axis] * INERTIA - gradient[axis] * (1.0 - INERTIA));
+            let length = direction[0].hypot(direction[1]);
+            if length == 0.0 {
+                break;
+            }
+            direction = direction.map(|component| component / length);
+            let old_position = position;
+            position = [position[0] + direction[0], position[1] + direction[1]];
+            if position[0] < 0.0 || position[1] < 0.0 || position[0] >= width - 1.0 || position[1] >= height - 1.0 {
+                break;
+            }
+
+            let height_change = height_and_gradient(grid, position).0 - old_height;
+            let capacity = (-height_change * speed * water * self.capacity).max(MIN_CAPACITY * self.area.cell_size());
+            if sediment > capacity || height_change > 0.0 {
+                // Uphill, fill the pit behind the droplet up to its new height at most.
+                let amount = if height_change > 0.0 {
+                    sediment.min(height_change)
+                } else {
+                    (sediment - capacity) * DEPOSITION_RATE
+                };
+                sediment -= amount;
+                spread(grid, old_position, amount);
+            } else {
+                // Never dig below the droplet's new height.
+                let amount = ((capacity - sediment) * EROSION_RATE).min(-height_change);
+                sediment += amount;
+                spread(grid, old_position, -amount);
+            }
+            speed = (speed * speed - height_change / self.area.cell_size() * GRAVITY).max(0.0).sqrt();
+            water *= 1.0 - self.evaporation;
+        }
+    }
+
+    /// Moves material from each cell down to its neighbours wherever the slope between them is
+    /// steeper than the talus angle.
+    fn crumble(&self, grid: &mut Grid) {
+        const NEIGHBOURS: [(isize, isize); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+        let mut changes = Grid::new(grid.width(), grid.height(), 0.0);
+        for y in 0..grid.height() {
+            for x in 0..grid.width() {
+                let here = grid.get(x as isize, y as isize);
+                let excesses = NEIGHBOURS.map(|(dx, dy)| {
+                    let (nx, ny) = (x as isize + dx, y as isize + dy);
+                    let inside = nx >= 0 && ny >= 0 && (nx as usize) < grid.width() && (ny as usize) < grid.height();
+                    let distance = ((dx * dx + dy * dy) as f64).sqrt();
+                    if inside { (here - grid.get(nx, ny) - self.talus * distance).max(0.0) } else { 0.0 }
+                });
+                let total: f64 = excesses.iter().sum();
+                let largest = excesses.iter().copied().fold(0.0, f64::max);
+                if total == 0.0 {
+                    continue;
+                }
+                let moved = largest * CRUMBLING;
+                let index = changes.index(x, y);
+                changes.values_mut()[index] -= moved;
+                for ((dx, dy), excess) in NEIGHBOURS.into_iter().zip(excesses) {
+                    if excess > 0.0 {
+                        let index = changes.index((x as isize + dx) as usize, (y as isize + dy) as usize);
+                        changes.values_mut()[index] += moved * excess / total;
+                    }
+                }
+            }
+        }
+        for (value, change) in grid.values_mut().iter_mut().zip(changes.values()) {
+            *value += change;
+        }
+    }
+}
+
+/// The height at a position inside the grid, interpolated from the four cells around it, and the
+/// slope there in height per cell.
+fn height_and_gradient(grid: &Grid, [x, y]: [f64; 2]) -> (f64, [f64; 2]) {
+    let (cx, cy) = (x.floor() as isize, y.floor() as isize);
+    let (tx, ty) = (x - cx as f64, y - cy as f64);
+    let [nw, ne, sw, se] = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| grid.get(cx + dx, cy + dy));
+    let gradient = [(ne - nw) * (1.0 - ty) + (se - sw) * ty, (sw - nw) * (1.0 - tx) + (se - ne) * tx];
+    (grid.bilinear(x, y), gradient)
+}
+
+/// Adds an amount to the four cells around a position inside the grid, weighted by how close
+/// each is.
+fn spread(grid: &mut Grid, [x, y]: [f64; 2], amount: f64) {
+    let (cx, cy) = (x.floor() as usize, y.floor() as usize);
+    let (tx, ty) = (x - cx as f64, y - cy as f64);
+    for (dx, dy, weight) in [(0, 0, (1.0 - tx) * (1.0 - ty)), (1, 0, tx * (1.0 - ty)), (0, 1, (1.0 - tx) * ty), (1, 1, tx * ty)] {
+        let index = grid.index(cx + dx, cy + dy);
+        grid.values_mut()[index] += amount * weight;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::NoiseFn;
+
+    use crate::{noises::NoiseType, params::ParamValue};
+
+    use super::*;
+
+    #[test]
+    fn settings_past_the_sliders_are_limited() {
+        let mut config = NoiseType::EROSION.config();
+        let values = config.values_mut();
+        values.set(Erosion::ITERATIONS.name, ParamValue::Int(1_000_000));
+        values.set(Erosion::RAIN.name, ParamValue::Float(50.0));
+        values.set(raster::RESOLUTION.name, ParamValue::Int(16));
+        let settings = ErosionSettings::new(config.values());
+        assert_eq!(settings.iterations, MAX_ITERATIONS);
+        assert_eq!(settings.rain, 1.0);
+
+        // Finishes in moments rather than running for hours.
+        let heights = DynNoise::new(noise::Perlin::new(1));
+        let eroded = NoiseType::EROSION.build::<2>(&config, vec![heights]);
+        assert!(eroded.get([0.5, 0.5]).is_finite());
+    }
+}
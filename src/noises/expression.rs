@@ -0,0 +1,526 @@
+//! The expression node, which computes a formula over its inputs and the coordinates, such as
+//! `abs(a) * 0.5 + sin(x * 4) * b`.
+//!
+//! Names other than the coordinates `x`, `y`, `z` and `w` and the constants `pi` and `tau` become
+//! input pins, in alphabetical order. Pins are found by scanning the text rather than parsing it,
+//! so they stay put while a formula is being typed.
+
+use std::fmt;
+
+use noise::NoiseFn;
+
+use crate::{build_in_every_dimension, params::{ParamKind, ParamSpec}};
+
+use super::{DynNoise, NodeKind, NoiseClassification, NoiseConfig};
+
+pub struct ExpressionKind;
+
+impl NodeKind for ExpressionKind {
+    fn id(&self) -> &'static str {
+        "Expression"
+    }
+
+    fn name(&self) -> &'static str {
+        "Expression"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Combinator
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[Self::EXPRESSION]
+    }
+
+    fn input_count(&self, config: &NoiseConfig) -> usize {
+        Expression::input_names(&Self::EXPRESSION.text(config.values())).len()
+    }
+
+    fn input_name(&self, config: &NoiseConfig, index: usize) -> String {
+        Expression::input_names(&Self::EXPRESSION.text(config.values())).swap_remove(index)
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    /// The formula is only applied when the field loses focus, so that wires aren't moved to
+    /// other pins while a name is half typed. Errors are shown as soon as they're typed.
+    #[cfg(feature = "gui")]
+    fn show_body(&self, config: &mut NoiseConfig, _context: &crate::params::BodyContext<'_>, ui: &mut egui::Ui, scale: f32) -> bool {
+        let values = config.values_mut();
+        let text = Self::EXPRESSION.text(values);
+        let id = ui.id().with("expression");
+        let mut edited = ui.data_mut(|data| data.get_temp::<String>(id)).unwrap_or_else(|| text.clone());
+        let response = ui.add(egui::TextEdit::singleline(&mut edited)
+            .code_editor()
+            .desired_width(256.0 * scale)
+            .hint_text("Formula over x, y, z, w and inputs"));
+        if let Err(err) = Expression::parse(&edited) {
+            ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+        }
+        if response.has_focus() {
+            ui.data_mut(|data| data.insert_temp(id, edited));
+            return false;
+        }
+        ui.data_mut(|data| data.remove::<String>(id));
+        if response.lost_focus() && edited != text {
+            values.set(Self::EXPRESSION.name, crate::params::ParamValue::Text(edited));
+            return true;
+        }
+        false
+    }
+}
+
+impl ExpressionKind {
+    pub const EXPRESSION: ParamSpec = ParamSpec {
+        name: "expression",
+        label: "Expression",
+        kind: ParamKind::Text { default: "(a + b) * 0.5" },
+    };
+
+    /// Formulas that don't parse produce 0, like an unconnected input.
+    fn build<const DIM: usize>(config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        match Expression::parse(&Self::EXPRESSION.text(config.values())) {
+            Ok(expression) => DynNoise::new(ExpressionNoise { expression, inputs }),
+            Err(_) => DynNoise::new(noise::Constant::new(0.0)),
+        }
+    }
+}
+
+struct ExpressionNoise<const DIM: usize> {
+    expression: Expression,
+    inputs: Vec<DynNoise<DIM>>,
+}
+
+impl<const DIM: usize> NoiseFn<f64, DIM> for ExpressionNoise<DIM> {
+    fn get(&self, point: [f64; DIM]) -> f64 {
+        let inputs: Vec<f64> = self.inputs.iter().map(|input| input.get(point)).collect();
+        self.expression.evaluate(&point, &inputs)
+    }
+}
+
+const COORDINATES: [&str; 4] = ["x", "y", "z", "w"];
+
+const CONSTANTS: [(&str, f64); 2] = [("pi", std::f64::consts::PI), ("tau", std::f64::consts::TAU)];
+
+struct Function {
+    name: &'static str,
+    arity: usize,
+    apply: fn(&[f64]) -> f64,
+}
+
+const FUNCTIONS: &[Function] = &[
+    Function { name: "abs", arity: 1, apply: |args| args[0].abs() },
+    Function { name: "sign", arity: 1, apply: |args| if args[0] == 0.0 { 0.0 } else { args[0].signum() } },
+    Function { name: "sqrt", arity: 1, apply: |args| args[0].sqrt() },
+    Function { name: "exp", arity: 1, apply: |args| args[0].exp() },
+    Function { name: "ln", arity: 1, apply: |args| args[0].ln() },
+    Function { name: "sin", arity: 1, apply: |args| args[0].sin() },
+    Function { name: "cos", arity: 1, apply: |args| args[0].cos() },
+    Function { name: "tan", arity: 1, apply: |args| args[0].tan() },
+    Function { name: "floor", arity: 1, apply: |args| args[0].floor() },
+    Function { name: "ceil", arity: 1, apply: |args| args[0].ceil() },
+    Function { name: "round", arity: 1, apply: |args| args[0].round() },
+    Function { name: "fract", arity: 1, apply: |args| args[0] - args[0].floor() },
+    Function { name: "min", arity: 2, apply: |args| args[0].min(args[1]) },
+    Function { name: "max", arity: 2, apply: |args| args[0].max(args[1]) },
+    Function { name: "pow", arity: 2, apply: |args| args[0].powf(args[1]) },
+    Function { name: "step", arity: 2, apply: |args| if args[1] < args[0] { 0.0 } else { 1.0 } },
+    Function { name: "clamp", arity: 3, apply: |args| args[0].max(args[1]).min(args[2]) },
+    Function { name: "lerp", arity: 3, apply: |args| args[0] + (args[1] - args[0]) * args[2] },
+    Function {
+        name: "smoothstep",
+        arity: 3,
+        apply: |args| {
+            let t = ((args[2] - args[0]) / (args[1] - args[0])).clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        },
+    },
+];
+
+/// A parsed formula.
+pub struct Expression {
+    root: Term,
+}
+
+enum Term {
+    Number(f64),
+    Coordinate(usize),
+    Input(usize),
+    Negate(Box<Term>),
+    Binary(Operator, Box<Term>, Box<Term>),
+    Call(&'static Function, Vec<Term>),
+}
+
+#[derive(Clone, Copy)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Power,
+}
+
+/// Why a formula couldn't be parsed, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// Offset of the problem in characters.
+    pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let tokens = tokenize(text);
+        let mut parser = Parser { tokens: &tokens, next: 0, inputs: Self::input_names(text), end: text.chars().count() };
+        let root = parser.sum()?;
+        match parser.peek() {
+            None => Ok(Self { root }),
+            Some(_) => Err(parser.error("Expected an operator")),
+        }
+    }
+
+    /// The names of the inputs a formula uses, in alphabetical order.
+    pub fn input_names(text: &str) -> Vec<String> {
+        let tokens = tokenize(text);
+        let mut names: Vec<String> = tokens
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (token, _))| match token {
+                Token::Name(name) if !matches!(tokens.get(index + 1), Some((Token::Symbol('('), _))) => Some(name),
+                _ => None,
+            })
+            .filter(|name| !COORDINATES.contains(&name.as_str()) && !CONSTANTS.iter().any(|(constant, _)| constant == name))
+            .cloned()
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Evaluates the formula at a point, given the values of its inputs. Coordinates past the
+    /// point's dimensions are 0.
+    pub fn evaluate(&self, point: &[f64], inputs: &[f64]) -> f64 {
+        self.root.evaluate(point, inputs)
+    }
+}
+
+impl Term {
+    fn evaluate(&self, point: &[f64], inputs: &[f64]) -> f64 {
+        match self {
+            Term::Number(number) => *number,
+            Term::Coordinate(axis) => point.get(*axis).copied().unwrap_or(0.0),
+            Term::Input(index) => inputs.get(*index).copied().unwrap_or(0.0),
+            Term::Negate(term) => -term.evaluate(point, inputs),
+            Term::Binary(operator, a, b) => {
+                let (a, b) = (a.evaluate(point, inputs), b.evaluate(point, inputs));
+                match operator {
+                    Operator::Add => a + b,
+                    Operator::Subtract => a - b,
+                    Operator::Multiply => a * b,
+                    Operator::Divide => a / b,
+                    Operator::Remainder => a.rem_euclid(b),
+                    Operator::Power => a.powf(b),
+                }
+            }
+            Term::Call(function, args) => {
+                let args: Vec<f64> = args.iter().map(|arg| arg.evaluate(point, inputs)).collect();
+                (function.apply)(&args)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+    /// Text that isn't part of any token, kept so the parser can point it out.
+    Invalid(String),
+}
+
+/// Splits a formula into tokens along with their positions in characters.
+fn tokenize(text: &str) -> Vec<(Token, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while let Some(&c) = chars.get(position) {
+        let start = position;
+        let token = if c.is_whitespace() {
+            position += 1;
+            continue;
+        } else if c.is_ascii_digit() || c == '.' {
+            while chars.get(position).is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+                position += 1;
+            }
+            // An exponent, as in 1e-3.
+            if chars.get(position).is_some_and(|c| *c == 'e' || *c == 'E') {
+                let sign = usize::from(chars.get(position + 1).is_some_and(|c| *c == '+' || *c == '-'));
+                if chars.get(position + 1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                    position += 1 + sign;
+                    while chars.get(position).is_some_and(|c| c.is_ascii_digit()) {
+                        position += 1;
+                    }
+                }
+            }
+            let number: String = chars[start..position].iter().collect();
+            match number.parse() {
+                Ok(number) => Token::Number(number),
+                Err(_) => Token::Invalid(number),
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            while chars.get(position).is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+                position += 1;
+            }
+            Token::Name(chars[start..position].iter().collect())
+        } else {
+            position += 1;
+            if "+-*/%^(),".contains(c) { Token::Symbol(c) } else { Token::Invalid(c.to_string()) }
+        };
+        tokens.push((token, start));
+    }
+    tokens
+}
+
+/// A recursive descent parser, with one method for each level of precedence.
+struct Parser<'a> {
+    tokens: &'a [(Token, usize)],
+    next: usize,
+    inputs: Vec<String>,
+    /// Length of the text, where errors about it ending early are reported.
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        let position = self.tokens.get(self.next).map_or(self.end, |&(_, position)| position);
+        let message = match self.peek() {
+            Some(Token::Invalid(text)) => format!("Unexpected {text:?}"),
+            None if message.starts_with("Expected") => format!("{message}, found the end"),
+            _ => message.to_string(),
+        };
+        ParseError { message, position }
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ParseError> {
+        if self.eat(symbol) { Ok(()) } else { Err(self.error(&format!("Expected '{symbol}'"))) }
+    }
+
+    /// Addition and subtraction.
+    fn sum(&mut self) -> Result<Term, ParseError> {
+        let mut term = self.product()?;
+        loop {
+            let operator = if self.eat('+') {
+                Operator::Add
+            } else if self.eat('-') {
+                Operator::Subtract
+            } else {
+                return Ok(term);
+            };
+            term = Term::Binary(operator, Box::new(term), Box::new(self.product()?));
+        }
+    }
+
+    /// Multiplication, division and remainder.
+    fn product(&mut self) -> Result<Term, ParseError> {
+        let mut term = self.unary()?;
+        loop {
+            let operator = if self.eat('*') {
+                Operator::Multiply
+            } else if self.eat('/') {
+                Operator::Divide
+            } else if self.eat('%') {
+                Operator::Remainder
+            } else {
+                return Ok(term);
+            };
+            term = Term::Binary(operator, Box::new(term), Box::new(self.unary()?));
+        }
+    }
+
+    /// Negation, which binds less tightly than powers, so `-a^2` is `-(a^2)`.
+    fn unary(&mut self) -> Result<Term, ParseError> {
+        if self.eat('-') {
+            return Ok(Term::Negate(Box::new(self.unary()?)));
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    /// Powers, which group from the right.
+    fn power(&mut self) -> Result<Term, ParseError> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            return Ok(Term::Binary(Operator::Power, Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    /// Numbers, names, function calls and parentheses.
+    fn atom(&mut self) -> Result<Term, ParseError> {
+        let Some((token, position)) = self.tokens.get(self.next) else { return Err(self.error("Expected a value")) };
+        let term = match token {
+            Token::Number(number) => Term::Number(*number),
+            Token::Symbol('(') => {
+                self.next += 1;
+                let term = self.sum()?;
+                self.expect(')')?;
+                return Ok(term);
+            }
+            Token::Name(name) if self.tokens.get(self.next + 1).is_some_and(|(token, _)| *token == Token::Symbol('(')) => {
+                let Some(function) = FUNCTIONS.iter().find(|function| function.name == name) else {
+                    return Err(ParseError { message: format!("Unknown function {name:?}"), position: *position });
+                };
+                self.next += 2;
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.sum()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        self.expect(',')?;
+                    }
+                }
+                if args.len() != function.arity {
+                    let plural = if function.arity == 1 { "" } else { "s" };
+                    return Err(ParseError {
+                        message: format!("{name} takes {} argument{plural}, not {}", function.arity, args.len()),
+                        position: *position,
+                    });
+                }
+                return Ok(Term::Call(function, args));
+            }
+            Token::Name(name) => {
+                if let Some(axis) = COORDINATES.iter().position(|coordinate| coordinate == name) {
+                    Term::Coordinate(axis)
+                } else if let Some(&(_, value)) = CONSTANTS.iter().find(|(constant, _)| constant == name) {
+                    Term::Number(value)
+                } else {
+                    let index = self.inputs.iter().position(|input| input == name).expect("Inputs should include every name");
+                    Term::Input(index)
+                }
+            }
+            _ => return Err(self.error("Expected a value")),
+        };
+        self.next += 1;
+        Ok(term)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(text: &str, point: &[f64], inputs: &[f64]) -> f64 {
+        Expression::parse(text).unwrap().evaluate(point, inputs)
+    }
+
+    fn parse_error(text: &str) -> ParseError {
+        Expression::parse(text).err().expect("Formula should not parse")
+    }
+
+    #[test]
+    fn negation_binds_less_tightly_than_powers() {
+        assert_eq!(evaluate("-a^2", &[], &[3.0]), -9.0);
+        assert_eq!(evaluate("(-a)^2", &[], &[3.0]), 9.0);
+        assert_eq!(evaluate("2^-1", &[], &[]), 0.5);
+    }
+
+    #[test]
+    fn powers_group_from_the_right() {
+        assert_eq!(evaluate("2^3^2", &[], &[]), 512.0);
+        assert_eq!(evaluate("(2^3)^2", &[], &[]), 64.0);
+    }
+
+    #[test]
+    fn products_bind_more_tightly_than_sums() {
+        assert_eq!(evaluate("1 + 2 * 3 - 4 / 2", &[], &[]), 5.0);
+        assert_eq!(evaluate("8 - 3 - 2", &[], &[]), 3.0);
+        assert_eq!(evaluate("-7 % 3", &[], &[]), 2.0);
+    }
+
+    #[test]
+    fn exponent_literals() {
+        assert_eq!(evaluate("1e3", &[], &[]), 1000.0);
+        assert_eq!(evaluate("2.5E-2", &[], &[]), 0.025);
+        assert_eq!(evaluate("1e+2", &[], &[]), 100.0);
+        // Without digits after it, the e is a separate name.
+        assert_eq!(Expression::input_names("2e"), ["e"]);
+        assert_eq!(parse_error("2e"), ParseError { message: "Expected an operator".to_string(), position: 1 });
+    }
+
+    #[test]
+    fn wrong_argument_counts_point_at_the_function() {
+        let err = parse_error("1 + min(a)");
+        assert_eq!(err.message, "min takes 2 arguments, not 1");
+        assert_eq!(err.position, 4);
+        assert_eq!(err.to_string(), "min takes 2 arguments, not 1 at column 5");
+        assert_eq!(parse_error("abs()").message, "abs takes 1 argument, not 0");
+    }
+
+    #[test]
+    fn unknown_functions_point_at_their_name() {
+        let err = parse_error("a * foo(b)");
+        assert_eq!(err.message, "Unknown function \"foo\"");
+        assert_eq!(err.position, 4);
+    }
+
+    #[test]
+    fn other_errors_have_positions() {
+        assert_eq!(parse_error("(a + b"), ParseError { message: "Expected ')', found the end".to_string(), position: 6 });
+        assert_eq!(parse_error("a b"), ParseError { message: "Expected an operator".to_string(), position: 2 });
+        assert_eq!(parse_error("a + $").message, "Unexpected \"$\"");
+        assert_eq!(parse_error("a + $").position, 4);
+    }
+
+    #[test]
+    fn input_names_are_sorted_and_unique() {
+        assert_eq!(Expression::input_names("zeta + alpha * sin(x) + alpha + pi * w"), ["alpha", "zeta"]);
+        // Function names only count when they're called.
+        assert_eq!(Expression::input_names("sin + sin(b)"), ["b", "sin"]);
+        // Names are found even when the formula doesn't parse yet.
+        assert_eq!(Expression::input_names("b + a *"), ["a", "b"]);
+    }
+
+    #[test]
+    fn inputs_follow_the_order_of_their_names() {
+        assert_eq!(evaluate("b - a", &[], &[1.0, 10.0]), 9.0);
+    }
+
+    #[test]
+    fn evaluates_coordinates_constants_and_functions() {
+        assert_eq!(evaluate("x + 2 * y", &[1.0, 3.0], &[]), 7.0);
+        // Coordinates past the point's dimensions are 0.
+        assert_eq!(evaluate("z + w", &[1.0, 2.0], &[]), 0.0);
+        assert_eq!(evaluate("tau / pi", &[], &[]), 2.0);
+        assert_eq!(evaluate("clamp(a, -1, 1)", &[], &[5.0]), 1.0);
+        assert_eq!(evaluate("lerp(0, 10, 0.25)", &[], &[]), 2.5);
+        assert_eq!(evaluate("smoothstep(0, 1, 0.5)", &[], &[]), 0.5);
+        assert_eq!(evaluate("step(0, -1) + step(0, 1)", &[], &[]), 1.0);
+        assert_eq!(evaluate("max(abs(-3), sqrt(16)) + fract(2.75)", &[], &[]), 4.75);
+    }
+}
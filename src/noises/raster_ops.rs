@@ -0,0 +1,289 @@
+//! Image processing nodes, which work on a grid of their input's values. Distances are in noise
+//! coordinates, so the results don't change with the resolution.
+
+use crate::{
+    build_in_every_dimension,
+    params::{ParamKind, ParamSpec},
+    raster::{self, Raster, RasterArea},
+};
+
+use super::{DynNoise, NodeKind, NoiseClassification, NoiseConfig};
+
+/// The radius of blurs and morphology, in noise coordinates.
+const RADIUS: ParamSpec = ParamSpec {
+    name: "radius",
+    label: "Radius",
+    kind: ParamKind::Float { default: 0.01, min: 0.001, max: 0.1, logarithmic: true },
+};
+
+/// Gaussian kernels are cut off at this many standard deviations.
+const BLUR_REACH: f64 = 3.0;
+
+pub struct Blur;
+
+impl NodeKind for Blur {
+    fn id(&self) -> &'static str {
+        "Blur"
+    }
+
+    fn name(&self) -> &'static str {
+        "Gaussian blur"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Raster
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[raster::RESOLUTION, raster::SIZE, RADIUS]
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        1
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, _index: usize) -> String {
+        "Source".to_string()
+    }
+
+    build_in_every_dimension!(Self::build);
+}
+
+impl Blur {
+    /// The radius is the kernel's standard deviation.
+    fn build<const DIM: usize>(config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [source] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        let radius = RADIUS.float(config.values());
+        let area = RasterArea::from_params(config.values(), radius * BLUR_REACH);
+        let sigma = area.cells(radius);
+        DynNoise::new(Raster::new(source, area, move |grid| grid.blur(sigma)))
+    }
+}
+
+/// An unsharp mask, which exaggerates the difference between each value and a blur of its
+/// surroundings.
+pub struct Sharpen;
+
+impl NodeKind for Sharpen {
+    fn id(&self) -> &'static str {
+        "Sharpen"
+    }
+
+    fn name(&self) -> &'static str {
+        "Unsharp mask"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Raster
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[raster::RESOLUTION, raster::SIZE, RADIUS, Self::AMOUNT]
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        1
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, _index: usize) -> String {
+        "Source".to_string()
+    }
+
+    build_in_every_dimension!(Self::build);
+}
+
+impl Sharpen {
+    /// How much of the difference from the blur is added.
+    pub const AMOUNT: ParamSpec = ParamSpec {
+        name: "amount",
+        label: "Amount",
+        kind: ParamKind::Float { default: 1.0, min: 0.0, max: 5.0, logarithmic: false },
+    };
+
+    fn build<const DIM: usize>(config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [source] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        let values = config.values();
+        let radius = RADIUS.float(values);
+        let amount = Self::AMOUNT.float(values);
+        let area = RasterArea::from_params(values, radius * BLUR_REACH);
+        let sigma = area.cells(radius);
+        DynNoise::new(Raster::new(source, area, move |mut grid| {
+            let blurred = grid.blur(sigma);
+            for (value, blurred) in grid.values_mut().iter_mut().zip(blurred.values()) {
+                *value += (*value - blurred) * amount;
+            }
+            grid
+        }))
+    }
+}
+
+/// The steepness of its input, which is largest at edges.
+pub struct EdgeDetect;
+
+impl NodeKind for EdgeDetect {
+    fn id(&self) -> &'static str {
+        "EdgeDetect"
+    }
+
+    fn name(&self) -> &'static str {
+        "Edge detect"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Raster
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[raster::RESOLUTION, raster::SIZE, Self::SCALE]
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        1
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, _index: usize) -> String {
+        "Source".to_string()
+    }
+
+    build_in_every_dimension!(Self::build);
+}
+
+impl EdgeDetect {
+    /// Multiplies the steepness, which is in change of value per unit of noise coordinates.
+    pub const SCALE: ParamSpec = ParamSpec {
+        name: "scale",
+        label: "Scale",
+        kind: ParamKind::Float { default: 0.25, min: 0.001, max: 10.0, logarithmic: true },
+    };
+
+    fn build<const DIM: usize>(config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [source] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        let values = config.values();
+        let mut area = RasterArea::from_params(values, 0.0);
+        // The Sobel filter reads one cell past each cell.
+        area.padding = 1;
+        let scale = Self::SCALE.float(values) / area.cell_size();
+        DynNoise::new(Raster::new(source, area, move |grid| {
+            let mut slopes = grid.slopes();
+            slopes.values_mut().iter_mut().for_each(|slope| *slope *= scale);
+            slopes
+        }))
+    }
+}
+
+/// The distance to the edge of the parts of its input above a threshold, negative inside them.
+pub struct DistanceTransform;
+
+impl NodeKind for DistanceTransform {
+    fn id(&self) -> &'static str {
+        "DistanceTransform"
+    }
+
+    fn name(&self) -> &'static str {
+        "Distance transform"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Raster
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[raster::RESOLUTION, raster::SIZE, Self::THRESHOLD, Self::MAX_DISTANCE]
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        1
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, _index: usize) -> String {
+        "Mask".to_string()
+    }
+
+    build_in_every_dimension!(Self::build);
+}
+
+impl DistanceTransform {
+    /// Values at or above the threshold are inside the mask.
+    pub const THRESHOLD: ParamSpec = ParamSpec {
+        name: "threshold",
+        label: "Threshold",
+        kind: ParamKind::Float { default: 0.0, min: -1.0, max: 1.0, logarithmic: false },
+    };
+    /// The distance that maps to 1, or -1 inside the mask. Further distances are clamped.
+    pub const MAX_DISTANCE: ParamSpec = ParamSpec {
+        name: "max_distance",
+        label: "Max distance",
+        kind: ParamKind::Float { default: 0.1, min: 0.001, max: 1.0, logarithmic: true },
+    };
+
+    /// The grid is padded by the maximum distance, so that the edges of the mask just past the
+    /// square count.
+    fn build<const DIM: usize>(config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [mask] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        let values = config.values();
+        let threshold = Self::THRESHOLD.float(values);
+        let max_distance = Self::MAX_DISTANCE.float(values);
+        let area = RasterArea::from_params(values, max_distance);
+        let max_cells = area.cells(max_distance);
+        DynNoise::new(Raster::new(mask, area, move |grid| {
+            let mut distances = grid.signed_distances(|value| value >= threshold);
+            for distance in distances.values_mut() {
+                *distance = (*distance / max_cells).clamp(-1.0, 1.0);
+            }
+            distances
+        }))
+    }
+}
+
+/// Dilation, which spreads the highest values over their surroundings, or erosion, which spreads
+/// the lowest.
+pub struct Morphology;
+
+impl NodeKind for Morphology {
+    fn id(&self) -> &'static str {
+        "Morphology"
+    }
+
+    fn name(&self) -> &'static str {
+        "Dilate / erode"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Raster
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[raster::RESOLUTION, raster::SIZE, Self::OPERATION, RADIUS]
+    }
+
+    fn input_count(&self, _config: &NoiseConfig) -> usize {
+        1
+    }
+
+    fn input_name(&self, _config: &NoiseConfig, _index: usize) -> String {
+        "Source".to_string()
+    }
+
+    build_in_every_dimension!(Self::build);
+}
+
+impl Morphology {
+    pub const OPERATION: ParamSpec = ParamSpec {
+        name: "operation",
+        label: "Operation",
+        kind: ParamKind::Enum { default: "Dilate", options: &["Dilate", "Erode"] },
+    };
+
+    /// Each cell takes the highest or lowest value within a disc of the radius around it.
+    fn build<const DIM: usize>(config: &NoiseConfig, inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let [source] = inputs.try_into().unwrap_or_else(|_| panic!("Missing input"));
+        let values = config.values();
+        let radius = RADIUS.float(values);
+        let area = RasterArea::from_params(values, radius);
+        let radius = area.cells(radius);
+        let dilate = Self::OPERATION.option(values) == 0;
+        DynNoise::new(Raster::new(source, area, move |grid| {
+            if dilate { grid.dilate(radius) } else { grid.erode(radius) }
+        }))
+    }
+}
@@ -0,0 +1,368 @@
+//! The image sampler node, which turns a channel of a PNG or JPEG into a noise source, so that
+//! hand-painted masks can be combined with procedural noise. Images are embedded in the graph.
+
+use std::{collections::HashMap, hash::{Hash, Hasher}, sync::{Arc, LazyLock, Mutex}};
+
+use base64::prelude::*;
+use image::RgbaImage;
+use noise::NoiseFn;
+
+use crate::{build_in_every_dimension, params::{ParamKind, ParamSpec, ParamValue, ParamValues}};
+
+use super::{DynNoise, NodeKind, NoiseClassification, NoiseConfig};
+
+pub struct ImageSampler;
+
+impl NodeKind for ImageSampler {
+    fn id(&self) -> &'static str {
+        "ImageSampler"
+    }
+
+    fn name(&self) -> &'static str {
+        "Image sampler"
+    }
+
+    fn classification(&self) -> NoiseClassification {
+        NoiseClassification::Source
+    }
+
+    fn params(&self) -> &'static [ParamSpec] {
+        &[Self::IMAGE, Self::CHANNEL, Self::FILTER, Self::WRAP, Self::SIZE]
+    }
+
+    build_in_every_dimension!(Self::build);
+
+    /// The image is chosen with a file dialog, which is asynchronous on the web, so the picked
+    /// file's contents wait in the UI's memory until the next frame after they arrive.
+    #[cfg(feature = "gui")]
+    fn show_body(&self, config: &mut NoiseConfig, context: &crate::params::BodyContext<'_>, ui: &mut egui::Ui, scale: f32) -> bool {
+        let values = config.values_mut();
+        let picked_id = ui.id().with("picked_image");
+        let picked = ui.data_mut(|data| data.get_temp_mut_or_default::<PickedImage>(picked_id).clone());
+        let mut changed = false;
+        if let Some(bytes) = picked.lock().expect("Picked image was poisoned").take() {
+            Self::embed(values, &bytes);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Image:");
+            match cached(Self::KEY.int(values)) {
+                Some(Ok(image)) => {
+                    ui.label(format!("{} × {} pixels", image.width(), image.height()));
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                }
+                None if Self::IMAGE.text(values).is_empty() => {
+                    match Self::PATH.text(values) {
+                        path if path.is_empty() => ui.label("None"),
+                        path => ui.label(format!("Read from {path}")).on_hover_text("Choose it again to embed it in the graph"),
+                    };
+                }
+                None => {
+                    ui.label("Embedded");
+                }
+            }
+            if ui.button("Choose…").clicked() {
+                pick_image(ui.ctx(), picked);
+            }
+        });
+        changed | crate::params::show(&self.params()[1..], values, context, ui, scale)
+    }
+}
+
+/// The contents of a picked image file, once it has been read.
+#[cfg(feature = "gui")]
+type PickedImage = Arc<Mutex<Option<Vec<u8>>>>;
+
+#[cfg(all(feature = "gui", not(target_arch = "wasm32")))]
+fn pick_image(_ctx: &egui::Context, picked: PickedImage) {
+    let Some(path) = rfd::FileDialog::new().add_filter("Image", IMAGE_EXTENSIONS).pick_file() else { return };
+    match std::fs::read(&path) {
+        Ok(bytes) => *picked.lock().expect("Picked image was poisoned") = Some(bytes),
+        Err(err) => log::error!("Failed to read {}: {err}", path.display()),
+    }
+}
+
+#[cfg(all(feature = "gui", target_arch = "wasm32"))]
+fn pick_image(ctx: &egui::Context, picked: PickedImage) {
+    let ctx = ctx.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        let Some(file) = rfd::AsyncFileDialog::new().add_filter("Image", IMAGE_EXTENSIONS).pick_file().await else { return };
+        *picked.lock().expect("Picked image was poisoned") = Some(file.read().await);
+        ctx.request_repaint();
+    });
+}
+
+#[cfg(feature = "gui")]
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+
+impl ImageSampler {
+    /// The image file's contents in base64, so that graphs carry their images with them and work
+    /// on the web.
+    pub const IMAGE: ParamSpec = ParamSpec { name: "image", label: "Image", kind: ParamKind::Text { default: "" } };
+    /// A hash of the image's contents, which identifies the decoded image without hashing the
+    /// whole image each time. Not listed with the parameters, so it's never swept or exposed.
+    const KEY: ParamSpec = ParamSpec { name: "image_key", label: "Image key", kind: ParamKind::Int { default: 0, min: i64::MIN, max: i64::MAX } };
+    /// Older graphs linked to an image file instead, which is still read on native when no image is
+    /// embedded.
+    const PATH: ParamSpec = ParamSpec { name: "path", label: "Path", kind: ParamKind::Text { default: "" } };
+    pub const CHANNEL: ParamSpec = ParamSpec {
+        name: "channel",
+        label: "Channel",
+        kind: ParamKind::Enum { default: "Luminance", options: &["Luminance", "Red", "Green", "Blue", "Alpha"] },
+    };
+    pub const FILTER: ParamSpec = ParamSpec {
+        name: "filter",
+        label: "Filter",
+        kind: ParamKind::Enum { default: "Bilinear", options: &["Bilinear", "Bicubic"] },
+    };
+    /// What lies past the image's edges: repeats of the image, or its edge pixels.
+    pub const WRAP: ParamSpec = ParamSpec {
+        name: "wrap",
+        label: "Edges",
+        kind: ParamKind::Enum { default: "Wrap", options: &["Wrap", "Clamp"] },
+    };
+    /// The width of the image in noise coordinates. Pixels are square, so its height follows
+    /// from its aspect ratio.
+    pub const SIZE: ParamSpec = ParamSpec {
+        name: "size",
+        label: "Size",
+        kind: ParamKind::Float { default: 1.0, min: 0.01, max: 100.0, logarithmic: true },
+    };
+
+    /// Stores the contents of an image file in the node, replacing any linked file.
+    pub fn embed(values: &mut ParamValues, bytes: &[u8]) {
+        values.set(Self::IMAGE.name, ParamValue::Text(BASE64_STANDARD.encode(bytes)));
+        values.set(Self::KEY.name, ParamValue::Int(content_key(bytes)));
+        values.set(Self::PATH.name, ParamValue::Text(String::new()));
+    }
+
+    /// Images that can't be loaded, or that have no size, produce 0 like an unconnected input.
+    fn build<const DIM: usize>(config: &NoiseConfig, _inputs: Vec<DynNoise<DIM>>) -> DynNoise<DIM> {
+        let values = config.values();
+        let size = Self::SIZE.float(values);
+        if !(size.is_finite() && size > 0.0) {
+            return DynNoise::new(noise::Constant::new(0.0));
+        }
+        match Self::load(values) {
+            Ok(image) => DynNoise::new(Sampler::new(image, values)),
+            Err(_) => DynNoise::new(noise::Constant::new(0.0)),
+        }
+    }
+
+    fn load(values: &ParamValues) -> Loaded {
+        let image = Self::IMAGE.text(values);
+        if !image.is_empty() {
+            return load(Self::KEY.int(values), || BASE64_STANDARD.decode(image).map_err(|err| err.to_string()));
+        }
+        let path = Self::PATH.text(values);
+        if path.is_empty() {
+            return Err("No image chosen".to_string());
+        }
+        #[cfg(target_arch = "wasm32")]
+        return Err(format!("Can't read {path} on the web, choose the image again"));
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            // Read each time, so that changes to the file show up, but only decoded when they do.
+            let bytes = std::fs::read(&path).map_err(|err| format!("Failed to read {path}: {err}"))?;
+            load(content_key(&bytes), || Ok(bytes))
+        }
+    }
+}
+
+/// Identifies an image by its contents.
+fn content_key(bytes: &[u8]) -> i64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish() as i64
+}
+
+/// A decoded image, or why it couldn't be loaded.
+type Loaded = Result<Arc<RgbaImage>, String>;
+
+/// Images kept decoded, beyond which the least recently used are dropped.
+const CACHED_IMAGES: usize = 8;
+
+struct CachedImage {
+    loaded: Loaded,
+    /// When the image was last asked for, counting requests.
+    used: u64,
+}
+
+/// Images by the key of their contents, including those that failed to decode.
+#[derive(Default)]
+struct ImageCache {
+    images: HashMap<i64, CachedImage>,
+    requests: u64,
+}
+
+static IMAGES: LazyLock<Mutex<ImageCache>> = LazyLock::new(Default::default);
+
+fn cache() -> std::sync::MutexGuard<'static, ImageCache> {
+    IMAGES.lock().expect("Image cache was poisoned")
+}
+
+/// The image last loaded with a key, if it's still cached.
+#[cfg(feature = "gui")]
+fn cached(key: i64) -> Option<Loaded> {
+    cache().images.get(&key).map(|image| image.loaded.clone())
+}
+
+/// Decodes an image, unless one with the same key is cached. Decoding happens outside the cache's
+/// lock, so that the UI isn't held up by a large image loading on another thread.
+fn load(key: i64, bytes: impl FnOnce() -> Result<Vec<u8>, String>) -> Loaded {
+    {
+        let mut cache = cache();
+        cache.requests += 1;
+        let requests = cache.requests;
+        if let Some(image) = cache.images.get_mut(&key) {
+            image.used = requests;
+            return image.loaded.clone();
+        }
+    }
+    let loaded = bytes().and_then(|bytes| {
+        image::load_from_memory(&bytes).map(|image| Arc::new(image.into_rgba8())).map_err(|err| err.to_string())
+    });
+    let mut cache = cache();
+    let used = cache.requests;
+    cache.images.insert(key, CachedImage { loaded: loaded.clone(), used });
+    if cache.images.len() > CACHED_IMAGES {
+        if let Some(oldest) = cache.images.iter().min_by_key(|(_, image)| image.used).map(|(&key, _)| key) {
+            cache.images.remove(&oldest);
+        }
+    }
+    loaded
+}
+
+#[derive(Clone, Copy)]
+enum Channel {
+    Luminance,
+    Rgba(usize),
+}
+
+struct Sampler {
+    image: Arc<RgbaImage>,
+    channel: Channel,
+    bicubic: bool,
+    wrap: bool,
+    /// Pixels per unit of noise coordinates.
+    scale: f64,
+}
+
+impl Sampler {
+    fn new(image: Arc<RgbaImage>, values: &ParamValues) -> Self {
+        let channel = match ImageSampler::CHANNEL.option(values) {
+            0 => Channel::Luminance,
+            channel => Channel::Rgba(channel - 1),
+        };
+        let scale = image.width() as f64 / ImageSampler::SIZE.float(values);
+        Self {
+            image,
+            channel,
+            bicubic: ImageSampler::FILTER.option(values) == 1,
+            wrap: ImageSampler::WRAP.option(values) == 0,
+            scale,
+        }
+    }
+
+    /// The channel's value of a pixel, from -1 to 1. Pixels past the edges wrap or clamp.
+    fn pixel(&self, x: i64, y: i64) -> f64 {
+        let (width, height) = (self.image.width() as i64, self.image.height() as i64);
+        let (x, y) = if self.wrap {
+            (x.rem_euclid(width), y.rem_euclid(height))
+        } else {
+            (x.clamp(0, width - 1), y.clamp(0, height - 1))
+        };
+        let [r, g, b, a] = self.image.get_pixel(x as u32, y as u32).0;
+        let value = match self.channel {
+            // Rec. 709 luma.
+            Channel::Luminance => 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64,
+            Channel::Rgba(channel) => [r, g, b, a][channel] as f64,
+        };
+        value / 255.0 * 2.0 - 1.0
+    }
+
+    /// Brings a position in pixels to within a few pixels of the image, where it reads the same
+    /// pixels, so that far away positions don't overflow when converted to integers.
+    fn fold(&self, position: f64, extent: u32) -> f64 {
+        let extent = extent as f64;
+        if self.wrap {
+            position.rem_euclid(extent)
+        } else {
+            // The filters read up to two pixels either side.
+            position.clamp(-2.0, extent + 2.0)
+        }
+    }
+
+    fn bilinear(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = lerp(self.pixel(x0, y0), self.pixel(x0 + 1, y0), tx);
+        let bottom = lerp(self.pixel(x0, y0 + 1), self.pixel(x0 + 1, y0 + 1), tx);
+        lerp(top, bottom, ty)
+    }
+
+    /// Catmull-Rom interpolation of the surrounding 4×4 pixels, which passes through the pixels'
+    /// values but can overshoot them slightly.
+    fn bicubic(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let rows = [-1, 0, 1, 2].map(|dy| {
+            let [a, b, c, d] = [-1, 0, 1, 2].map(|dx| self.pixel(x0 + dx, y0 + dy));
+            catmull_rom(a, b, c, d, tx)
+        });
+        catmull_rom(rows[0], rows[1], rows[2], rows[3], ty)
+    }
+}
+
+impl<const DIM: usize> NoiseFn<f64, DIM> for Sampler {
+    /// Only the first two coordinates are used, with the image's top left corner at the origin.
+    fn get(&self, point: [f64; DIM]) -> f64 {
+        // Pixel centers are at half coordinates.
+        let x = self.fold(point[0] * self.scale - 0.5, self.image.width());
+        let y = self.fold(point.get(1).copied().unwrap_or(0.0) * self.scale - 0.5, self.image.height());
+        if self.bicubic { self.bicubic(x, y) } else { self.bilinear(x, y) }
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn catmull_rom(a: f64, b: f64, c: f64, d: f64, t: f64) -> f64 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * b + (c - a) * t + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2 + (3.0 * b - a - 3.0 * c + d) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn sampler_with(bytes: &[u8]) -> NoiseConfig {
+        let mut config = super::super::NoiseType::IMAGE_SAMPLER.config();
+        ImageSampler::embed(config.values_mut(), bytes);
+        config
+    }
+
+    #[test]
+    fn embedded_images_are_sampled() {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(2, 2, image::Rgba([255; 4])).write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+        let config = sampler_with(&png);
+        let noise = ImageSampler::build::<2>(&config, Vec::new());
+        assert!((noise.get([0.25, 0.25]) - 1.0).abs() < 1e-9);
+        assert!(cache().images.get(&ImageSampler::KEY.int(config.values())).is_some_and(|image| image.loaded.is_ok()));
+    }
+
+    #[test]
+    fn undecodable_images_sample_zero() {
+        let noise = ImageSampler::build::<2>(&sampler_with(b"not an image"), Vec::new());
+        assert_eq!(noise.get([0.25, 0.25]), 0.0);
+    }
+}
@@ -0,0 +1,469 @@
+//! Declarative descriptions of node parameters.
+//!
+//! A [`NodeKind`](crate::noises::NodeKind) lists its parameters as [`ParamSpec`]s, and everything
+//! else follows from them: the default config of new nodes, how values are saved, and the controls
+//! in the node's body, each with a button to reset it to its default.
+
+use std::collections::BTreeMap;
+
+use noise::NoiseFn;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "gui")]
+use crate::render::Histogram;
+
+/// A parameter of a kind of node.
+pub struct ParamSpec {
+    /// Identifies the parameter in saved graphs, so it must never change.
+    pub name: &'static str,
+    /// Shown next to the parameter's control.
+    pub label: &'static str,
+    pub kind: ParamKind,
+}
+
+/// The type of a parameter, along with its default value and the range offered by its control.
+pub enum ParamKind {
+    Int { default: i64, min: i64, max: i64 },
+    /// Values can be typed outside the range. Logarithmic sliders suit ranges spanning several
+    /// orders of magnitude, such as frequencies.
+    Float { default: f64, min: f64, max: f64, logarithmic: bool },
+    /// One of several named options, saved by name.
+    Enum { default: &'static str, options: &'static [&'static str] },
+    Bool { default: bool },
+    /// A seed for a random number generator. Any value is as good as another, so it's edited as a
+    /// number rather than with a slider.
+    Seed { default: u32 },
+    /// Control points `[input, output]` of a curve, in order of input.
+    Curve { default: &'static [[f64; 2]], shape: CurveShape },
+    /// Free text, such as a formula.
+    Text { default: &'static str },
+}
+
+/// How the points of a curve parameter map input values to output values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveShape {
+    /// A spline through the points, as in [`noise::Curve`].
+    Spline,
+    /// Terraces between the points' inputs, as in [`noise::Terrace`]. Only the inputs matter, so
+    /// points are kept on the diagonal.
+    Terraces,
+}
+
+impl CurveShape {
+    /// The fewest points the curve needs.
+    pub fn min_points(self) -> usize {
+        match self {
+            CurveShape::Spline => 4,
+            CurveShape::Terraces => 2,
+        }
+    }
+
+    /// The points the `noise` crate keeps, which skips any point whose input is within
+    /// `f64::EPSILON` of an earlier one, or None if too few are left to make the curve.
+    pub fn usable_points(self, points: &[[f64; 2]]) -> Option<Vec<[f64; 2]>> {
+        let mut usable: Vec<[f64; 2]> = Vec::with_capacity(points.len());
+        for &point in points {
+            if point[0].is_finite() && !usable.iter().any(|other| (other[0] - point[0]).abs() < f64::EPSILON) {
+                usable.push(point);
+            }
+        }
+        (usable.len() >= self.min_points()).then_some(usable)
+    }
+
+    /// The curve's output for an input value. Curves with too few points leave values unchanged.
+    pub fn evaluate(self, points: &[[f64; 2]], input: f64) -> f64 {
+        let Some(points) = self.usable_points(points) else { return input };
+        let source = noise::Constant::new(input);
+        match self {
+            CurveShape::Spline => points
+                .iter()
+                .fold(noise::Curve::new(source), |curve, &[x, y]| curve.add_control_point(x, y))
+                .get([0.0, 0.0]),
+            CurveShape::Terraces => points
+                .iter()
+                .fold(noise::Terrace::new(source), |terrace, &[x, _]| terrace.add_control_point(x))
+                .get([0.0, 0.0]),
+        }
+    }
+}
+
+/// What a node's body can show about the rest of the graph.
+#[cfg(feature = "gui")]
+pub struct BodyContext<'a> {
+    /// Parameters set through input pins, whose controls are disabled.
+    pub driven: &'a [String],
+    /// The values of the node's first input, shown behind curves.
+    pub input_histogram: Option<&'a Histogram>,
+}
+
+#[cfg(feature = "gui")]
+impl BodyContext<'_> {
+    pub fn is_driven(&self, spec: &ParamSpec) -> bool {
+        self.driven.iter().any(|name| name == spec.name)
+    }
+}
+
+/// The value of a parameter. Saved untagged, since the parameter's [`ParamKind`] tells them apart.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Points(Vec<[f64; 2]>),
+}
+
+/// The values of a node's parameters by name. Parameters without a value have their default.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ParamValues(BTreeMap<String, ParamValue>);
+
+impl ParamValues {
+    pub const EMPTY: ParamValues = ParamValues(BTreeMap::new());
+
+    /// The default values of every parameter.
+    pub fn defaults(specs: &[ParamSpec]) -> Self {
+        Self(specs.iter().map(|spec| (spec.name.to_string(), spec.default_value())).collect())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ParamValue> {
+        self.0.get(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: ParamValue) {
+        self.0.insert(name.to_string(), value);
+    }
+}
+
+impl ParamSpec {
+    pub fn default_value(&self) -> ParamValue {
+        match self.kind {
+            ParamKind::Int { default, .. } => ParamValue::Int(default),
+            ParamKind::Float { default, .. } => ParamValue::Float(default),
+            ParamKind::Enum { default, .. } => ParamValue::Text(default.to_string()),
+            ParamKind::Bool { default } => ParamValue::Bool(default),
+            ParamKind::Seed { default } => ParamValue::Int(default.into()),
+            ParamKind::Curve { default, .. } => ParamValue::Points(default.to_vec()),
+            ParamKind::Text { default } => ParamValue::Text(default.to_string()),
+        }
+    }
+
+    /// The parameter's value, or its default if it has none or one of the wrong type. The accessors
+    /// below convert it to the type matching the parameter's kind.
+    pub fn value(&self, values: &ParamValues) -> ParamValue {
+        let default = self.default_value();
+        match (values.get(self.name), &default) {
+            (Some(value @ ParamValue::Float(_)), ParamValue::Float(_))
+            | (Some(value @ ParamValue::Bool(_)), ParamValue::Bool(_))
+            | (Some(value @ ParamValue::Points(_)), ParamValue::Points(_))
+            | (Some(value @ ParamValue::Int(_)), ParamValue::Int(_) | ParamValue::Float(_)) => value.clone(),
+            (Some(ParamValue::Text(text)), ParamValue::Text(_))
+                if matches!(self.kind, ParamKind::Text { .. }) || self.options().contains(&text.as_str()) => {
+                ParamValue::Text(text.clone())
+            }
+            _ => default,
+        }
+    }
+
+    pub fn int(&self, values: &ParamValues) -> i64 {
+        match self.value(values) {
+            ParamValue::Int(value) => value,
+            _ => panic!("Parameter {} isn't an integer", self.name),
+        }
+    }
+
+    pub fn float(&self, values: &ParamValues) -> f64 {
+        match self.value(values) {
+            ParamValue::Float(value) => value,
+            ParamValue::Int(value) => value as f64,
+            _ => panic!("Parameter {} isn't a number", self.name),
+        }
+    }
+
+    /// The index of the selected option.
+    pub fn option(&self, values: &ParamValues) -> usize {
+        match self.value(values) {
+            ParamValue::Text(option) => self.options().iter().position(|&o| o == option).unwrap_or(0),
+            _ => panic!("Parameter {} isn't an enum", self.name),
+        }
+    }
+
+    pub fn bool(&self, values: &ParamValues) -> bool {
+        match self.value(values) {
+            ParamValue::Bool(value) => value,
+            _ => panic!("Parameter {} isn't a bool", self.name),
+        }
+    }
+
+    /// Seeds wrap around rather than saturate, so that every saved value is usable.
+    pub fn seed(&self, values: &ParamValues) -> u32 {
+        self.int(values) as u32
+    }
+
+    pub fn text(&self, values: &ParamValues) -> String {
+        match self.value(values) {
+            ParamValue::Text(text) => text,
+            _ => panic!("Parameter {} isn't text", self.name),
+        }
+    }
+
+    pub fn points(&self, values: &ParamValues) -> Vec<[f64; 2]> {
+        match self.value(values) {
+            ParamValue::Points(points) => points,
+            _ => panic!("Parameter {} isn't a curve", self.name),
+        }
+    }
+
+    /// Whether the parameter can be exposed as an input pin and driven by a scalar node.
+    pub fn is_scalar(&self) -> bool {
+        matches!(self.kind, ParamKind::Int { .. } | ParamKind::Float { .. } | ParamKind::Seed { .. })
+    }
+
+    /// Converts the output of a scalar node to a value of this parameter. Integers are rounded.
+    pub fn from_scalar(&self, scalar: f64) -> Option<ParamValue> {
+        match self.kind {
+            ParamKind::Int { .. } => Some(ParamValue::Int(scalar.round() as i64)),
+            ParamKind::Float { .. } => Some(ParamValue::Float(scalar)),
+            ParamKind::Seed { .. } => Some(ParamValue::Int((scalar.round() as i64 as u32).into())),
+            _ => None,
+        }
+    }
+
+    fn options(&self) -> &'static [&'static str] {
+        match self.kind {
+            ParamKind::Enum { options, .. } => options,
+            _ => &[],
+        }
+    }
+}
+
+/// Shows a control for each parameter, returning whether any value changed. Parameters set through
+/// input pins have their controls disabled.
+#[cfg(feature = "gui")]
+pub fn show(specs: &[ParamSpec], values: &mut ParamValues, context: &BodyContext<'_>, ui: &mut egui::Ui, scale: f32) -> bool {
+    let mut changed = false;
+    egui::Grid::new("params").num_columns(3).show(ui, |ui| {
+        for spec in specs {
+            ui.label(spec.label);
+            let mut value = spec.value(values);
+            let is_driven = context.is_driven(spec);
+            let edited = ui.add_enabled_ui(!is_driven, |ui| {
+                ui.push_id(spec.name, |ui| show_value(spec, &mut value, context, ui, scale)).inner
+            });
+            edited.response.on_disabled_hover_text("Set by an input pin");
+            let edited = edited.inner;
+            let default = spec.default_value();
+            let reset = ui
+                .add_enabled(!is_driven && value != default, egui::Button::new("⟲").small())
+                .on_hover_text("Reset to default")
+                .clicked();
+            if reset {
+                value = default;
+            }
+            if edited || reset {
+                values.set(spec.name, value);
+                changed = true;
+            }
+            ui.end_row();
+        }
+    });
+    changed
+}
+
+#[cfg(feature = "gui")]
+fn show_value(spec: &ParamSpec, value: &mut ParamValue, context: &BodyContext<'_>, ui: &mut egui::Ui, scale: f32) -> bool {
+    use egui::{DragValue, Slider, SliderClamping};
+
+    // Slider values can be clicked to type an exact value.
+    match (&spec.kind, value) {
+        (&ParamKind::Int { min, max, .. }, ParamValue::Int(value)) => {
+            ui.add(Slider::new(value, min..=max).clamping(SliderClamping::Never)).changed()
+        }
+        (&ParamKind::Float { min, max, logarithmic, .. }, value) => {
+            let mut number = match value {
+                ParamValue::Int(int) => *int as f64,
+                ParamValue::Float(float) => *float,
+                _ => return false,
+            };
+            let slider = Slider::new(&mut number, min..=max).logarithmic(logarithmic).clamping(SliderClamping::Never);
+            let changed = ui.add(slider).changed();
+            *value = ParamValue::Float(number);
+            changed
+        }
+        (ParamKind::Enum { options, .. }, ParamValue::Text(selected)) => {
+            let mut changed = false;
+            egui::ComboBox::from_id_salt(spec.name).selected_text(selected.as_str()).show_ui(ui, |ui| {
+                for option in *options {
+                    if ui.selectable_label(selected == option, *option).clicked() && selected != option {
+                        *selected = option.to_string();
+                        changed = true;
+                    }
+                }
+            });
+            changed
+        }
+        (ParamKind::Bool { .. }, ParamValue::Bool(value)) => ui.checkbox(value, "").changed(),
+        (ParamKind::Seed { .. }, ParamValue::Int(value)) => {
+            let mut seed = *value as u32;
+            let changed = ui.add(DragValue::new(&mut seed).speed(1.0)).changed();
+            *value = seed.into();
+            changed
+        }
+        (&ParamKind::Curve { shape, .. }, ParamValue::Points(points)) => {
+            show_curve(points, shape, context.input_histogram, &|points, input| shape.evaluate(points, input), ui, scale)
+        }
+        (ParamKind::Text { .. }, ParamValue::Text(text)) => ui.text_edit_singleline(text).changed(),
+        _ => false,
+    }
+}
+
+/// The part of the curve plane shown by [`show_curve`]. Kept while a point is dragged, so that
+/// the view doesn't move under the pointer.
+#[cfg(feature = "gui")]
+#[derive(Clone, Copy)]
+struct CurveView {
+    x: (f64, f64),
+    y: (f64, f64),
+}
+
+#[cfg(feature = "gui")]
+impl CurveView {
+    /// Covers -1 to 1 on both axes, every point, and the range of the histogram's inputs.
+    fn fit(points: &[[f64; 2]], histogram: Option<&Histogram>) -> Self {
+        let mut view = Self { x: (-1.0, 1.0), y: (-1.0, 1.0) };
+        for &[x, y] in points {
+            view.x = (view.x.0.min(x), view.x.1.max(x));
+            view.y = (view.y.0.min(y), view.y.1.max(y));
+        }
+        if let Some(histogram) = histogram.filter(|histogram| histogram.min <= histogram.max) {
+            view.x = (view.x.0.min(histogram.min), view.x.1.max(histogram.max));
+        }
+        view
+    }
+
+    fn screen_pos(self, rect: egui::Rect, [x, y]: [f64; 2]) -> egui::Pos2 {
+        egui::pos2(
+            rect.left() + ((x - self.x.0) / (self.x.1 - self.x.0)) as f32 * rect.width(),
+            rect.bottom() - ((y - self.y.0) / (self.y.1 - self.y.0)) as f32 * rect.height(),
+        )
+    }
+
+    fn plane_pos(self, rect: egui::Rect, pos: egui::Pos2) -> [f64; 2] {
+        [
+            self.x.0 + ((pos.x - rect.left()) / rect.width()) as f64 * (self.x.1 - self.x.0),
+            self.y.0 + ((rect.bottom() - pos.y) / rect.height()) as f64 * (self.y.1 - self.y.0),
+        ]
+    }
+}
+
+/// Shows a curve's points over the histogram of the values going into it. Points can be dragged,
+/// added by double-clicking and deleted by right-clicking, keeping at least the shape's minimum.
+/// `evaluate` gives the curve's output for an input, for drawing it.
+#[cfg(feature = "gui")]
+pub fn show_curve(
+        points: &mut Vec<[f64; 2]>,
+        shape: CurveShape,
+        histogram: Option<&Histogram>,
+        evaluate: &dyn Fn(&[[f64; 2]], f64) -> f64,
+        ui: &mut egui::Ui,
+        scale: f32) -> bool {
+    use egui::{Color32, Sense, Shape, Stroke, Vec2};
+
+    const POINT_RADIUS: f32 = 4.0;
+    const GRAB_DISTANCE: f32 = 8.0;
+
+    let (response, painter) = ui.allocate_painter(Vec2::new(256.0, 160.0) * scale, Sense::click_and_drag());
+    let rect = response.rect;
+    let drag_id = response.id.with("dragged_point");
+    let dragged: Option<(usize, CurveView)> = ui.data(|data| data.get_temp(drag_id));
+    let view = dragged.map_or_else(|| CurveView::fit(points, histogram), |(_, view)| view);
+    let nearest = |pos: egui::Pos2, points: &[[f64; 2]]| {
+        points
+            .iter()
+            .map(|&point| view.screen_pos(rect, point).distance(pos))
+            .enumerate()
+            .filter(|&(_, distance)| distance <= GRAB_DISTANCE * scale)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    };
+    let on_curve = |[x, y]: [f64; 2]| match shape {
+        CurveShape::Spline => [x, y],
+        CurveShape::Terraces => [x, x],
+    };
+
+    let mut changed = false;
+    if response.drag_started() {
+        let origin = ui.input(|input| input.pointer.press_origin());
+        if let Some(index) = origin.and_then(|origin| nearest(origin, points)) {
+            ui.data_mut(|data| data.insert_temp(drag_id, (index, view)));
+        }
+    }
+    if let (Some((index, view)), Some(pos)) = (dragged, response.interact_pointer_pos()) {
+        if response.dragged() && index < points.len() {
+            let point = on_curve(view.plane_pos(rect, pos));
+            points[index] = point;
+            points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+            // Keep hold of the point when it passes its neighbours.
+            let index = points.iter().position(|&other| other == point).unwrap_or(index);
+            ui.data_mut(|data| data.insert_temp(drag_id, (index, view)));
+            changed = true;
+        }
+    }
+    if response.drag_stopped() {
+        ui.data_mut(|data| data.remove::<(usize, CurveView)>(drag_id));
+    }
+    if let Some(pos) = response.interact_pointer_pos() {
+        if response.double_clicked() && nearest(pos, points).is_none() {
+            let point = on_curve(view.plane_pos(rect, pos));
+            let index = points.partition_point(|other| other[0] < point[0]);
+            points.insert(index, point);
+            changed = true;
+        }
+        if response.secondary_clicked() && points.len() > shape.min_points() {
+            if let Some(index) = nearest(pos, points) {
+                points.remove(index);
+                changed = true;
+            }
+        }
+    }
+
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+    if let Some(histogram) = histogram.filter(|histogram| histogram.min <= histogram.max) {
+        let most = histogram.counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let bin_width = (histogram.max - histogram.min) / histogram.counts.len() as f64;
+        for (bin, &count) in histogram.counts.iter().enumerate() {
+            let left = view.screen_pos(rect, [histogram.min + bin as f64 * bin_width, 0.0]).x;
+            let right = view.screen_pos(rect, [histogram.min + (bin + 1) as f64 * bin_width, 0.0]).x;
+            let top = rect.bottom() - count as f32 / most * rect.height();
+            let bar = egui::Rect::from_min_max(egui::pos2(left, top), egui::pos2(right.max(left + 1.0), rect.bottom()));
+            painter.rect_filled(bar, 0.0, visuals.widgets.inactive.bg_fill);
+        }
+    }
+    let axis = Stroke::new(1.0, visuals.weak_text_color().gamma_multiply(0.5));
+    let origin = view.screen_pos(rect, [0.0, 0.0]);
+    painter.hline(rect.x_range(), origin.y, axis);
+    painter.vline(origin.x, rect.y_range(), axis);
+    let steps = (rect.width() / 2.0).max(2.0) as usize;
+    let line: Vec<egui::Pos2> = (0..=steps)
+        .map(|step| {
+            let input = view.x.0 + (view.x.1 - view.x.0) * step as f64 / steps as f64;
+            let output = evaluate(points, input).clamp(view.y.0 - 1.0, view.y.1 + 1.0);
+            view.screen_pos(rect, [input, output])
+        })
+        .collect();
+    painter.with_clip_rect(rect).add(Shape::line(line, Stroke::new(2.0 * scale, visuals.selection.bg_fill)));
+    let hovered = response.hover_pos().and_then(|pos| nearest(pos, points));
+    let dragged = ui.data(|data| data.get_temp::<(usize, CurveView)>(drag_id)).map(|(index, _)| index);
+    for (index, &point) in points.iter().enumerate() {
+        let fill = if Some(index) == dragged || Some(index) == hovered { Color32::WHITE } else { visuals.text_color() };
+        painter.circle(view.screen_pos(rect, point), POINT_RADIUS * scale, fill, Stroke::new(1.0, visuals.extreme_bg_color));
+    }
+    if shape.usable_points(points).is_none() {
+        let message = format!("Needs at least {} points with different inputs", shape.min_points());
+        painter.text(rect.center(), egui::Align2::CENTER_CENTER, message, egui::FontId::default(), visuals.warn_fg_color);
+    }
+    response.on_hover_text("Drag points to move them, double-click to add one and right-click to delete one");
+    changed
+}
@@ -0,0 +1,498 @@
+//! Support for nodes that work on a grid of samples rather than point by point, such as erosion
+//! and blurs.
+//!
+//! A raster node samples its input over a square of the plane into a [`Grid`], processes the grid
+//! as a whole and is then sampled like any other noise, by interpolating the processed grid. The
+//! grid is only computed the first time the node is sampled, so compiling a graph stays cheap and
+//! the work happens on whichever thread renders it. Each node chooses the resolution and padding
+//! its input is sampled at, so raster nodes feeding each other are resampled at the consumer's.
+
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use noise::NoiseFn;
+
+use crate::{noises::DynNoise, params::{ParamKind, ParamSpec, ParamValues}};
+
+/// The most cells across the square a raster node works on. Resolutions typed past the slider's
+/// range are limited to this, as the grids would otherwise exhaust memory.
+const MAX_RESOLUTION: usize = 1024;
+
+/// Cells across the square a raster node works on. Raster nodes take time in proportion to the
+/// number of cells at least.
+pub const RESOLUTION: ParamSpec = ParamSpec {
+    name: "resolution",
+    label: "Resolution",
+    kind: ParamKind::Int { default: 256, min: 16, max: MAX_RESOLUTION as i64 },
+};
+
+/// Width of the square a raster node works on, in noise coordinates.
+pub const SIZE: ParamSpec = ParamSpec {
+    name: "size",
+    label: "Size",
+    kind: ParamKind::Float { default: 1.0, min: MIN_SIZE, max: MAX_SIZE, logarithmic: true },
+};
+
+/// Sizes typed past the slider's range are limited to it, as cells need a width to divide by.
+const MIN_SIZE: f64 = 0.01;
+const MAX_SIZE: f64 = 100.0;
+
+/// Raster nodes don't know what area their consumers sample, so they always work on the same one.
+#[cfg(feature = "gui")]
+pub const AREA_NOTE: &str = "Works on the square from the origin to (size, size), whatever area the \
+    preview or an export samples. Outside it, the values at the square's edges repeat.";
+
+/// A rectangle of values, stored row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    values: Vec<f64>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize, value: f64) -> Self {
+        Self { width, height, values: vec![value; width * height] }
+    }
+
+    pub fn from_fn(width: usize, height: usize, mut value: impl FnMut(usize, usize) -> f64) -> Self {
+        let values = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| value(x, y)).collect();
+        Self { width, height, values }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub fn values_mut(&mut self) -> &mut [f64] {
+        &mut self.values
+    }
+
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    /// The value of a cell, with cells past the edges taking the value of the nearest edge cell.
+    pub fn get(&self, x: isize, y: isize) -> f64 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.values[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: f64) {
+        let index = self.index(x, y);
+        self.values[index] = value;
+    }
+
+    /// Interpolates between the four cells around a position, measured in cells.
+    pub fn bilinear(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+        let top = self.get(x0, y0) + (self.get(x0 + 1, y0) - self.get(x0, y0)) * tx;
+        let bottom = self.get(x0, y0 + 1) + (self.get(x0 + 1, y0 + 1) - self.get(x0, y0 + 1)) * tx;
+        top + (bottom - top) * ty
+    }
+}
+
+/// The part of the plane a raster node samples, which is a square with its top left corner at the
+/// origin like plane previews, plus a border of padding cells around it.
+///
+/// Padding lets a node see past the square's edges, so that processes that look at a cell's
+/// neighbours don't treat the edges differently. Past the padding, the grid's edge cells repeat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RasterArea {
+    /// Cells across the square.
+    pub resolution: usize,
+    /// Width of the square in noise coordinates.
+    pub size: f64,
+    /// Cells added on every side.
+    pub padding: usize,
+}
+
+impl RasterArea {
+    /// The square set by a node's [`RESOLUTION`] and [`SIZE`], padded to reach a distance in
+    /// noise coordinates past its edges.
+    pub fn from_params(values: &ParamValues, reach: f64) -> Self {
+        let resolution = RESOLUTION.int(values).clamp(1, MAX_RESOLUTION as i64) as usize;
+        let size = match SIZE.float(values) {
+            size if size.is_nan() => MIN_SIZE,
+            size => size.clamp(MIN_SIZE, MAX_SIZE),
+        };
+        let mut area = Self { resolution, size, padding: 0 };
+        // Limited, so that huge reaches don't exhaust memory.
+        area.padding = area.cells(reach).ceil().clamp(0.0, resolution as f64) as usize;
+        area
+    }
+
+    /// Converts a distance in noise coordinates to cells.
+    pub fn cells(&self, distance: f64) -> f64 {
+        distance / self.cell_size()
+    }
+
+    /// Width of a cell in noise coordinates.
+    pub fn cell_size(&self) -> f64 {
+        self.size / self.resolution as f64
+    }
+
+    /// Cells across the grid, including padding.
+    pub fn side(&self) -> usize {
+        self.resolution + 2 * self.padding
+    }
+
+    /// Samples a noise at the centers of the grid's cells. Coordinates past the first two are 0.
+    pub fn sample<const DIM: usize>(&self, noise: &impl NoiseFn<f64, DIM>) -> Grid {
+        let side = self.side();
+        Grid::from_fn(side, side, |x, y| {
+            let [x, y] = [x, y].map(|cell| self.to_noise(cell as f64));
+            noise.get(std::array::from_fn(|axis| [x, y].get(axis).copied().unwrap_or(0.0)))
+        })
+    }
+
+    /// The noise coordinate of the center of a row or column of cells.
+    fn to_noise(self, cell: f64) -> f64 {
+        (cell - self.padding as f64 + 0.5) * self.cell_size()
+    }
+
+    /// The position of a noise coordinate in cells, the inverse of `to_noise`.
+    fn to_cells(self, coordinate: f64) -> f64 {
+        coordinate / self.cell_size() - 0.5 + self.padding as f64
+    }
+}
+
+impl Grid {
+    /// Blurs with a Gaussian kernel, whose standard deviation is in cells.
+    pub fn blur(&self, sigma: f64) -> Grid {
+        if sigma.is_nan() || sigma <= 0.0 {
+            return self.clone();
+        }
+        // Past the grid's size, the kernel only reaches further into repeated edge cells, and
+        // huge kernels would take forever.
+        let sigma = sigma.min(self.width.max(self.height) as f64);
+        let radius = (sigma * 3.0).ceil() as isize;
+        let weights: Vec<f64> = (-radius..=radius).map(|offset| (-(offset * offset) as f64 / (2.0 * sigma * sigma)).exp()).collect();
+        let total: f64 = weights.iter().sum();
+        let weights: Vec<f64> = weights.iter().map(|weight| weight / total).collect();
+        // Gaussians are separable, so blur rows and then columns.
+        let convolve = |grid: &Grid, [dx, dy]: [isize; 2]| {
+            Grid::from_fn(grid.width, grid.height, |x, y| {
+                (-radius..=radius)
+                    .zip(&weights)
+                    .map(|(offset, weight)| weight * grid.get(x as isize + offset * dx, y as isize + offset * dy))
+                    .sum()
+            })
+        };
+        convolve(&convolve(self, [1, 0]), [0, 1])
+    }
+
+    /// The steepness of each cell in height per cell, from a Sobel filter.
+    pub fn slopes(&self) -> Grid {
+        Grid::from_fn(self.width, self.height, |x, y| {
+            let cell = |dx: isize, dy: isize| self.get(x as isize + dx, y as isize + dy);
+            let dx = (cell(1, -1) + 2.0 * cell(1, 0) + cell(1, 1)) - (cell(-1, -1) + 2.0 * cell(-1, 0) + cell(-1, 1));
+            let dy = (cell(-1, 1) + 2.0 * cell(0, 1) + cell(1, 1)) - (cell(-1, -1) + 2.0 * cell(0, -1) + cell(1, -1));
+            dx.hypot(dy) / 8.0
+        })
+    }
+
+    /// The largest value within a radius in cells of each cell.
+    pub fn dilate(&self, radius: f64) -> Grid {
+        self.neighbourhood(radius, f64::max)
+    }
+
+    /// The smallest value within a radius in cells of each cell.
+    pub fn erode(&self, radius: f64) -> Grid {
+        self.neighbourhood(radius, f64::min)
+    }
+
+    /// Combines the values within a disc around each cell, as the rows of the disc's spans of
+    /// cells combined along each row of the grid.
+    fn neighbourhood(&self, radius: f64, combine: fn(f64, f64) -> f64) -> Grid {
+        // A disc as wide as the grid and its height together already covers every cell.
+        let radius = radius.clamp(0.0, (self.width + self.height) as f64);
+        let reach = radius.floor() as isize;
+        let half_widths: Vec<usize> = (-reach..=reach)
+            .map(|dy| (radius * radius - (dy * dy) as f64).sqrt().floor() as usize)
+            .collect();
+        let mut runs = BTreeMap::new();
+        for &half_width in &half_widths {
+            runs.entry(half_width).or_insert_with(|| self.runs(half_width, combine));
+        }
+        Grid::from_fn(self.width, self.height, |x, y| {
+            (-reach..=reach)
+                .zip(&half_widths)
+                .map(|(dy, half_width)| runs[half_width].get(x as isize, y as isize + dy))
+                .fold(self.get(x as isize, y as isize), combine)
+        })
+    }
+
+    /// Combines the values within a number of cells either side of each cell along its row, by
+    /// van Herk and Gil-Werman's method, which takes the same time whatever the width.
+    fn runs(&self, half_width: usize, combine: fn(f64, f64) -> f64) -> Grid {
+        let window = 2 * half_width + 1;
+        let mut runs = Grid::new(self.width, self.height, 0.0);
+        for y in 0..self.height {
+            let row: Vec<f64> = (0..self.width + 2 * half_width)
+                .map(|x| self.get(x as isize - half_width as isize, y as isize))
+                .collect();
+            // Splitting the row into blocks of the window's width, each window overlaps the end
+            // of one block and the start of the next.
+            let mut from_block_start = row.clone();
+            for x in 1..row.len() {
+                if x % window != 0 {
+                    from_block_start[x] = combine(from_block_start[x - 1], row[x]);
+                }
+            }
+            let mut to_block_end = row.clone();
+            for x in (0..row.len() - 1).rev() {
+                if (x + 1) % window != 0 {
+                    to_block_end[x] = combine(to_block_end[x + 1], row[x]);
+                }
+            }
+            for x in 0..self.width {
+                runs.set(x, y, combine(to_block_end[x], from_block_start[x + window - 1]));
+            }
+        }
+        runs
+    }
+
+    /// The distance in cells from each cell to the edge of the mask of cells for which `inside`
+    /// is true, negative inside the mask. Without cells both inside and out, every distance is
+    /// infinite.
+    pub fn signed_distances(&self, inside: impl Fn(f64) -> bool) -> Grid {
+        let mask: Vec<bool> = self.values.iter().map(|&value| inside(value)).collect();
+        let to_inside = self.distances_to(|index| mask[index]);
+        let to_outside = self.distances_to(|index| !mask[index]);
+        Grid {
+            width: self.width,
+            height: self.height,
+            values: to_inside.iter().zip(&to_outside).map(|(inside, outside)| inside - outside).collect(),
+        }
+    }
+
+    /// The exact Euclidean distance from each cell to the nearest chosen cell, by Felzenszwalb and
+    /// Huttenlocher's method of transforming columns and then rows.
+    fn distances_to(&self, chosen: impl Fn(usize) -> bool) -> Vec<f64> {
+        let mut squared: Vec<f64> = (0..self.values.len()).map(|index| if chosen(index) { 0.0 } else { f64::INFINITY }).collect();
+        let mut line = Vec::new();
+        for x in 0..self.width {
+            line.clear();
+            line.extend((0..self.height).map(|y| squared[self.index(x, y)]));
+            for (y, distance) in squared_distances(&line).into_iter().enumerate() {
+                let index = self.index(x, y);
+                squared[index] = distance;
+            }
+        }
+        for y in 0..self.height {
+            let row = y * self.width..(y + 1) * self.width;
+            let distances = squared_distances(&squared[row.clone()]);
+            squared[row].copy_from_slice(&distances);
+        }
+        squared.into_iter().map(f64::sqrt).collect()
+    }
+}
+
+/// The lower envelope of parabolas rooted at each position, at the height given there, which for
+/// squared distances along a line gives the squared distance to the nearest position along it.
+fn squared_distances(heights: &[f64]) -> Vec<f64> {
+    // Indices of the parabolas making up the envelope, and where each starts being the lowest.
+    let mut parabolas: Vec<usize> = Vec::with_capacity(heights.len());
+    let mut starts: Vec<f64> = Vec::with_capacity(heights.len());
+    for (position, &height) in heights.iter().enumerate() {
+        if height.is_infinite() {
+            continue;
+        }
+        let mut start = f64::NEG_INFINITY;
+        while let Some(&last) = parabolas.last() {
+            let crossing = ((height + (position * position) as f64) - (heights[last] + (last * last) as f64))
+                / (2.0 * (position - last) as f64);
+            if crossing > starts[starts.len() - 1] {
+                start = crossing;
+                break;
+            }
+            parabolas.pop();
+            starts.pop();
+        }
+        parabolas.push(position);
+        starts.push(start);
+    }
+    if parabolas.is_empty() {
+        return vec![f64::INFINITY; heights.len()];
+    }
+    let mut lowest = 0;
+    (0..heights.len())
+        .map(|position| {
+            while lowest + 1 < parabolas.len() && starts[lowest + 1] < position as f64 {
+                lowest += 1;
+            }
+            let root = parabolas[lowest];
+            let offset = position as f64 - root as f64;
+            offset * offset + heights[root]
+        })
+        .collect()
+}
+
+/// Processes a grid sampled from a raster node's input.
+type Process = Box<dyn Fn(Grid) -> Grid + Send + Sync>;
+
+/// A noise function backed by a processed grid of its input's values.
+///
+/// Only the first two coordinates are used, as the grid is a slice through the input with the
+/// other coordinates at 0.
+pub struct Raster<const DIM: usize> {
+    input: DynNoise<DIM>,
+    area: RasterArea,
+    process: Process,
+    grid: OnceLock<Grid>,
+}
+
+impl<const DIM: usize> Raster<DIM> {
+    pub fn new(input: DynNoise<DIM>, area: RasterArea, process: impl Fn(Grid) -> Grid + Send + Sync + 'static) -> Self {
+        Self { input, area, process: Box::new(process), grid: OnceLock::new() }
+    }
+
+    /// The processed grid, which is computed the first time it's needed.
+    pub fn grid(&self) -> &Grid {
+        self.grid.get_or_init(|| (self.process)(self.area.sample(&self.input)))
+    }
+}
+
+impl<const DIM: usize> NoiseFn<f64, DIM> for Raster<DIM> {
+    fn get(&self, point: [f64; DIM]) -> f64 {
+        let x = self.area.to_cells(point[0]);
+        let y = self.area.to_cells(point.get(1).copied().unwrap_or(0.0));
+        self.grid().bilinear(x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    /// Grids small enough to check against brute force, including single rows and columns.
+    fn random_grids() -> impl Iterator<Item = Grid> {
+        let mut rng = Rng::new(7);
+        [(1, 1), (1, 6), (5, 1), (4, 4), (7, 3), (9, 11)]
+            .into_iter()
+            .map(move |(width, height)| Grid::from_fn(width, height, |_, _| rng.next_f64() * 2.0 - 1.0))
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(actual == expected || (actual - expected).abs() < 1e-9, "{actual} != {expected}");
+        }
+    }
+
+    #[test]
+    fn squared_distances_match_brute_force() {
+        let lines: [&[f64]; 5] = [
+            &[0.0],
+            &[f64::INFINITY, f64::INFINITY],
+            &[f64::INFINITY, 0.0, f64::INFINITY, f64::INFINITY, f64::INFINITY, 0.0],
+            &[4.0, f64::INFINITY, 1.0, 0.0, 9.0, f64::INFINITY, 2.0],
+            &[0.0, 25.0, 16.0, 9.0, 4.0, 1.0, 0.0],
+        ];
+        for line in lines {
+            let expected: Vec<f64> = (0..line.len())
+                .map(|i| (0..line.len()).map(|j| (i as f64 - j as f64).powi(2) + line[j]).fold(f64::INFINITY, f64::min))
+                .collect();
+            assert_close(&squared_distances(line), &expected);
+        }
+    }
+
+    #[test]
+    fn signed_distances_match_brute_force() {
+        for grid in random_grids() {
+            let inside = |value: f64| value >= 0.0;
+            let nearest = |x: usize, y: usize, wanted: bool| {
+                (0..grid.height())
+                    .flat_map(|cy| (0..grid.width()).map(move |cx| (cx, cy)))
+                    .filter(|&(cx, cy)| inside(grid.get(cx as isize, cy as isize)) == wanted)
+                    .map(|(cx, cy)| (x as f64 - cx as f64).hypot(y as f64 - cy as f64))
+                    .fold(f64::INFINITY, f64::min)
+            };
+            let expected = Grid::from_fn(grid.width(), grid.height(), |x, y| nearest(x, y, true) - nearest(x, y, false));
+            let mask = grid.values().iter().map(|&value| inside(value));
+            if mask.clone().all(|inside| inside) || mask.clone().all(|inside| !inside) {
+                // Infinite on one side, which makes the difference NaN or infinite.
+                assert!(grid.signed_distances(inside).values().iter().all(|distance| !distance.is_finite()));
+                continue;
+            }
+            assert_close(grid.signed_distances(inside).values(), expected.values());
+        }
+    }
+
+    #[test]
+    fn runs_match_brute_force() {
+        for grid in random_grids() {
+            for half_width in [0, 1, 2, 5] {
+                let expected = Grid::from_fn(grid.width(), grid.height(), |x, y| {
+                    let x = x as isize;
+                    let half_width = half_width as isize;
+                    (x - half_width..=x + half_width).map(|cx| grid.get(cx, y as isize)).fold(f64::NEG_INFINITY, f64::max)
+                });
+                assert_eq!(grid.runs(half_width, f64::max), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn dilate_and_erode_match_brute_force() {
+        for grid in random_grids() {
+            for radius in [0.0, 0.5, 1.0, 1.5, 2.9, 4.2] {
+                let brute_force = |start: f64, combine: fn(f64, f64) -> f64| {
+                    let reach = radius as isize;
+                    Grid::from_fn(grid.width(), grid.height(), |x, y| {
+                        (-reach..=reach)
+                            .flat_map(|dy| (-reach..=reach).map(move |dx| (dx, dy)))
+                            .filter(|&(dx, dy)| ((dx * dx + dy * dy) as f64) <= radius * radius)
+                            .map(|(dx, dy)| grid.get(x as isize + dx, y as isize + dy))
+                            .fold(start, combine)
+                    })
+                };
+                assert_eq!(grid.dilate(radius), brute_force(f64::NEG_INFINITY, f64::max));
+                assert_eq!(grid.erode(radius), brute_force(f64::INFINITY, f64::min));
+            }
+        }
+    }
+
+    #[test]
+    fn huge_radii_cover_the_grid() {
+        for grid in random_grids() {
+            let highest = grid.values().iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            assert!(grid.dilate(1e12).values().iter().all(|&value| value == highest));
+            // The blur is limited to the grid's size rather than taking forever.
+            let blurred = grid.blur(1e12);
+            let lowest = grid.values().iter().copied().fold(f64::INFINITY, f64::min);
+            assert!(blurred.values().iter().all(|&value| (lowest - 1e-9..=highest + 1e-9).contains(&value)));
+        }
+    }
+
+    #[test]
+    fn blur_keeps_constant_grids() {
+        let grid = Grid::new(6, 4, 0.25);
+        assert_close(grid.blur(1.5).values(), grid.values());
+        assert_eq!(grid.blur(0.0), grid);
+        assert_eq!(grid.blur(f64::NAN), grid);
+    }
+
+    #[test]
+    fn sizes_past_the_slider_are_limited() {
+        for (size, expected) in [(0.0, MIN_SIZE), (-1.0, MIN_SIZE), (f64::NAN, MIN_SIZE), (1e9, MAX_SIZE), (2.0, 2.0)] {
+            let mut values = ParamValues::default();
+            values.set(SIZE.name, crate::params::ParamValue::Float(size));
+            let area = RasterArea::from_params(&values, 0.1);
+            assert_eq!(area.size, expected, "for {size}");
+            assert!(area.cells(0.1).is_finite());
+        }
+    }
+}
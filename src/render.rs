@@ -0,0 +1,364 @@
+use std::{f64::consts::{PI, TAU}, path::{Path, PathBuf}};
+
+use noise::{utils::{CylinderMapBuilder, NoiseMap, NoiseMapBuilder, PlaneMapBuilder, SphereMapBuilder}, NoiseFn, ScalePoint};
+use serde::{Deserialize, Serialize};
+use strum::VariantArray;
+
+use crate::noises::DynNoise;
+
+/// How the output image is mapped onto the noise domain.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, VariantArray, Serialize, Deserialize)]
+pub enum Projection {
+    /// The 2D noise sampled over a rectangle.
+    #[default]
+    Plane,
+    /// The 3D noise sampled around the side of a cylinder, so the output wraps horizontally.
+    Cylinder,
+    /// The 3D noise sampled over the surface of a sphere, laid out as an equirectangular
+    /// (longitude/latitude) map with north at the top.
+    Sphere,
+}
+
+impl Projection {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Projection::Plane => "Plane",
+            Projection::Cylinder => "Cylinder",
+            Projection::Sphere => "Sphere",
+        }
+    }
+
+    /// Width of the output image divided by its height.
+    pub const fn aspect_ratio(&self) -> usize {
+        match self {
+            Projection::Plane | Projection::Cylinder => 1,
+            Projection::Sphere => 2,
+        }
+    }
+}
+
+/// How many dimensions a noise is evaluated in. Previews and exports show a 2D slice through the
+/// higher dimensional noises, positioned by [`SampleSettings::z`] and [`SampleSettings::w`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, VariantArray, Serialize, Deserialize)]
+pub enum Dimensions {
+    #[default]
+    Two,
+    Three,
+    Four,
+}
+
+impl Dimensions {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Dimensions::Two => "2D",
+            Dimensions::Three => "3D",
+            Dimensions::Four => "4D",
+        }
+    }
+
+    pub const fn count(&self) -> usize {
+        match self {
+            Dimensions::Two => 2,
+            Dimensions::Three => 3,
+            Dimensions::Four => 4,
+        }
+    }
+}
+
+/// Width of the previews shown in the editor.
+pub const PREVIEW_SIZE: usize = 256;
+
+/// A node's noise function built for each dimension it can be sampled in.
+pub struct CompiledNoise {
+    pub noise_2d: DynNoise<2>,
+    pub noise_3d: DynNoise<3>,
+    pub noise_4d: DynNoise<4>,
+}
+
+/// Samples a 4D noise in 3D, with the fourth coordinate fixed.
+struct FixedW<'a> {
+    noise: &'a DynNoise<4>,
+    w: f64,
+}
+
+impl NoiseFn<f64, 3> for FixedW<'_> {
+    fn get(&self, [x, y, z]: [f64; 3]) -> f64 {
+        self.noise.get([x, y, z, self.w])
+    }
+}
+
+/// Describes how a noise function is sampled into a grid of values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SampleSettings {
+    pub width: usize,
+    pub height: usize,
+    /// Extent of the noise domain covered by the width of the image. For the cylinder and sphere
+    /// projections this is the circumference.
+    pub noise_width: f64,
+    pub noise_height: f64,
+    /// Blend the samples across the edges of the domain so that the output wraps seamlessly. Only
+    /// applies to the plane projection, the others already wrap.
+    pub tileable: bool,
+    pub projection: Projection,
+    #[serde(default)]
+    pub dimensions: Dimensions,
+    /// Position of the plane projection's slice along the z axis, when sampling in 3D or 4D.
+    /// Animations step through time by moving along this axis.
+    #[serde(default)]
+    pub z: f64,
+    /// Position of the slice along the w axis, when sampling in 4D. The cylinder and sphere
+    /// projections already use three dimensions, so this is the only offset that applies to them.
+    #[serde(default)]
+    pub w: f64,
+}
+
+impl SampleSettings {
+    /// Settings for an image `width` pixels wide, with the height chosen to suit the projection.
+    pub fn new(width: usize, tileable: bool, projection: Projection) -> Self {
+        Self {
+            width,
+            height: width / projection.aspect_ratio(),
+            noise_width: 1.0,
+            noise_height: 1.0 / projection.aspect_ratio() as f64,
+            tileable,
+            projection,
+            dimensions: Dimensions::Two,
+            z: 0.0,
+            w: 0.0,
+        }
+    }
+
+    pub fn preview(tileable: bool, projection: Projection) -> Self {
+        Self::new(PREVIEW_SIZE, tileable, projection)
+    }
+}
+
+/// Samples the noise function according to the settings' projection.
+pub fn sample(noise: &CompiledNoise, settings: &SampleSettings) -> NoiseMap {
+    // The cylinder and sphere builders work on the unit shapes, so scale the points such that
+    // one revolution spans the same distance as the width of the plane.
+    let noise_3d: &dyn NoiseFn<f64, 3> = match settings.dimensions {
+        Dimensions::Two | Dimensions::Three => &noise.noise_3d,
+        Dimensions::Four => &FixedW { noise: &noise.noise_4d, w: settings.w },
+    };
+    let scaled_3d = || ScalePoint::new(noise_3d).set_scale(settings.noise_width / TAU);
+    match settings.projection {
+        Projection::Plane => PlaneMapBuilder::new_fn(|[x, y]| match settings.dimensions {
+                Dimensions::Two => noise.noise_2d.get([x, y]),
+                Dimensions::Three => noise.noise_3d.get([x, y, settings.z]),
+                Dimensions::Four => noise.noise_4d.get([x, y, settings.z, settings.w]),
+            })
+            .set_size(settings.width, settings.height)
+            .set_x_bounds(0.0, settings.noise_width)
+            .set_y_bounds(0.0, settings.noise_height)
+            .set_is_seamless(settings.tileable)
+            .build(),
+        Projection::Cylinder => {
+            // Keep the pixels square: the unit cylinder's circumference is 2π.
+            let half_height = PI * settings.height as f64 / settings.width as f64;
+            CylinderMapBuilder::new(scaled_3d())
+                .set_size(settings.width, settings.height)
+                .set_angle_bounds(-180.0, 180.0)
+                .set_height_bounds(-half_height, half_height)
+                .build()
+        }
+        Projection::Sphere => SphereMapBuilder::new(scaled_3d())
+            .set_size(settings.width, settings.height)
+            .set_bounds(90.0, -90.0, -180.0, 180.0)
+            .build(),
+    }
+}
+
+/// An axis aligned plane through a 3D volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, VariantArray)]
+pub enum SliceAxis {
+    Xy,
+    Xz,
+    Yz,
+}
+
+impl SliceAxis {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            SliceAxis::Xy => "XY",
+            SliceAxis::Xz => "XZ",
+            SliceAxis::Yz => "YZ",
+        }
+    }
+
+    /// Index of the axis the slice is perpendicular to.
+    pub const fn normal(&self) -> usize {
+        match self {
+            SliceAxis::Xy => 2,
+            SliceAxis::Xz => 1,
+            SliceAxis::Yz => 0,
+        }
+    }
+
+    /// Indices of the axes along the slice's width and height.
+    pub const fn plane_axes(&self) -> [usize; 2] {
+        match self {
+            SliceAxis::Xy => [0, 1],
+            SliceAxis::Xz => [0, 2],
+            SliceAxis::Yz => [1, 2],
+        }
+    }
+
+    /// Converts a point on the slice to the point in the volume, with the slice `position` along
+    /// its normal.
+    pub fn point(&self, [a, b]: [f64; 2], position: f64) -> [f64; 3] {
+        let mut point = [position; 3];
+        let [u, v] = self.plane_axes();
+        point[u] = a;
+        point[v] = b;
+        point
+    }
+}
+
+/// Samples a slice through the noise's volume, using the settings' size and extent. The
+/// projection and z position are ignored, and 2D noise is sampled in 3D.
+pub fn sample_slice(noise: &CompiledNoise, settings: &SampleSettings, axis: SliceAxis, position: f64) -> NoiseMap {
+    PlaneMapBuilder::new_fn(|point| {
+            let [x, y, z] = axis.point(point, position);
+            match settings.dimensions {
+                Dimensions::Two | Dimensions::Three => noise.noise_3d.get([x, y, z]),
+                Dimensions::Four => noise.noise_4d.get([x, y, z, settings.w]),
+            }
+        })
+        .set_size(settings.width, settings.height)
+        .set_x_bounds(0.0, settings.noise_width)
+        .set_y_bounds(0.0, settings.noise_height)
+        .build()
+}
+
+/// Animates a noise by using its third dimension as time, so that `t` seconds in the slice is moved
+/// t · speed along the z axis. 2D noises are sampled in 3D to animate them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Animation {
+    /// Length of the animation in seconds.
+    pub duration: f64,
+    pub frames_per_second: f64,
+    /// Distance travelled along the z axis per second.
+    pub speed: f64,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self { duration: 4.0, frames_per_second: 25.0, speed: 0.25 }
+    }
+}
+
+impl Animation {
+    pub fn frame_count(&self) -> usize {
+        (self.duration * self.frames_per_second).round().max(1.0) as usize
+    }
+
+    /// The z coordinate sampled at a time in seconds.
+    pub fn z_at(&self, time: f64) -> f64 {
+        time * self.speed
+    }
+
+    /// Moves the settings' slice to where it is at a time in seconds.
+    pub fn settings_at(&self, settings: &SampleSettings, time: f64) -> SampleSettings {
+        SampleSettings {
+            dimensions: settings.dimensions.max(Dimensions::Three),
+            z: settings.z + self.z_at(time),
+            ..*settings
+        }
+    }
+
+    /// Samples every frame of the animation. Only the plane projection is animated.
+    pub fn sample_frames<'a>(&'a self, noise: &'a CompiledNoise, settings: &'a SampleSettings) -> impl Iterator<Item = NoiseMap> + 'a {
+        (0..self.frame_count()).map(move |frame| {
+            let time = frame as f64 / self.frames_per_second;
+            sample(noise, &self.settings_at(settings, time))
+        })
+    }
+}
+
+/// Returns the smallest and largest values in the map.
+pub fn value_range(map: &NoiseMap) -> (f64, f64) {
+    map.iter().fold((f64::MAX, f64::MIN), |(min, max), &value| (min.min(value), max.max(value)))
+}
+
+/// How many of a map's values fall into each of several equal ranges between its smallest and
+/// largest values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<u32>,
+}
+
+/// Counts the map's values into `bins` ranges. Values that aren't finite are left out.
+pub fn histogram(map: &NoiseMap, bins: usize) -> Histogram {
+    let finite = || map.iter().copied().filter(|value| value.is_finite());
+    let (min, max) = finite().fold((f64::MAX, f64::MIN), |(min, max), value| (min.min(value), max.max(value)));
+    let mut counts = vec![0; bins];
+    if min <= max {
+        let width = (max - min).max(f64::EPSILON);
+        for value in finite() {
+            let bin = ((value - min) / width * bins as f64) as usize;
+            counts[bin.min(bins - 1)] += 1;
+        }
+    }
+    Histogram { min, max, counts }
+}
+
+/// Converts a noise value to a gray level. This assumes the noise is in the range [-1, 1].
+pub fn to_gray(value: f64) -> u8 {
+    ((value * 0.5 + 0.5).clamp(0.0, 1.0) * 255.0) as u8
+}
+
+/// Converts a noise value to a 16 bit height. This assumes the noise is in the range [-1, 1].
+pub fn to_height(value: f64) -> u16 {
+    ((value * 0.5 + 0.5).clamp(0.0, 1.0) * u16::MAX as f64) as u16
+}
+
+pub fn to_gray_image(map: &NoiseMap) -> image::GrayImage {
+    let (width, height) = map.size();
+    image::GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        image::Luma([to_gray(map.get_value(x as usize, y as usize))])
+    })
+}
+
+/// Converts the map to a 16 bit grayscale heightmap, which preserves much more detail than
+/// [`to_gray_image`].
+pub fn to_heightmap_image(map: &NoiseMap) -> image::ImageBuffer<image::Luma<u16>, Vec<u16>> {
+    let (width, height) = map.size();
+    image::ImageBuffer::from_fn(width as u32, height as u32, |x, y| {
+        image::Luma([to_height(map.get_value(x as usize, y as usize))])
+    })
+}
+
+/// Converts the map to unscaled little endian 32 bit floats in row-major order, for tools that
+/// accept raw heightmaps.
+pub fn to_raw_f32(map: &NoiseMap) -> Vec<u8> {
+    map.iter().flat_map(|&value| (value as f32).to_le_bytes()).collect()
+}
+
+/// Writes the maps as the frames of a looping animated GIF.
+pub fn write_gif(frames: impl Iterator<Item = NoiseMap>, frames_per_second: f64, path: &Path) -> image::ImageResult<()> {
+    use image::{codecs::gif::{GifEncoder, Repeat}, Delay, DynamicImage, Frame};
+
+    let mut encoder = GifEncoder::new_with_speed(std::io::BufWriter::new(std::fs::File::create(path)?), 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delay = Delay::from_saturating_duration(std::time::Duration::from_secs_f64(1.0 / frames_per_second));
+    encoder.encode_frames(frames.map(|map| {
+        Frame::from_parts(DynamicImage::ImageLuma8(to_gray_image(&map)).into_rgba8(), 0, 0, delay)
+    }))
+}
+
+/// Writes each map to its own 8 bit grayscale PNG, numbered as described by [`sequence_path`].
+pub fn write_png_sequence(frames: impl Iterator<Item = NoiseMap>, path: &Path) -> image::ImageResult<()> {
+    for (frame, map) in frames.enumerate() {
+        to_gray_image(&map).save_with_format(sequence_path(path, frame), image::ImageFormat::Png)?;
+    }
+    Ok(())
+}
+
+/// Numbers a path for a frame of a sequence, e.g. `clouds.png` becomes `clouds_0007.png`.
+pub fn sequence_path(path: &Path, frame: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}_{frame:04}.png"))
+}